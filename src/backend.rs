@@ -0,0 +1,120 @@
+use crate::mode::{DisplayMode, Position, RefreshRate};
+
+// `DISPLAY_DEVICEW::StateFlags` bits, mirrored from wingdi.h so they are
+// usable without winapi.
+pub const DISPLAY_DEVICE_ATTACHED_TO_DESKTOP: u32 = 0x0000_0001;
+pub const DISPLAY_DEVICE_PRIMARY_DEVICE: u32 = 0x0000_0004;
+pub const DISPLAY_DEVICE_MIRRORING_DRIVER: u32 = 0x0000_0008;
+pub const DISPLAY_DEVICE_ACTIVE: u32 = 0x0000_0001;
+pub const DISPLAY_DEVICE_ATTACHED: u32 = 0x0000_0002;
+
+// `ChangeDisplaySettingsExW` return codes, mirrored from winuser.h.
+pub const DISP_CHANGE_SUCCESSFUL: i32 = 0;
+pub const DISP_CHANGE_RESTART: i32 = 1;
+pub const DISP_CHANGE_FAILED: i32 = -1;
+pub const DISP_CHANGE_BADMODE: i32 = -2;
+pub const DISP_CHANGE_NOTUPDATED: i32 = -3;
+pub const DISP_CHANGE_BADFLAGS: i32 = -4;
+pub const DISP_CHANGE_BADPARAM: i32 = -5;
+pub const DISP_CHANGE_BADDUALVIEW: i32 = -6;
+
+// `ChangeDisplaySettingsExW` flags, mirrored from winuser.h.
+pub const CDS_UPDATEREGISTRY: u32 = 0x0000_0001;
+pub const CDS_TEST: u32 = 0x0000_0002;
+pub const CDS_GLOBAL: u32 = 0x0000_0008;
+pub const CDS_SET_PRIMARY: u32 = 0x0000_0010;
+pub const CDS_NORESET: u32 = 0x1000_0000;
+
+// `SetDisplayConfig` flags, mirrored from wingdi.h.
+pub const SDC_USE_SUPPLIED_DISPLAY_CONFIG: u32 = 0x0000_0020;
+pub const SDC_VALIDATE: u32 = 0x0000_0040;
+pub const SDC_APPLY: u32 = 0x0000_0080;
+pub const SDC_SAVE_TO_DATABASE: u32 = 0x0000_0200;
+pub const SDC_ALLOW_CHANGES: u32 = 0x0000_0400;
+
+/// The fields of a `DISPLAY_DEVICEW` the library cares about, for either an
+/// adapter output or a monitor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RawDisplayDevice {
+    pub device_name: String,
+    pub device_string: String,
+    pub state_flags: u32,
+    /// `DeviceID` as returned without flags, e.g. `MONITOR\DEL4123\{4d36e96e-...}\0001`.
+    pub device_id: String,
+    /// `DeviceID` as returned with `EDD_GET_DEVICE_INTERFACE_NAME`, e.g.
+    /// `\\?\DISPLAY#DEL4123#5&1a2b3c&0&UID4353#{e6f07b5f-...}`. Empty for adapters.
+    pub interface_path: String,
+}
+
+/// A devnode of the monitor device class as seen by SetupAPI.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitorDevNode {
+    /// Device instance ID, e.g. `DISPLAY\DEL4123\5&1a2b3c&0&UID4353`.
+    pub instance_id: String,
+    /// `CM_DRP_HARDWAREID` entries, e.g. `MONITOR\DEL4123`.
+    pub hardware_ids: Vec<String>,
+    /// `CM_DRP_DRIVER`, e.g. `{4d36e96e-e325-11ce-bfc1-08002be10318}\0001`.
+    pub driver_key: Option<String>,
+    pub friendly_name: Option<String>,
+    pub device_desc: Option<String>,
+}
+
+/// Everything the library needs from the operating system's display stack.
+///
+/// `Win32Backend` talks to the real APIs; `FakeBackend` serves scripted data
+/// so the rest of the crate can run anywhere.
+pub trait DisplayBackend: Send + Sync {
+    /// Display adapter outputs, attached or not (`EnumDisplayDevicesW(NULL, ..)`).
+    fn adapters(&self) -> Vec<RawDisplayDevice>;
+
+    /// Monitors connected to the adapter output `adapter_name`.
+    fn monitors(&self, adapter_name: &str) -> Vec<RawDisplayDevice>;
+
+    /// Present devnodes of the monitor device class. The error is the OS
+    /// error code of the failed SetupAPI call.
+    fn monitor_devnodes(&self) -> Result<Vec<MonitorDevNode>, u32>;
+
+    /// The raw EDID of the monitor with the given device interface path, as
+    /// stored under its devnode's `Device Parameters` registry key.
+    fn monitor_edid(&self, interface_path: &str) -> Option<Vec<u8>>;
+
+    /// Every mode the driver lists for `device_name`, in driver order.
+    fn modes(&self, device_name: &str) -> Vec<DisplayMode>;
+
+    /// The mode `device_name` is currently running. The error is the OS
+    /// error code of the failed query.
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode, u32>;
+
+    /// Applies `mode` to `device_name` with the given `CDS_*` flags and
+    /// returns the raw `DISP_CHANGE_*` code.
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode, flags: u32) -> i32;
+
+    /// Where `device_name` currently sits on the virtual desktop. The error
+    /// is the OS error code of the failed query.
+    fn current_position(&self, device_name: &str) -> Result<Position, u32>;
+
+    /// Like `apply_mode`, but also moves `device_name` to `position`
+    /// (`DM_POSITION`). Pass `CDS_SET_PRIMARY` to make it the primary display.
+    fn apply_layout(
+        &self,
+        device_name: &str,
+        mode: &DisplayMode,
+        position: Position,
+        flags: u32,
+    ) -> i32;
+
+    /// Applies every mode staged with `CDS_NORESET` in one go
+    /// (`ChangeDisplaySettingsExW(NULL, NULL, ..)`) and returns the raw
+    /// `DISP_CHANGE_*` code.
+    fn commit_modes(&self) -> i32;
+
+    /// The exact refresh rate of the active display path whose source is
+    /// `device_name` (`QueryDisplayConfig`). The error is the Win32 error code
+    /// of the failed query.
+    fn current_refresh_rate(&self, device_name: &str) -> Result<RefreshRate, u32>;
+
+    /// Asks for `rate` on the active display path whose source is
+    /// `device_name`, keeping its resolution (`SetDisplayConfig` with the
+    /// given `SDC_*` flags). Returns the Win32 error code, 0 on success.
+    fn apply_refresh_rate(&self, device_name: &str, rate: RefreshRate, flags: u32) -> u32;
+}
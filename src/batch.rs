@@ -0,0 +1,270 @@
+//! Atomic mode changes across several monitors.
+//!
+//! Each device's new mode is staged with `CDS_NORESET`, plus
+//! `CDS_UPDATEREGISTRY` unless the change is temporary, and all of them are
+//! applied by a single `ChangeDisplaySettingsExW(NULL, ..)`, so the screens
+//! blank once and either every monitor switches or none does.
+
+use log::{debug, info, warn};
+
+use crate::backend::{
+    DisplayBackend, CDS_NORESET, CDS_SET_PRIMARY, CDS_TEST, DISPLAY_DEVICE_PRIMARY_DEVICE,
+    DISP_CHANGE_RESTART, DISP_CHANGE_SUCCESSFUL,
+};
+use crate::error::{check_change_code, ChangeOutcome, DisplayError};
+use crate::mode::{DisplayMode, Position};
+use crate::options::ChangeOptions;
+use crate::refresh_rate_mode;
+
+/// What a batch entry asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// A refresh rate at the current resolution and colour depth.
+    RefreshRate(u32),
+    /// A complete mode.
+    Mode(DisplayMode),
+    /// A complete mode at a desktop position, optionally as the primary display.
+    Layout {
+        mode: DisplayMode,
+        position: Position,
+        primary: bool,
+    },
+}
+
+/// Mode changes for several devices, applied together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisplayBatch {
+    changes: Vec<(String, Target)>,
+}
+
+/// A device the batch switches, with the mode to go back to.
+struct Staged<'a> {
+    device_name: &'a str,
+    previous: DisplayMode,
+    previous_position: Option<Position>,
+    mode: DisplayMode,
+    position: Option<Position>,
+    primary: bool,
+}
+
+impl Staged<'_> {
+    fn stage(&self, backend: &dyn DisplayBackend, flags: u32) -> i32 {
+        let flags = if self.primary {
+            flags | CDS_SET_PRIMARY
+        } else {
+            flags
+        };
+        match self.position {
+            Some(position) => backend.apply_layout(self.device_name, &self.mode, position, flags),
+            None => backend.apply_mode(self.device_name, &self.mode, flags),
+        }
+    }
+
+    /// Stages the previous mode and position again. The primary display is
+    /// not moved back.
+    fn unstage(&self, backend: &dyn DisplayBackend, flags: u32) -> i32 {
+        match self.previous_position {
+            Some(position) => {
+                backend.apply_layout(self.device_name, &self.previous, position, flags)
+            }
+            None => backend.apply_mode(self.device_name, &self.previous, flags),
+        }
+    }
+}
+
+impl DisplayBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switches `device_name` to `refresh_rate` at its current resolution and
+    /// colour depth. A later entry for the same device replaces this one.
+    pub fn refresh_rate(self, device_name: &str, refresh_rate: u32) -> Self {
+        self.with(device_name, Target::RefreshRate(refresh_rate))
+    }
+
+    /// Switches `device_name` to `mode`. A later entry for the same device
+    /// replaces this one.
+    pub fn mode(self, device_name: &str, mode: DisplayMode) -> Self {
+        self.with(device_name, Target::Mode(mode))
+    }
+
+    /// Switches `device_name` to `mode` and moves it to `position` on the
+    /// virtual desktop, making it the primary display if `primary` is set. A
+    /// later entry for the same device replaces this one.
+    pub fn layout(
+        self,
+        device_name: &str,
+        mode: DisplayMode,
+        position: Position,
+        primary: bool,
+    ) -> Self {
+        self.with(
+            device_name,
+            Target::Layout {
+                mode,
+                position,
+                primary,
+            },
+        )
+    }
+
+    fn with(mut self, device_name: &str, target: Target) -> Self {
+        self.changes.retain(|(name, _)| name != device_name);
+        self.changes.push((device_name.to_string(), target));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Devices in the batch, in the order they were added.
+    pub fn device_names(&self) -> impl Iterator<Item = &str> {
+        self.changes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Applies every change at once, with the default backend.
+    #[cfg(windows)]
+    pub fn apply(&self, options: ChangeOptions) -> Result<ChangeOutcome, DisplayError> {
+        self.apply_with(&crate::Win32Backend, options)
+    }
+
+    /// Applies every change at once. If a device refuses its mode, the
+    /// devices staged before it are staged back to their previous modes and
+    /// nothing is applied.
+    ///
+    /// With `Persistence::Temporary` the modes are staged without
+    /// `CDS_UPDATEREGISTRY`, so nothing is written to the registry.
+    pub fn apply_with(
+        &self,
+        backend: &dyn DisplayBackend,
+        options: ChangeOptions,
+    ) -> Result<ChangeOutcome, DisplayError> {
+        // Resolve every target before touching anything.
+        let mut staged = Vec::new();
+        for (device_name, target) in &self.changes {
+            let previous = backend.current_mode(device_name).map_err(|os_error| {
+                DisplayError::CurrentModeUnavailable {
+                    device_name: device_name.clone(),
+                    os_error,
+                }
+            })?;
+            let mut change = Staged {
+                device_name,
+                previous,
+                previous_position: None,
+                mode: previous,
+                position: None,
+                primary: false,
+            };
+            match *target {
+                Target::RefreshRate(rate) if rate == previous.frequency => {}
+                Target::RefreshRate(rate) => {
+                    change.mode = refresh_rate_mode(backend, device_name, &previous, rate)?;
+                }
+                Target::Mode(mode) => change.mode = mode,
+                Target::Layout {
+                    mode,
+                    position,
+                    primary,
+                } => {
+                    let previous_position =
+                        backend.current_position(device_name).map_err(|os_error| {
+                            DisplayError::CurrentModeUnavailable {
+                                device_name: device_name.clone(),
+                                os_error,
+                            }
+                        })?;
+                    change.mode = mode;
+                    change.previous_position = Some(previous_position);
+                    change.position = Some(position);
+                    change.primary = primary && !is_primary(backend, device_name);
+                }
+            }
+            if change.mode != change.previous
+                || change.position != change.previous_position
+                || change.primary
+            {
+                staged.push(change);
+            }
+        }
+        if staged.is_empty() {
+            info!("Every device in the batch is already at its mode, no change needed");
+            return Ok(ChangeOutcome::Unchanged);
+        }
+
+        if options.dry_run {
+            return validate(backend, &staged);
+        }
+
+        let flags = CDS_NORESET | options.persistence.cds_flags();
+        let mut outcome = ChangeOutcome::Applied;
+        for (index, change) in staged.iter().enumerate() {
+            let code = change.stage(backend, flags);
+            match check_change_code(change.device_name, code) {
+                Ok(ChangeOutcome::RestartRequired) => outcome = ChangeOutcome::RestartRequired,
+                Ok(_) => {}
+                Err(error) => {
+                    debug!(device = change.device_name, mode = change.mode.to_string(), code = code; "Staging failed, rolling back the batch");
+                    unstage(backend, &staged[..index], flags);
+                    return Err(error);
+                }
+            }
+        }
+
+        let code = backend.commit_modes();
+        match code {
+            DISP_CHANGE_SUCCESSFUL => {}
+            DISP_CHANGE_RESTART => outcome = ChangeOutcome::RestartRequired,
+            code => {
+                warn!(code = code; "Applying the staged modes failed, rolling back the batch");
+                unstage(backend, &staged, flags);
+                backend.commit_modes();
+                return Err(DisplayError::CommitFailed {
+                    device_names: staged.iter().map(|c| c.device_name.to_string()).collect(),
+                    code,
+                });
+            }
+        }
+
+        for change in &staged {
+            info!(device = change.device_name, mode = change.mode.to_string(), persistence = options.persistence.as_str(); "Changed display mode");
+        }
+        Ok(outcome)
+    }
+}
+
+/// Asks the driver whether it would accept every change in `staged`.
+fn validate(
+    backend: &dyn DisplayBackend,
+    staged: &[Staged],
+) -> Result<ChangeOutcome, DisplayError> {
+    let mut outcome = ChangeOutcome::Validated;
+    for change in staged {
+        let code = change.stage(backend, CDS_TEST);
+        if check_change_code(change.device_name, code)? == ChangeOutcome::RestartRequired {
+            outcome = ChangeOutcome::RestartRequired;
+        }
+    }
+    Ok(outcome)
+}
+
+/// Whether `device_name` is the primary adapter output.
+fn is_primary(backend: &dyn DisplayBackend, device_name: &str) -> bool {
+    backend
+        .adapters()
+        .iter()
+        .any(|a| a.device_name == device_name && a.state_flags & DISPLAY_DEVICE_PRIMARY_DEVICE != 0)
+}
+
+/// Stages the previous modes of `staged` again, so what is staged, and for
+/// persistent changes the registry, matches what is on screen.
+fn unstage(backend: &dyn DisplayBackend, staged: &[Staged], flags: u32) {
+    for change in staged {
+        let code = change.unstage(backend, flags);
+        if let Err(error) = check_change_code(change.device_name, code) {
+            warn!(device = change.device_name, mode = change.previous.to_string(); "Could not roll back the staged mode: {}", error);
+        }
+    }
+}
@@ -0,0 +1,20 @@
+//! Command-line counterpart of the tray, see `refresh_rate_windows_rs::cli`.
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("refresh-rate only runs on Windows.");
+    std::process::exit(1);
+}
+
+#[cfg(windows)]
+fn main() {
+    use refresh_rate_windows_rs::{cli, Win32Backend};
+
+    let code = cli::main_with(
+        &Win32Backend,
+        std::env::args().skip(1),
+        &mut std::io::stdout().lock(),
+        &mut std::io::stderr().lock(),
+    );
+    std::process::exit(code);
+}
@@ -0,0 +1,476 @@
+//! The `refresh-rate` command line, for scripts and shortcuts:
+//!
+//! ```text
+//! refresh-rate list [--json]
+//! refresh-rate get [<monitor>]
+//! refresh-rate set <monitor> <rate> [--dry-run] [--persistence=<persistence>]
+//! refresh-rate modes <monitor>
+//! refresh-rate primary
+//! ```
+//!
+//! Every command takes `--json` to write NDJSON instead of text, see
+//! `crate::schema`.
+//!
+//! `<monitor>` is the number `list` shows, an adapter output such as
+//! `DISPLAY1` or `\\.\DISPLAY1`, or a stable identifier as accepted by
+//! `DisplayDevice::matches_id`. The process exit code tells failures apart,
+//! see `CliError::exit_code`.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::backend::DisplayBackend;
+use crate::error::{ChangeOutcome, DisplayError};
+use crate::mode::RefreshRate;
+use crate::options::{ChangeOptions, Persistence};
+use crate::schema::Record;
+use crate::{
+    get_all_display_devices_with, get_exact_refresh_rates_with,
+    get_primary_display_device_name_with, set_display_refresh_rate_with,
+    set_exact_refresh_rate_with, DisplayDevice,
+};
+
+pub const EXIT_SUCCESS: i32 = 0;
+/// Displays could not be enumerated or read, or the output could not be written.
+pub const EXIT_FAILURE: i32 = 1;
+/// Unknown command, missing argument or invalid value.
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_MONITOR_NOT_FOUND: i32 = 3;
+/// The monitor does not offer the requested rate.
+pub const EXIT_UNSUPPORTED: i32 = 4;
+/// The driver or `SetDisplayConfig` refused the change.
+pub const EXIT_CHANGE_FAILED: i32 = 5;
+/// The change was accepted but only takes effect after a restart.
+pub const EXIT_RESTART_REQUIRED: i32 = 6;
+
+pub const USAGE: &str = "\
+Usage: refresh-rate <command>
+
+Commands:
+  list                    List connected monitors
+  get [<monitor>]         Show the current refresh rate
+  set <monitor> <rate>    Switch to a refresh rate, e.g. 144 or 59.94
+  modes <monitor>         List the refresh rates at the current resolution
+  primary                 Show the primary monitor
+  help                    Show this help
+
+Options:
+  --json                       Write one JSON record per line
+  --dry-run                    set: only check that the driver accepts the rate
+  --persistence=<persistence>  set: temporary, persistent or global
+
+<monitor> is the number shown by `list`, an output such as DISPLAY1, or a
+monitor ID such as DEL4123.";
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    List,
+    /// `None` shows every monitor.
+    Get {
+        monitor: Option<String>,
+    },
+    Set {
+        monitor: String,
+        rate: RefreshRate,
+        options: ChangeOptions,
+    },
+    Modes {
+        monitor: String,
+    },
+    Primary,
+    Help,
+}
+
+/// Why a command failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    Usage(String),
+    Display(DisplayError),
+    Output(io::ErrorKind),
+}
+
+impl CliError {
+    /// The process exit code for this failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Usage(_) => EXIT_USAGE,
+            CliError::Display(error) => exit_code(error),
+            CliError::Output(_) => EXIT_FAILURE,
+        }
+    }
+}
+
+/// The process exit code for a failed display query or change.
+pub fn exit_code(error: &DisplayError) -> i32 {
+    match error {
+        DisplayError::MonitorNotFound { .. } => EXIT_MONITOR_NOT_FOUND,
+        DisplayError::UnsupportedMode { .. }
+        | DisplayError::UnsupportedRefreshRate { .. }
+        | DisplayError::BadMode { .. } => EXIT_UNSUPPORTED,
+        DisplayError::DisplayConfigFailed { .. }
+        | DisplayError::ChangeFailed { .. }
+        | DisplayError::NotUpdated { .. }
+        | DisplayError::BadFlags { .. }
+        | DisplayError::BadParam { .. }
+        | DisplayError::BadDualView { .. }
+        | DisplayError::UnknownChangeCode { .. }
+        | DisplayError::CommitFailed { .. } => EXIT_CHANGE_FAILED,
+        DisplayError::SetupApi { .. }
+        | DisplayError::WatcherUnavailable { .. }
+        | DisplayError::CurrentModeUnavailable { .. } => EXIT_FAILURE,
+    }
+}
+
+impl From<DisplayError> for CliError {
+    fn from(error: DisplayError) -> Self {
+        CliError::Display(error)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> Self {
+        CliError::Output(error.kind())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => f.write_str(message),
+            CliError::Display(error) => write!(f, "{}", error),
+            CliError::Output(kind) => write!(f, "could not write the output: {}", kind),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+/// A command together with how its output is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub command: Command,
+    /// `--json`: write NDJSON records as described in `crate::schema`
+    /// instead of text.
+    pub json: bool,
+}
+
+impl Invocation {
+    /// Parses the arguments after the program name.
+    pub fn parse<I>(args: I) -> Result<Invocation, CliError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut options = ChangeOptions::default();
+        let mut json = false;
+        let mut words = Vec::new();
+        for arg in args {
+            let arg = arg.as_ref();
+            match arg.split_once('=') {
+                None if arg == "--dry-run" => options.dry_run = true,
+                None if arg == "--json" => json = true,
+                None if arg == "--help" || arg == "-h" => {
+                    let command = Command::Help;
+                    return Ok(Invocation { command, json });
+                }
+                Some(("--persistence", value)) => {
+                    options.persistence = value.parse::<Persistence>().map_err(CliError::Usage)?
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(CliError::Usage(format!("unknown option `{}`", arg)))
+                }
+                _ => words.push(arg.to_string()),
+            }
+        }
+
+        let mut words = words.into_iter();
+        let name = words.next();
+        let mut monitor = |required: bool| match words.next() {
+            Some(monitor) => Ok(Some(monitor)),
+            None if required => Err(CliError::Usage("missing <monitor>".to_string())),
+            None => Ok(None),
+        };
+        let command = match name.as_deref() {
+            None | Some("help") => Command::Help,
+            Some("list") => Command::List,
+            Some("primary") => Command::Primary,
+            Some("get") => Command::Get {
+                monitor: monitor(false)?,
+            },
+            Some("modes") => Command::Modes {
+                monitor: monitor(true)?.unwrap_or_default(),
+            },
+            Some("set") => {
+                let monitor = monitor(true)?.unwrap_or_default();
+                let rate = words
+                    .next()
+                    .ok_or_else(|| CliError::Usage("missing <rate>".to_string()))?
+                    .parse()
+                    .map_err(CliError::Usage)?;
+                Command::Set {
+                    monitor,
+                    rate,
+                    options,
+                }
+            }
+            Some(name) => return Err(CliError::Usage(format!("unknown command `{}`", name))),
+        };
+        if let Some(extra) = words.next() {
+            return Err(CliError::Usage(format!("unexpected argument `{}`", extra)));
+        }
+        Ok(Invocation { command, json })
+    }
+
+    /// Runs the command, writing its report to `out`. Returns the outcome of
+    /// `set`, `None` for the other commands.
+    pub fn run(
+        &self,
+        backend: &dyn DisplayBackend,
+        out: &mut dyn Write,
+    ) -> Result<Option<ChangeOutcome>, CliError> {
+        match &self.command {
+            Command::Help => writeln!(out, "{}", USAGE)?,
+            Command::List => {
+                for (i, device) in get_all_display_devices_with(backend)?.iter().enumerate() {
+                    // A monitor whose settings cannot be read is still listed,
+                    // without the rate, so one bad output does not hide the rest.
+                    let mode = backend.current_mode(&device.device_name).ok();
+                    let rate = current_rate(backend, &device.device_name).ok();
+                    if self.json {
+                        let record = Record::Monitor {
+                            index: i + 1,
+                            device,
+                            mode,
+                            rate,
+                            position: backend.current_position(&device.device_name).ok(),
+                        };
+                        writeln!(out, "{}", record.to_json())?;
+                        continue;
+                    }
+                    write!(
+                        out,
+                        "{}: {} {}",
+                        i + 1,
+                        device.device_name,
+                        device.display_name
+                    )?;
+                    if let Some(hardware_id) = device.hardware_id() {
+                        write!(out, " ({})", hardware_id)?;
+                    }
+                    if let Some(mode) = mode {
+                        write!(out, ", {}x{}", mode.width, mode.height)?;
+                    }
+                    match rate {
+                        Some(rate) => write!(out, ", {}", rate)?,
+                        None => write!(out, ", unknown rate")?,
+                    }
+                    if device.is_primary {
+                        write!(out, ", primary")?;
+                    }
+                    writeln!(out)?;
+                }
+            }
+            Command::Get { monitor: None } => {
+                // A monitor whose rate cannot be read does not hide the rest;
+                // the first such error is returned once all are written.
+                let mut failure = None;
+                for device in get_all_display_devices_with(backend)? {
+                    let rate = match current_rate(backend, &device.device_name) {
+                        Ok(rate) => rate,
+                        Err(error) => {
+                            if !self.json {
+                                writeln!(out, "{}: unknown rate", device.device_name)?;
+                            }
+                            failure.get_or_insert(error);
+                            continue;
+                        }
+                    };
+                    if self.json {
+                        let record = rate_record(&device.device_name, rate, true);
+                        writeln!(out, "{}", record.to_json())?;
+                    } else {
+                        writeln!(out, "{}: {}", device.device_name, rate)?;
+                    }
+                }
+                if let Some(error) = failure {
+                    return Err(error.into());
+                }
+            }
+            Command::Get {
+                monitor: Some(monitor),
+            } => {
+                let device_name = find_monitor(backend, monitor)?.device_name;
+                let rate = current_rate(backend, &device_name)?;
+                if self.json {
+                    writeln!(out, "{}", rate_record(&device_name, rate, true).to_json())?;
+                } else {
+                    writeln!(out, "{}", rate)?;
+                }
+            }
+            Command::Modes { monitor } => {
+                let device_name = find_monitor(backend, monitor)?.device_name;
+                let current = current_rate(backend, &device_name)?;
+                for rate in get_exact_refresh_rates_with(backend, &device_name) {
+                    if self.json {
+                        let record = rate_record(&device_name, rate, rate == current);
+                        writeln!(out, "{}", record.to_json())?;
+                    } else {
+                        let marker = if rate == current { " (current)" } else { "" };
+                        writeln!(out, "{}{}", rate, marker)?;
+                    }
+                }
+            }
+            Command::Primary => {
+                let device_name = get_primary_display_device_name_with(backend).ok_or(
+                    DisplayError::MonitorNotFound {
+                        monitor: "primary".to_string(),
+                    },
+                )?;
+                if self.json {
+                    let record = Record::Primary {
+                        device_name: &device_name,
+                    };
+                    writeln!(out, "{}", record.to_json())?;
+                } else {
+                    writeln!(out, "{}", device_name)?;
+                }
+            }
+            Command::Set {
+                monitor,
+                rate,
+                options,
+            } => {
+                let device_name = find_monitor(backend, monitor)?.device_name;
+                let outcome = if whole_hertz_change(backend, &device_name, *rate) {
+                    set_display_refresh_rate_with(
+                        backend,
+                        &device_name,
+                        rate.rounded_hz(),
+                        *options,
+                    )
+                } else {
+                    set_exact_refresh_rate_with(backend, &device_name, *rate, *options)
+                }?;
+                if self.json {
+                    let record = Record::Change {
+                        device_name: &device_name,
+                        rate: *rate,
+                        outcome,
+                    };
+                    writeln!(out, "{}", record.to_json())?;
+                } else {
+                    let report = match outcome {
+                        ChangeOutcome::Applied => "switched to",
+                        ChangeOutcome::Unchanged => "already at",
+                        ChangeOutcome::RestartRequired => "switches after a restart to",
+                        ChangeOutcome::Validated => "would switch to",
+                    };
+                    writeln!(out, "{}: {} {}", device_name, report, rate)?;
+                }
+                return Ok(Some(outcome));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parses `args` (without the program name), runs the command and returns
+/// the process exit code. Errors and usage go to `err`; with `--json`,
+/// errors are written as an `error` record.
+pub fn main_with<I>(
+    backend: &dyn DisplayBackend,
+    args: I,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> i32
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let args: Vec<String> = args
+        .into_iter()
+        .map(|arg| arg.as_ref().to_string())
+        .collect();
+    let json = args.iter().any(|arg| arg == "--json");
+    let result = Invocation::parse(&args).and_then(|invocation| invocation.run(backend, out));
+    let error = match result {
+        Ok(Some(ChangeOutcome::RestartRequired)) => return EXIT_RESTART_REQUIRED,
+        Ok(_) => return EXIT_SUCCESS,
+        Err(error) => error,
+    };
+    let _ = if json {
+        let record = Record::Error {
+            exit_code: error.exit_code(),
+            message: error.to_string(),
+        };
+        writeln!(err, "{}", record.to_json())
+    } else if let CliError::Usage(_) = error {
+        writeln!(err, "error: {}\n\n{}", error, USAGE)
+    } else {
+        writeln!(err, "error: {}", error)
+    };
+    error.exit_code()
+}
+
+fn rate_record(device_name: &str, rate: RefreshRate, current: bool) -> Record<'_> {
+    Record::RefreshRate {
+        device_name,
+        rate,
+        current,
+    }
+}
+
+/// Whether `set` can switch `device_name` to `rate` through
+/// `ChangeDisplaySettingsExW`, which only sees whole hertz: `rate` is an
+/// integer and the monitor is not running a fractional rate that rounds down
+/// to the same frequency, such as 59.94 Hz for `59`.
+fn whole_hertz_change(backend: &dyn DisplayBackend, device_name: &str, rate: RefreshRate) -> bool {
+    rate.is_integer()
+        && match backend.current_refresh_rate(device_name) {
+            Ok(current) => current == rate || current.dm_frequency() != rate.dm_frequency(),
+            Err(_) => true,
+        }
+}
+
+/// The exact rate of `device_name`, or its integer rate when the CCD API
+/// cannot see it.
+fn current_rate(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+) -> Result<RefreshRate, DisplayError> {
+    match backend.current_refresh_rate(device_name) {
+        Ok(rate) => Ok(rate),
+        Err(_) => backend
+            .current_mode(device_name)
+            .map(|mode| RefreshRate::integer(mode.frequency))
+            .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+                device_name: device_name.to_string(),
+                os_error,
+            }),
+    }
+}
+
+/// The connected monitor `monitor` names: its number in `list`, its adapter
+/// output with or without the `\\.\` prefix, or a stable identifier.
+fn find_monitor(
+    backend: &dyn DisplayBackend,
+    monitor: &str,
+) -> Result<DisplayDevice, DisplayError> {
+    let devices = get_all_display_devices_with(backend)?;
+    let output = |device: &DisplayDevice| {
+        let name = device.device_name.trim_start_matches(r"\\.\");
+        name.eq_ignore_ascii_case(monitor.trim_start_matches(r"\\.\"))
+    };
+    let found = match monitor.parse::<usize>() {
+        Ok(number) => number.checked_sub(1).and_then(|i| devices.get(i)),
+        Err(_) => devices
+            .iter()
+            .find(|device| output(device))
+            .or_else(|| devices.iter().find(|device| device.matches_id(monitor))),
+    };
+    found.cloned().ok_or_else(|| DisplayError::MonitorNotFound {
+        monitor: monitor.to_string(),
+    })
+}
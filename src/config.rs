@@ -0,0 +1,468 @@
+//! The tray's settings file, `%APPDATA%\refresh-rate-windows-rs\config.toml`.
+//!
+//! ```toml
+//! version = 1
+//! persistence = "temporary"
+//! revert_timeout = 15
+//! log_level = "info"
+//! tooltip = "Refresh Rate Tray"
+//!
+//! [[profiles]]
+//! name = "Gaming"
+//! ...
+//!
+//! [[power]]
+//! monitor = "DEL4123"
+//! ...
+//!
+//! [[apps]]
+//! app = "eldenring.exe"
+//! ...
+//!
+//! [[fullscreen]]
+//! monitor = "DEL4123"
+//! ...
+//! ```
+//!
+//! Every key but `version` is optional and falls back to its default. A file
+//! without `version` comes from before versioning, when it only held
+//! `[[profiles]]`; it is read as version 1 and written back with the version.
+//! Files from newer versions are refused rather than half understood.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{info, LevelFilter};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use toml::value::{Table, Value};
+
+use crate::format::{Fields, FormatError};
+use crate::fullscreen::{fullscreen_rules_from_fields, FullscreenRule};
+use crate::options::Persistence;
+use crate::power::{power_rules_from_fields, PowerRule};
+use crate::profile::{profiles_from_fields, Profile};
+use crate::revert::DEFAULT_REVERT_TIMEOUT;
+use crate::rules::{app_rules_from_fields, AppRule};
+
+/// The config version this crate writes.
+pub const CONFIG_VERSION: i64 = 1;
+
+pub const DEFAULT_TOOLTIP: &str = "Refresh Rate Tray";
+
+/// Why the config file could not be used. Line and column are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The file exists but could not be read or written.
+    Io { path: PathBuf, message: String },
+    /// The file is not valid TOML.
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A setting has the wrong type or value. `position` is where its value
+    /// starts, if the setting is present at all.
+    Invalid {
+        path: String,
+        expected: &'static str,
+        position: Option<(usize, usize)>,
+    },
+    /// The file was written by a newer version.
+    UnsupportedVersion(i64),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, message } => write!(f, "{}: {}", path.display(), message),
+            ConfigError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            ConfigError::Invalid {
+                path,
+                expected,
+                position: Some((line, column)),
+            } => write!(
+                f,
+                "line {}, column {}: `{}` must be {}",
+                line, column, path, expected
+            ),
+            ConfigError::Invalid { path, expected, .. } => {
+                write!(f, "`{}` must be {}", path, expected)
+            }
+            ConfigError::UnsupportedVersion(version) => write!(
+                f,
+                "config version {} is newer than the supported version {}",
+                version, CONFIG_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// The tray's settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// How menu changes are applied until changed in the menu.
+    pub persistence: Persistence,
+    /// Countdown before an unconfirmed change is reverted; zero keeps
+    /// changes without asking.
+    pub revert_timeout: Duration,
+    /// Log level unless `REFRESH_RATE_LOG` overrides it.
+    pub log_level: LevelFilter,
+    /// Tray icon tooltip.
+    pub tooltip: String,
+    pub profiles: Vec<Profile>,
+    /// Refresh rates per power source, see `crate::power`.
+    pub power: Vec<PowerRule>,
+    /// Refresh rates forced while an application is in the foreground, see
+    /// `crate::rules`.
+    pub apps: Vec<AppRule>,
+    /// Refresh rates forced while an application is full screen, see
+    /// `crate::fullscreen`.
+    pub fullscreen: Vec<FullscreenRule>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            persistence: Persistence::default(),
+            revert_timeout: DEFAULT_REVERT_TIMEOUT,
+            log_level: LevelFilter::Info,
+            tooltip: DEFAULT_TOOLTIP.to_string(),
+            profiles: Vec::new(),
+            power: Vec::new(),
+            apps: Vec::new(),
+            fullscreen: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// `%APPDATA%\refresh-rate-windows-rs\config.toml`, if `APPDATA` is set.
+    pub fn default_path() -> Option<PathBuf> {
+        let app_data = std::env::var_os("APPDATA")?;
+        Some(
+            PathBuf::from(app_data)
+                .join("refresh-rate-windows-rs")
+                .join("config.toml"),
+        )
+    }
+
+    /// Reads the config at `path`. On first run the defaults are written
+    /// there; a file from an older version is migrated and written back.
+    pub fn load_or_create(path: &Path) -> Result<Self, ConfigError> {
+        let io_error = |error: std::io::Error| ConfigError::Io {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let config = Config::default();
+                config.save(path)?;
+                info!(path = path.display().to_string(); "Wrote default config");
+                return Ok(config);
+            }
+            Err(error) => return Err(io_error(error)),
+        };
+
+        let mut value = parse_toml(&text)?;
+        let version = migrate(&mut value)?;
+        let config = Config::from_value(&value).map_err(|error| located(&text, error))?;
+        if version < CONFIG_VERSION {
+            write_document(path, value)?;
+            info!(path = path.display().to_string(), from = version, to = CONFIG_VERSION; "Migrated config");
+        }
+        Ok(config)
+    }
+
+    /// Writes this config's top-level `key`, e.g. `persistence` after it was
+    /// changed in the menu, to the file at `path`. Every other key in the
+    /// file, including ones this version does not know, is kept; comments
+    /// are not. Without a file the whole config is written.
+    pub fn save_setting(&self, path: &Path, key: &str) -> Result<(), ConfigError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return self.save(path),
+            Err(error) => {
+                return Err(ConfigError::Io {
+                    path: path.to_path_buf(),
+                    message: error.to_string(),
+                })
+            }
+        };
+        let mut value = parse_toml(&text)?;
+        migrate(&mut value)?;
+        if let (Value::Table(table), Value::Table(settings)) = (&mut value, self.to_value()) {
+            match settings.get(key) {
+                Some(setting) => table.insert(key.to_string(), setting.clone()),
+                None => table.remove(key),
+            };
+        }
+        write_document(path, value)
+    }
+
+    /// Writes the whole config to `path`, creating its directory. Keys the
+    /// config does not hold are not written; see `save_setting`.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        write_document(path, self.to_value())
+    }
+
+    /// Reads a config document of any supported version.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let mut value = parse_toml(text)?;
+        migrate(&mut value)?;
+        Config::from_value(&value).map_err(|error| located(text, error))
+    }
+
+    /// The config as TOML, with `version` on the first line.
+    pub fn to_toml(&self) -> String {
+        document_to_toml(self.to_value())
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        let mut insert = |key: &str, value: Value| table.insert(key.to_string(), value);
+        insert("version", Value::Integer(CONFIG_VERSION));
+        insert(
+            "persistence",
+            Value::String(self.persistence.as_str().to_string()),
+        );
+        insert(
+            "revert_timeout",
+            Value::Integer(self.revert_timeout.as_secs() as i64),
+        );
+        insert(
+            "log_level",
+            Value::String(self.log_level.as_str().to_lowercase()),
+        );
+        insert("tooltip", Value::String(self.tooltip.clone()));
+        if !self.profiles.is_empty() {
+            insert(
+                "profiles",
+                Value::Array(self.profiles.iter().map(Profile::to_value).collect()),
+            );
+        }
+        if !self.power.is_empty() {
+            insert(
+                "power",
+                Value::Array(self.power.iter().map(PowerRule::to_value).collect()),
+            );
+        }
+        if !self.apps.is_empty() {
+            insert(
+                "apps",
+                Value::Array(self.apps.iter().map(AppRule::to_value).collect()),
+            );
+        }
+        if !self.fullscreen.is_empty() {
+            insert(
+                "fullscreen",
+                Value::Array(
+                    self.fullscreen
+                        .iter()
+                        .map(FullscreenRule::to_value)
+                        .collect(),
+                ),
+            );
+        }
+        Value::Table(table)
+    }
+
+    /// Reads a current-version document; missing settings take their
+    /// defaults.
+    pub fn from_value(value: &Value) -> Result<Self, FormatError> {
+        let fields = Fields::root(value)?;
+        let mut config = Config::default();
+        if let Some(persistence) = fields.optional_string("persistence")? {
+            config.persistence = persistence
+                .parse()
+                .map_err(|_| fields.invalid("persistence", "temporary, persistent or global"))?;
+        }
+        if let Some(seconds) = fields.optional_unsigned("revert_timeout")? {
+            config.revert_timeout = Duration::from_secs(seconds.into());
+        }
+        if let Some(log_level) = fields.optional_string("log_level")? {
+            config.log_level = log_level.parse().map_err(|_| {
+                fields.invalid("log_level", "off, error, warn, info, debug or trace")
+            })?;
+        }
+        if let Some(tooltip) = fields.optional_string("tooltip")? {
+            config.tooltip = tooltip;
+        }
+        config.profiles = profiles_from_fields(&fields)?;
+        config.power = power_rules_from_fields(&fields)?;
+        config.apps = app_rules_from_fields(&fields)?;
+        config.fullscreen = fullscreen_rules_from_fields(&fields)?;
+        Ok(config)
+    }
+}
+
+/// `value`, a config document, as TOML with `version` on the first line.
+fn document_to_toml(value: Value) -> String {
+    let Value::Table(mut table) = value else {
+        unreachable!("config documents are tables");
+    };
+    let version = table
+        .remove("version")
+        .unwrap_or(Value::Integer(CONFIG_VERSION));
+    // Parsed TOML and tables of strings, integers and booleans always
+    // serialize.
+    let settings = toml::to_string(&Value::Table(table)).expect("config is valid TOML");
+    format!("version = {}\n{}", version, settings)
+}
+
+/// Writes the config document `value` to `path`, creating its directory.
+fn write_document(path: &Path, value: Value) -> Result<(), ConfigError> {
+    let io_error = |error: std::io::Error| ConfigError::Io {
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    fs::write(path, document_to_toml(value)).map_err(io_error)
+}
+
+fn parse_toml(text: &str) -> Result<Value, ConfigError> {
+    toml::from_str(text).map_err(|error| {
+        let (line, column) = error.line_col().unwrap_or_default();
+        let message = error.to_string();
+        // The position is reported separately.
+        let message = match message.find(" at line ") {
+            Some(end) => message[..end].to_string(),
+            None => message,
+        };
+        ConfigError::Syntax {
+            line: line + 1,
+            column: column + 1,
+            message,
+        }
+    })
+}
+
+/// Brings `value` up to `CONFIG_VERSION` and returns the version it had. A
+/// document without `version` is version 0, whose layout version 1 kept, so
+/// only the version is added; changes to the layout go here with the next
+/// version.
+pub fn migrate(value: &mut Value) -> Result<i64, ConfigError> {
+    let invalid = ConfigError::Invalid {
+        path: "version".to_string(),
+        expected: "an integer",
+        position: None,
+    };
+    let Value::Table(table) = value else {
+        return Err(invalid);
+    };
+    let version = match table.get("version") {
+        None => 0,
+        Some(Value::Integer(version)) => *version,
+        Some(_) => return Err(invalid),
+    };
+    if !(0..=CONFIG_VERSION).contains(&version) {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION));
+    Ok(version)
+}
+
+/// Adds the position of the offending value in `text` to `error`.
+fn located(text: &str, error: FormatError) -> ConfigError {
+    match error {
+        FormatError::InvalidField { path, expected } => ConfigError::Invalid {
+            position: position_of(text, &path),
+            path,
+            expected,
+        },
+        FormatError::UnsupportedVersion(version) => ConfigError::UnsupportedVersion(version),
+        // Config documents are parsed before they are checked.
+        FormatError::Toml(_) | FormatError::Json { .. } => {
+            unreachable!("checked an unparsed config")
+        }
+    }
+}
+
+/// A step in a `Fields` path such as `profiles[1].monitors[0].resolution`.
+#[derive(Clone, Copy)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Where the value at `path` starts in `text`, as a 1-based line and column.
+/// `toml::Value` keeps no positions, so the document is walked again with
+/// the TOML deserializer, which reports where a value it could not read
+/// starts. Tables and missing keys have no position.
+fn position_of(text: &str, path: &str) -> Option<(usize, usize)> {
+    let mut segments = Vec::new();
+    for part in path.split('.') {
+        let (key, indices) = part.split_once('[').unwrap_or((part, ""));
+        segments.push(Segment::Key(key));
+        for index in indices.split('[').filter(|i| !i.is_empty()) {
+            segments.push(Segment::Index(index.trim_end_matches(']').parse().ok()?));
+        }
+    }
+    let mut deserializer = toml::Deserializer::new(text);
+    let error = Probe(&segments).deserialize(&mut deserializer).err()?;
+    let (line, column) = error.line_col()?;
+    Some((line + 1, column + 1))
+}
+
+/// Skips everything off `path` and fails on the scalar or array at its end.
+struct Probe<'a>(&'a [Segment<'a>]);
+
+impl<'de> DeserializeSeed<'de> for Probe<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Probe<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a table on the path")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match self.0.split_first() {
+                Some((Segment::Key(wanted), rest)) if *wanted == key => {
+                    map.next_value_seed(Probe(rest))?
+                }
+                _ => map.next_value::<IgnoredAny>().map(drop)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Some((Segment::Index(wanted), rest)) = self.0.split_first() else {
+            return Err(de::Error::custom("found the value"));
+        };
+        let mut index = 0;
+        loop {
+            let item = if index == *wanted {
+                seq.next_element_seed(Probe(rest))?
+            } else {
+                seq.next_element::<IgnoredAny>()?.map(drop)
+            };
+            if item.is_none() {
+                return Ok(());
+            }
+            index += 1;
+        }
+    }
+
+    // Every other `visit_*` fails by default: a scalar was reached, so the
+    // path ends here.
+}
@@ -0,0 +1,73 @@
+//! Matching `EnumDisplayDevicesW` monitors to their SetupAPI devnodes.
+//!
+//! A monitor and its devnode share no single common field, so we try, in
+//! order of reliability:
+//!
+//! 1. the device interface path (`EDD_GET_DEVICE_INTERFACE_NAME`), which is
+//!    the devnode's instance ID in disguise;
+//! 2. the driver key at the end of the monitor's `DeviceID`, which equals the
+//!    devnode's `CM_DRP_DRIVER`;
+//! 3. the hardware ID, but only when exactly one devnode carries it, since
+//!    identical monitors share it.
+
+use crate::backend::{MonitorDevNode, RawDisplayDevice};
+
+/// Converts a monitor device interface path such as
+/// `\\?\DISPLAY#DEL4123#5&1a2b3c&0&UID4353#{e6f07b5f-...}` into the devnode
+/// instance ID it names (`DISPLAY\DEL4123\5&1a2b3c&0&UID4353`).
+pub fn instance_id_from_interface_path(interface_path: &str) -> Option<String> {
+    let path = interface_path.strip_prefix(r"\\?\")?;
+    // Drop the trailing `#{interface class GUID}`.
+    let (instance, class_guid) = path.rsplit_once('#')?;
+    if !class_guid.starts_with('{') {
+        return None;
+    }
+    Some(instance.replace('#', "\\"))
+}
+
+/// Splits a monitor `DeviceID` such as
+/// `MONITOR\DEL4123\{4d36e96e-e325-11ce-bfc1-08002be10318}\0001` into its
+/// hardware ID (`MONITOR\DEL4123`) and driver key (`{4d36e96e-...}\0001`).
+pub fn split_monitor_device_id(device_id: &str) -> Option<(&str, &str)> {
+    let driver_start = device_id.find("\\{")?;
+    let (hardware_id, driver_key) = device_id.split_at(driver_start);
+    Some((hardware_id, &driver_key[1..]))
+}
+
+/// Finds the devnode describing `monitor`, if any.
+pub fn match_devnode<'a>(
+    monitor: &RawDisplayDevice,
+    devnodes: &'a [MonitorDevNode],
+) -> Option<&'a MonitorDevNode> {
+    if let Some(instance_id) = instance_id_from_interface_path(&monitor.interface_path) {
+        let found = devnodes
+            .iter()
+            .find(|devnode| devnode.instance_id.eq_ignore_ascii_case(&instance_id));
+        if found.is_some() {
+            return found;
+        }
+    }
+
+    let (hardware_id, driver_key) = split_monitor_device_id(&monitor.device_id)?;
+
+    let found = devnodes.iter().find(|devnode| {
+        devnode
+            .driver_key
+            .as_deref()
+            .is_some_and(|key| key.eq_ignore_ascii_case(driver_key))
+    });
+    if found.is_some() {
+        return found;
+    }
+
+    let mut candidates = devnodes.iter().filter(|devnode| {
+        devnode
+            .hardware_ids
+            .iter()
+            .any(|id| id.eq_ignore_ascii_case(hardware_id))
+    });
+    match (candidates.next(), candidates.next()) {
+        (Some(devnode), None) => Some(devnode),
+        _ => None,
+    }
+}
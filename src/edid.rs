@@ -0,0 +1,397 @@
+//! Parsing of EDID (Extended Display Identification Data).
+//!
+//! Monitors describe themselves in a 128-byte base block, optionally followed
+//! by 128-byte extension blocks. This module decodes the identification and
+//! timing data from the base block; `cta` and `displayid` decode the
+//! extensions where high-refresh timings and VRR windows usually live.
+
+use std::error::Error;
+use std::fmt;
+
+pub mod cta;
+pub mod displayid;
+
+use cta::CtaExtension;
+use displayid::DisplayIdExtension;
+
+pub const EDID_BLOCK_LEN: usize = 128;
+
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+
+const TAG_SERIAL: u8 = 0xFF;
+const TAG_RANGE_LIMITS: u8 = 0xFD;
+const TAG_NAME: u8 = 0xFC;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdidError {
+    /// No EDID could be read for the monitor.
+    Unavailable,
+    /// The blob is shorter than one block or not a whole number of blocks.
+    BadLength(usize),
+    /// The base block does not start with the fixed EDID header.
+    BadHeader,
+    /// The bytes of block `block` do not sum to zero.
+    BadChecksum { block: usize },
+}
+
+impl fmt::Display for EdidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdidError::Unavailable => write!(f, "no EDID available"),
+            EdidError::BadLength(len) => write!(f, "invalid EDID length {}", len),
+            EdidError::BadHeader => write!(f, "missing EDID header"),
+            EdidError::BadChecksum { block } => write!(f, "bad checksum in EDID block {}", block),
+        }
+    }
+}
+
+impl Error for EdidError {}
+
+/// When the monitor was made. `week` is `None` when unspecified; when
+/// `is_model_year` is set, `year` is the model year rather than the
+/// manufacture date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManufactureDate {
+    pub week: Option<u8>,
+    pub year: u16,
+    pub is_model_year: bool,
+}
+
+/// A detailed timing descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub h_active: u16,
+    pub h_blanking: u16,
+    pub v_active: u16,
+    pub v_blanking: u16,
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    /// Parses an 18-byte descriptor, or `None` if it is not a timing.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let pixel_clock = u16::from_le_bytes([bytes[0], bytes[1]]);
+        if pixel_clock == 0 {
+            return None;
+        }
+        Some(DetailedTiming {
+            pixel_clock_khz: u32::from(pixel_clock) * 10,
+            h_active: u16::from(bytes[2]) | (u16::from(bytes[4] & 0xF0) << 4),
+            h_blanking: u16::from(bytes[3]) | (u16::from(bytes[4] & 0x0F) << 8),
+            v_active: u16::from(bytes[5]) | (u16::from(bytes[7] & 0xF0) << 4),
+            v_blanking: u16::from(bytes[6]) | (u16::from(bytes[7] & 0x0F) << 8),
+            interlaced: bytes[17] & 0x80 != 0,
+        })
+    }
+
+    /// Vertical refresh rate in millihertz.
+    pub fn refresh_millihz(&self) -> u32 {
+        let total =
+            u64::from(self.h_active + self.h_blanking) * u64::from(self.v_active + self.v_blanking);
+        if total == 0 {
+            return 0;
+        }
+        (u64::from(self.pixel_clock_khz) * 1_000_000 / total) as u32
+    }
+
+    /// Vertical refresh rate rounded to whole hertz.
+    pub fn refresh_hz(&self) -> u32 {
+        (self.refresh_millihz() + 500) / 1000
+    }
+}
+
+/// The display range limits descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLimits {
+    pub min_vertical_hz: u16,
+    pub max_vertical_hz: u16,
+    pub min_horizontal_khz: u16,
+    pub max_horizontal_khz: u16,
+    /// Maximum pixel clock in MHz, if given.
+    pub max_pixel_clock_mhz: Option<u16>,
+}
+
+impl RangeLimits {
+    fn parse(bytes: &[u8]) -> Self {
+        // EDID 1.4 offset flags add 255 to rates that do not fit in a byte:
+        // 0b10 offsets the maximum, 0b11 both the minimum and the maximum.
+        let vertical = bytes[4] & 0x03;
+        let horizontal = (bytes[4] >> 2) & 0x03;
+        let offset = |flags: u8, threshold: u8| if flags >= threshold { 255 } else { 0 };
+        RangeLimits {
+            min_vertical_hz: u16::from(bytes[5]) + offset(vertical, 0b11),
+            max_vertical_hz: u16::from(bytes[6]) + offset(vertical, 0b10),
+            min_horizontal_khz: u16::from(bytes[7]) + offset(horizontal, 0b11),
+            max_horizontal_khz: u16::from(bytes[8]) + offset(horizontal, 0b10),
+            max_pixel_clock_mhz: (bytes[9] != 0).then(|| u16::from(bytes[9]) * 10),
+        }
+    }
+}
+
+/// An inclusive vertical refresh range in hertz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshRange {
+    pub min_hz: u16,
+    pub max_hz: u16,
+}
+
+impl RefreshRange {
+    pub fn contains(&self, hz: u32) -> bool {
+        (u32::from(self.min_hz)..=u32::from(self.max_hz)).contains(&hz)
+    }
+}
+
+/// A timing the monitor explicitly advertises, from any EDID block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdvertisedTiming {
+    pub width: u16,
+    pub height: u16,
+    pub refresh_millihz: u32,
+    pub interlaced: bool,
+}
+
+impl AdvertisedTiming {
+    /// Refresh rate rounded to whole hertz, as `dmDisplayFrequency` reports it.
+    pub fn refresh_hz(&self) -> u32 {
+        (self.refresh_millihz + 500) / 1000
+    }
+}
+
+/// What the monitor says about the refresh rates it can display, merged
+/// from the base block and every extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefreshCapabilities {
+    pub timings: Vec<AdvertisedTiming>,
+    /// Vertical range from the display range limits descriptor, or else the
+    /// span of the advertised timings.
+    pub refresh_range: Option<RefreshRange>,
+    /// Variable refresh window, preferring DisplayID Adaptive-Sync, then the
+    /// HDMI Forum VRR range, then AMD FreeSync.
+    pub vrr: Option<RefreshRange>,
+}
+
+impl RefreshCapabilities {
+    /// Whether a `width`x`height` mode at `refresh_hz` is advertised, either as
+    /// an explicit timing or, at the native resolution, inside the VRR window.
+    pub fn advertises(&self, width: u32, height: u32, refresh_hz: u32) -> bool {
+        let same_size =
+            |t: &AdvertisedTiming| u32::from(t.width) == width && u32::from(t.height) == height;
+        if self
+            .timings
+            .iter()
+            .any(|t| same_size(t) && t.refresh_hz() == refresh_hz)
+        {
+            return true;
+        }
+        let native = self.timings.first().is_some_and(same_size);
+        native && self.vrr.is_some_and(|vrr| vrr.contains(refresh_hz))
+    }
+}
+
+/// A parsed EDID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edid {
+    /// Three-letter PNP manufacturer ID, e.g. `DEL`.
+    pub manufacturer: String,
+    pub product_code: u16,
+    /// Binary serial number; 0 when unused.
+    pub serial_number: u32,
+    pub manufacture_date: ManufactureDate,
+    /// EDID version and revision, e.g. `(1, 4)`.
+    pub version: (u8, u8),
+    /// Physical size in centimetres, `None` if unspecified or variable.
+    pub physical_size_cm: Option<(u8, u8)>,
+    /// Monitor name descriptor.
+    pub name: Option<String>,
+    /// Serial number descriptor.
+    pub serial: Option<String>,
+    /// The first detailed timing, which is the panel's preferred mode.
+    pub preferred_timing: Option<DetailedTiming>,
+    /// Every detailed timing in the base block, preferred first.
+    pub detailed_timings: Vec<DetailedTiming>,
+    pub range_limits: Option<RangeLimits>,
+    /// Raw 128-byte extension blocks following the base block.
+    pub extensions: Vec<Vec<u8>>,
+    /// Decoded CTA-861 extensions.
+    pub cta: Vec<CtaExtension>,
+    /// Decoded DisplayID extensions.
+    pub displayid: Vec<DisplayIdExtension>,
+}
+
+impl Edid {
+    /// Parses a raw EDID blob (128 bytes, or more with extensions).
+    pub fn parse(bytes: &[u8]) -> Result<Self, EdidError> {
+        if bytes.len() < EDID_BLOCK_LEN || !bytes.len().is_multiple_of(EDID_BLOCK_LEN) {
+            return Err(EdidError::BadLength(bytes.len()));
+        }
+        if bytes[..8] != HEADER {
+            return Err(EdidError::BadHeader);
+        }
+        for (block, chunk) in bytes.chunks(EDID_BLOCK_LEN).enumerate() {
+            if chunk.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+                return Err(EdidError::BadChecksum { block });
+            }
+        }
+
+        let week = bytes[16];
+        let mut edid = Edid {
+            manufacturer: decode_pnp_id([bytes[8], bytes[9]]),
+            product_code: u16::from_le_bytes([bytes[10], bytes[11]]),
+            serial_number: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            manufacture_date: ManufactureDate {
+                week: (week != 0 && week != 0xFF).then_some(week),
+                year: 1990 + u16::from(bytes[17]),
+                is_model_year: week == 0xFF,
+            },
+            version: (bytes[18], bytes[19]),
+            physical_size_cm: (bytes[21] != 0 && bytes[22] != 0).then_some((bytes[21], bytes[22])),
+            name: None,
+            serial: None,
+            preferred_timing: None,
+            detailed_timings: Vec::new(),
+            range_limits: None,
+            extensions: Vec::new(),
+            cta: Vec::new(),
+            displayid: Vec::new(),
+        };
+
+        for offset in DESCRIPTOR_OFFSETS {
+            let descriptor = &bytes[offset..offset + 18];
+            if let Some(timing) = DetailedTiming::parse(descriptor) {
+                edid.detailed_timings.push(timing);
+                continue;
+            }
+            match descriptor[3] {
+                TAG_NAME => edid.name = Some(decode_text(&descriptor[5..])),
+                TAG_SERIAL => edid.serial = Some(decode_text(&descriptor[5..])),
+                TAG_RANGE_LIMITS => edid.range_limits = Some(RangeLimits::parse(descriptor)),
+                _ => {}
+            }
+        }
+        edid.preferred_timing = edid.detailed_timings.first().copied();
+
+        let extension_count = usize::from(bytes[126]);
+        edid.extensions = bytes[EDID_BLOCK_LEN..]
+            .chunks(EDID_BLOCK_LEN)
+            .take(extension_count)
+            .map(<[u8]>::to_vec)
+            .collect();
+        edid.cta = edid
+            .extensions
+            .iter()
+            .filter_map(|block| CtaExtension::parse(block))
+            .collect();
+        edid.displayid = edid
+            .extensions
+            .iter()
+            .filter_map(|block| DisplayIdExtension::parse(block))
+            .collect();
+
+        Ok(edid)
+    }
+
+    /// Merges the timings and refresh windows of every block. The preferred
+    /// timing comes first.
+    pub fn refresh_capabilities(&self) -> RefreshCapabilities {
+        let from_dtd = |t: &DetailedTiming| AdvertisedTiming {
+            width: t.h_active,
+            height: t.v_active,
+            refresh_millihz: t.refresh_millihz(),
+            interlaced: t.interlaced,
+        };
+
+        let mut timings: Vec<AdvertisedTiming> =
+            self.detailed_timings.iter().map(from_dtd).collect();
+        for displayid in &self.displayid {
+            // Without base-block timings, DisplayID's preferred timing is the native mode.
+            for t in &displayid.timings {
+                let timing = AdvertisedTiming {
+                    width: t.h_active,
+                    height: t.v_active,
+                    refresh_millihz: t.refresh_millihz(),
+                    interlaced: t.interlaced,
+                };
+                if t.preferred && self.detailed_timings.is_empty() {
+                    timings.insert(0, timing);
+                } else {
+                    timings.push(timing);
+                }
+            }
+        }
+        for cta in &self.cta {
+            timings.extend(cta.detailed_timings.iter().map(from_dtd));
+            timings.extend(cta.video_timings.iter().map(|v| AdvertisedTiming {
+                width: v.width,
+                height: v.height,
+                refresh_millihz: u32::from(v.refresh_hz) * 1000,
+                interlaced: v.interlaced,
+            }));
+        }
+        let mut seen = Vec::new();
+        timings.retain(|t| {
+            let key = (t.width, t.height, t.refresh_hz(), t.interlaced);
+            let new = !seen.contains(&key);
+            seen.push(key);
+            new
+        });
+
+        let refresh_range = self
+            .range_limits
+            .map(|limits| RefreshRange {
+                min_hz: limits.min_vertical_hz,
+                max_hz: limits.max_vertical_hz,
+            })
+            .or_else(|| {
+                let rates = timings.iter().map(|t| t.refresh_hz() as u16);
+                Some(RefreshRange {
+                    min_hz: rates.clone().min()?,
+                    max_hz: rates.max()?,
+                })
+            });
+
+        let vrr = self
+            .displayid
+            .iter()
+            .find_map(|d| d.adaptive_sync.first().copied())
+            .or_else(|| self.cta.iter().find_map(|c| c.hdmi_vrr))
+            .or_else(|| self.cta.iter().find_map(|c| c.freesync));
+
+        RefreshCapabilities {
+            timings,
+            refresh_range,
+            vrr,
+        }
+    }
+
+    /// The PNP ID and product code as Windows prints them, e.g. `DEL4123`.
+    pub fn hardware_id(&self) -> String {
+        format!("{}{:04X}", self.manufacturer, self.product_code)
+    }
+}
+
+/// Decodes the big-endian, 5-bits-per-letter PNP manufacturer ID.
+fn decode_pnp_id(bytes: [u8; 2]) -> String {
+    let value = u16::from_be_bytes(bytes);
+    [10, 5, 0]
+        .iter()
+        .map(|shift| {
+            let letter = ((value >> shift) & 0x1F) as u8;
+            if (1..=26).contains(&letter) {
+                char::from(b'A' + letter - 1)
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Decodes a descriptor string: up to 13 bytes, ended by a line feed and
+/// padded with spaces.
+fn decode_text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0x0A).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end])
+        .trim_end()
+        .to_string()
+}
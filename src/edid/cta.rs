@@ -0,0 +1,178 @@
+//! CTA-861 EDID extension blocks (tag `0x02`).
+
+use super::DetailedTiming;
+use super::RefreshRange;
+
+pub const CTA_EXTENSION_TAG: u8 = 0x02;
+
+const BLOCK_VIDEO: u8 = 2;
+const BLOCK_VENDOR: u8 = 3;
+const BLOCK_EXTENDED: u8 = 7;
+
+const EXTENDED_HF_SCDB: u8 = 0x79;
+
+const OUI_HDMI_FORUM: u32 = 0xC4_5D_D8;
+const OUI_AMD: u32 = 0x00_00_1A;
+
+/// A timing referenced by a Video Identification Code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoTiming {
+    pub vic: u8,
+    pub width: u16,
+    pub height: u16,
+    pub refresh_hz: u16,
+    pub interlaced: bool,
+    /// Whether the sink marked this VIC as its native format.
+    pub native: bool,
+}
+
+/// Decoded CTA-861 extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CtaExtension {
+    pub revision: u8,
+    /// Short video descriptors with a known VIC, in block order.
+    pub video_timings: Vec<VideoTiming>,
+    /// Detailed timings following the data block collection.
+    pub detailed_timings: Vec<DetailedTiming>,
+    /// VRRmin/VRRmax from the HDMI Forum VSDB or SCDB.
+    pub hdmi_vrr: Option<RefreshRange>,
+    /// Refresh window from the AMD FreeSync vendor-specific block.
+    pub freesync: Option<RefreshRange>,
+}
+
+impl CtaExtension {
+    /// Parses a 128-byte CTA-861 extension block, or `None` if `block` is
+    /// something else.
+    pub fn parse(block: &[u8]) -> Option<Self> {
+        if block.len() < 4 || block[0] != CTA_EXTENSION_TAG {
+            return None;
+        }
+        let mut cta = CtaExtension {
+            revision: block[1],
+            ..Default::default()
+        };
+        let dtd_offset = usize::from(block[2]).min(block.len() - 1);
+        if dtd_offset == 0 {
+            // No data blocks and no detailed timings, whatever the rest holds.
+            return Some(cta);
+        }
+
+        // Revision 1 blocks have no data block collection.
+        let mut offset = 4;
+        while cta.revision >= 3 && offset < dtd_offset {
+            let tag = block[offset] >> 5;
+            let len = usize::from(block[offset] & 0x1F);
+            let end = (offset + 1 + len).min(dtd_offset);
+            let payload = &block[offset + 1..end];
+            match tag {
+                BLOCK_VIDEO => cta
+                    .video_timings
+                    .extend(payload.iter().filter_map(|&svd| video_timing(svd))),
+                BLOCK_VENDOR if payload.len() >= 3 => {
+                    let oui = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]);
+                    match oui {
+                        OUI_HDMI_FORUM => cta.hdmi_vrr = hdmi_vrr(&payload[3..]).or(cta.hdmi_vrr),
+                        OUI_AMD => cta.freesync = freesync(&payload[3..]).or(cta.freesync),
+                        _ => {}
+                    }
+                }
+                BLOCK_EXTENDED if payload.len() >= 3 && payload[0] == EXTENDED_HF_SCDB => {
+                    // The SCDB lays out the HF-VSDB fields at the same offsets,
+                    // with the extended tag and two reserved bytes in place of the OUI.
+                    cta.hdmi_vrr = hdmi_vrr(&payload[3..]).or(cta.hdmi_vrr);
+                }
+                _ => {}
+            }
+            offset = end;
+        }
+
+        let mut offset = dtd_offset.max(4);
+        // Detailed timings run up to the checksum in the last byte.
+        while offset + 18 < block.len() {
+            match DetailedTiming::parse(&block[offset..offset + 18]) {
+                Some(timing) => cta.detailed_timings.push(timing),
+                None => break,
+            }
+            offset += 18;
+        }
+
+        Some(cta)
+    }
+}
+
+/// VRRmin/VRRmax from an HDMI Forum block, `fields` starting at the version byte.
+fn hdmi_vrr(fields: &[u8]) -> Option<RefreshRange> {
+    // fields[5] holds VRRmin (bits 5:0) and VRRmax bits 9:8; fields[6] VRRmax bits 7:0.
+    let (&packed, &max_low) = (fields.get(5)?, fields.get(6)?);
+    let min_hz = u16::from(packed & 0x3F);
+    let max_hz = (u16::from(packed >> 6) << 8) | u16::from(max_low);
+    (min_hz > 0 && max_hz > min_hz).then_some(RefreshRange { min_hz, max_hz })
+}
+
+/// Minimum/maximum refresh from an AMD vendor-specific block, `fields`
+/// starting at the version byte.
+fn freesync(fields: &[u8]) -> Option<RefreshRange> {
+    let (&min, &max) = (fields.get(2)?, fields.get(3)?);
+    (min > 0 && max > min).then(|| RefreshRange {
+        min_hz: u16::from(min),
+        max_hz: u16::from(max),
+    })
+}
+
+fn video_timing(svd: u8) -> Option<VideoTiming> {
+    // VICs 1-64 use bit 7 as the native flag; 129-192 are native VICs 1-64.
+    let (vic, native) = match svd {
+        1..=127 => (svd, false),
+        129..=192 => (svd & 0x7F, true),
+        _ => (svd, false),
+    };
+    let &(_, width, height, refresh_hz, interlaced) =
+        VIC_TIMINGS.iter().find(|entry| entry.0 == vic)?;
+    Some(VideoTiming {
+        vic,
+        width,
+        height,
+        refresh_hz,
+        interlaced,
+        native,
+    })
+}
+
+/// Common CTA-861 VICs: (vic, width, height, refresh, interlaced).
+const VIC_TIMINGS: &[(u8, u16, u16, u16, bool)] = &[
+    (1, 640, 480, 60, false),
+    (2, 720, 480, 60, false),
+    (3, 720, 480, 60, false),
+    (4, 1280, 720, 60, false),
+    (5, 1920, 1080, 60, true),
+    (16, 1920, 1080, 60, false),
+    (17, 720, 576, 50, false),
+    (18, 720, 576, 50, false),
+    (19, 1280, 720, 50, false),
+    (20, 1920, 1080, 50, true),
+    (31, 1920, 1080, 50, false),
+    (32, 1920, 1080, 24, false),
+    (33, 1920, 1080, 25, false),
+    (34, 1920, 1080, 30, false),
+    (41, 1280, 720, 100, false),
+    (47, 1280, 720, 120, false),
+    (60, 1280, 720, 24, false),
+    (61, 1280, 720, 25, false),
+    (62, 1280, 720, 30, false),
+    (63, 1920, 1080, 120, false),
+    (64, 1920, 1080, 100, false),
+    (93, 3840, 2160, 24, false),
+    (94, 3840, 2160, 25, false),
+    (95, 3840, 2160, 30, false),
+    (96, 3840, 2160, 50, false),
+    (97, 3840, 2160, 60, false),
+    (98, 4096, 2160, 24, false),
+    (99, 4096, 2160, 25, false),
+    (100, 4096, 2160, 30, false),
+    (101, 4096, 2160, 50, false),
+    (102, 4096, 2160, 60, false),
+    (117, 3840, 2160, 100, false),
+    (118, 3840, 2160, 120, false),
+    (218, 4096, 2160, 100, false),
+    (219, 4096, 2160, 120, false),
+];
@@ -0,0 +1,121 @@
+//! DisplayID sections carried in EDID extension blocks (tag `0x70`).
+
+use super::RefreshRange;
+
+pub const DISPLAYID_EXTENSION_TAG: u8 = 0x70;
+
+const BLOCK_TYPE_I_TIMING: u8 = 0x03;
+const BLOCK_TYPE_VII_TIMING: u8 = 0x22;
+const BLOCK_ADAPTIVE_SYNC: u8 = 0x2B;
+
+const TIMING_DESCRIPTOR_LEN: usize = 20;
+const ADAPTIVE_SYNC_DESCRIPTOR_LEN: usize = 6;
+
+/// A Type I (DisplayID 1.x) or Type VII (DisplayID 2.0) detailed timing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayIdTiming {
+    pub pixel_clock_khz: u32,
+    pub h_active: u16,
+    pub h_blanking: u16,
+    pub v_active: u16,
+    pub v_blanking: u16,
+    pub interlaced: bool,
+    pub preferred: bool,
+}
+
+impl DisplayIdTiming {
+    /// Parses a 20-byte descriptor whose pixel clock is stored in units of
+    /// `clock_unit_khz`, minus one.
+    fn parse(bytes: &[u8], clock_unit_khz: u32) -> Self {
+        let clock = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+        // Every size is stored minus one.
+        let field =
+            |offset: usize| (u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) & 0x7FFF) + 1;
+        DisplayIdTiming {
+            pixel_clock_khz: (clock + 1) * clock_unit_khz,
+            h_active: field(4),
+            h_blanking: field(6),
+            v_active: field(12),
+            v_blanking: field(14),
+            interlaced: bytes[3] & 0x10 != 0,
+            preferred: bytes[3] & 0x80 != 0,
+        }
+    }
+
+    /// Vertical refresh rate in millihertz.
+    pub fn refresh_millihz(&self) -> u32 {
+        // Each size can be 0x8000, so the sums need more than 16 bits.
+        let h_total = u64::from(self.h_active) + u64::from(self.h_blanking);
+        let v_total = u64::from(self.v_active) + u64::from(self.v_blanking);
+        let total = h_total * v_total;
+        if total == 0 {
+            return 0;
+        }
+        (u64::from(self.pixel_clock_khz) * 1_000_000 / total) as u32
+    }
+}
+
+/// Decoded DisplayID section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisplayIdExtension {
+    /// Version and revision, e.g. `(2, 0)`.
+    pub version: (u8, u8),
+    pub timings: Vec<DisplayIdTiming>,
+    /// Adaptive-Sync refresh windows, one per descriptor.
+    pub adaptive_sync: Vec<RefreshRange>,
+}
+
+impl DisplayIdExtension {
+    /// Parses a 128-byte EDID extension block carrying a DisplayID section,
+    /// or `None` if `block` is something else.
+    pub fn parse(block: &[u8]) -> Option<Self> {
+        if block.len() < 6 || block[0] != DISPLAYID_EXTENSION_TAG {
+            return None;
+        }
+        let mut displayid = DisplayIdExtension {
+            version: (block[1] >> 4, block[1] & 0x0F),
+            ..Default::default()
+        };
+
+        // Section header: version, payload length, product type, extension count.
+        let section_end = (5 + usize::from(block[2])).min(block.len());
+        let mut offset = 5;
+        while offset + 3 <= section_end {
+            let tag = block[offset];
+            let len = usize::from(block[offset + 2]);
+            let end = (offset + 3 + len).min(section_end);
+            let payload = &block[offset + 3..end];
+            match tag {
+                BLOCK_TYPE_I_TIMING => displayid.timings.extend(
+                    payload
+                        .chunks_exact(TIMING_DESCRIPTOR_LEN)
+                        .map(|descriptor| DisplayIdTiming::parse(descriptor, 10)),
+                ),
+                BLOCK_TYPE_VII_TIMING => displayid.timings.extend(
+                    payload
+                        .chunks_exact(TIMING_DESCRIPTOR_LEN)
+                        .map(|descriptor| DisplayIdTiming::parse(descriptor, 1)),
+                ),
+                BLOCK_ADAPTIVE_SYNC => displayid.adaptive_sync.extend(
+                    payload
+                        .chunks_exact(ADAPTIVE_SYNC_DESCRIPTOR_LEN)
+                        .filter_map(adaptive_sync_range),
+                ),
+                // A zero tag pads the rest of the section.
+                0 => break,
+                _ => {}
+            }
+            offset = end;
+        }
+
+        Some(displayid)
+    }
+}
+
+/// Refresh window of an Adaptive-Sync descriptor: minimum in byte 2, maximum
+/// minus one in the low 10 bits of bytes 3-4.
+fn adaptive_sync_range(descriptor: &[u8]) -> Option<RefreshRange> {
+    let min_hz = u16::from(descriptor[2]);
+    let max_hz = (u16::from_le_bytes([descriptor[3], descriptor[4]]) & 0x03FF) + 1;
+    (min_hz > 0 && max_hz > min_hz).then_some(RefreshRange { min_hz, max_hz })
+}
@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::backend::{
+    DISP_CHANGE_BADDUALVIEW, DISP_CHANGE_BADFLAGS, DISP_CHANGE_BADMODE, DISP_CHANGE_BADPARAM,
+    DISP_CHANGE_FAILED, DISP_CHANGE_NOTUPDATED, DISP_CHANGE_RESTART, DISP_CHANGE_SUCCESSFUL,
+};
+use crate::mode::{DisplayMode, RefreshRate};
+
+/// How a successful mode change request ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOutcome {
+    /// The new mode is active.
+    Applied,
+    /// The device was already running the requested rate, mode or
+    /// orientation; nothing was changed.
+    Unchanged,
+    /// The change was accepted but only takes effect after a restart
+    /// (`DISP_CHANGE_RESTART`).
+    RestartRequired,
+    /// Dry run: the driver would accept the change, but nothing was changed.
+    Validated,
+}
+
+/// Why a display query or mode change failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayError {
+    /// SetupAPI could not enumerate the monitor device class.
+    SetupApi { os_error: u32 },
+    /// A display, power or foreground watcher could not create its window
+    /// or register for notifications.
+    WatcherUnavailable { os_error: u32 },
+    /// `EnumDisplaySettingsW(ENUM_CURRENT_SETTINGS)` failed for the device.
+    CurrentModeUnavailable { device_name: String, os_error: u32 },
+    /// The driver does not list the requested mode for the device.
+    UnsupportedMode {
+        device_name: String,
+        mode: DisplayMode,
+    },
+    /// The display path of the device does not accept the exact refresh rate.
+    UnsupportedRefreshRate {
+        device_name: String,
+        rate: RefreshRate,
+    },
+    /// `SetDisplayConfig` refused a change it had validated.
+    DisplayConfigFailed { device_name: String, os_error: u32 },
+    /// `DISP_CHANGE_FAILED`: the driver failed the mode change.
+    ChangeFailed { device_name: String },
+    /// `DISP_CHANGE_BADMODE`: the mode is not supported.
+    BadMode { device_name: String },
+    /// `DISP_CHANGE_NOTUPDATED`: the settings could not be written to the registry.
+    NotUpdated { device_name: String },
+    /// `DISP_CHANGE_BADFLAGS`: an invalid set of `CDS_*` flags was passed.
+    BadFlags { device_name: String },
+    /// `DISP_CHANGE_BADPARAM`: an invalid parameter, such as an unknown device name.
+    BadParam { device_name: String },
+    /// `DISP_CHANGE_BADDUALVIEW`: the system is DualView capable and refused the change.
+    BadDualView { device_name: String },
+    /// A `ChangeDisplaySettingsExW` result this crate does not know about.
+    UnknownChangeCode { device_name: String, code: i32 },
+    /// A monitor a snapshot or profile refers to is not connected.
+    MonitorNotFound { monitor: String },
+    /// `ChangeDisplaySettingsExW(NULL, ..)` failed to apply the modes staged
+    /// for a batch; the previous modes were put back.
+    CommitFailed {
+        device_names: Vec<String>,
+        code: i32,
+    },
+}
+
+impl DisplayError {
+    /// Maps a failing `DISP_CHANGE_*` code to its error.
+    pub fn from_change_code(device_name: &str, code: i32) -> Self {
+        let device_name = device_name.to_string();
+        match code {
+            DISP_CHANGE_FAILED => DisplayError::ChangeFailed { device_name },
+            DISP_CHANGE_BADMODE => DisplayError::BadMode { device_name },
+            DISP_CHANGE_NOTUPDATED => DisplayError::NotUpdated { device_name },
+            DISP_CHANGE_BADFLAGS => DisplayError::BadFlags { device_name },
+            DISP_CHANGE_BADPARAM => DisplayError::BadParam { device_name },
+            DISP_CHANGE_BADDUALVIEW => DisplayError::BadDualView { device_name },
+            code => DisplayError::UnknownChangeCode { device_name, code },
+        }
+    }
+
+    /// The `DISP_CHANGE_*` code behind this error, if it came from
+    /// `ChangeDisplaySettingsExW`.
+    pub fn change_code(&self) -> Option<i32> {
+        match self {
+            DisplayError::ChangeFailed { .. } => Some(DISP_CHANGE_FAILED),
+            DisplayError::BadMode { .. } => Some(DISP_CHANGE_BADMODE),
+            DisplayError::NotUpdated { .. } => Some(DISP_CHANGE_NOTUPDATED),
+            DisplayError::BadFlags { .. } => Some(DISP_CHANGE_BADFLAGS),
+            DisplayError::BadParam { .. } => Some(DISP_CHANGE_BADPARAM),
+            DisplayError::BadDualView { .. } => Some(DISP_CHANGE_BADDUALVIEW),
+            DisplayError::UnknownChangeCode { code, .. } => Some(*code),
+            DisplayError::CommitFailed { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+/// Interprets a `ChangeDisplaySettingsExW` result for `device_name`.
+pub fn check_change_code(device_name: &str, code: i32) -> Result<ChangeOutcome, DisplayError> {
+    match code {
+        DISP_CHANGE_SUCCESSFUL => Ok(ChangeOutcome::Applied),
+        DISP_CHANGE_RESTART => Ok(ChangeOutcome::RestartRequired),
+        code => Err(DisplayError::from_change_code(device_name, code)),
+    }
+}
+
+impl fmt::Display for DisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayError::SetupApi { os_error } => {
+                write!(
+                    f,
+                    "could not enumerate monitor devices (error {})",
+                    os_error
+                )
+            }
+            DisplayError::WatcherUnavailable { os_error } => {
+                write!(
+                    f,
+                    "could not start watching for display changes (error {})",
+                    os_error
+                )
+            }
+            DisplayError::CurrentModeUnavailable {
+                device_name,
+                os_error,
+            } => write!(
+                f,
+                "could not read the current display settings of {} (error {})",
+                device_name, os_error
+            ),
+            DisplayError::UnsupportedMode { device_name, mode } => {
+                write!(f, "{} does not support {}", device_name, mode)
+            }
+            DisplayError::UnsupportedRefreshRate { device_name, rate } => {
+                write!(f, "{} does not support {}", device_name, rate)
+            }
+            DisplayError::DisplayConfigFailed {
+                device_name,
+                os_error,
+            } => write!(
+                f,
+                "could not apply the display configuration of {} (error {})",
+                device_name, os_error
+            ),
+            DisplayError::ChangeFailed { device_name } => {
+                write!(
+                    f,
+                    "the display driver failed to change the mode of {}",
+                    device_name
+                )
+            }
+            DisplayError::BadMode { device_name } => {
+                write!(f, "the requested mode is not supported by {}", device_name)
+            }
+            DisplayError::NotUpdated { device_name } => {
+                write!(
+                    f,
+                    "could not write the settings of {} to the registry",
+                    device_name
+                )
+            }
+            DisplayError::BadFlags { device_name } => {
+                write!(f, "invalid flags for a mode change on {}", device_name)
+            }
+            DisplayError::BadParam { device_name } => {
+                write!(f, "invalid parameter for a mode change on {}", device_name)
+            }
+            DisplayError::BadDualView { device_name } => {
+                write!(
+                    f,
+                    "{} is part of a DualView system and cannot be changed",
+                    device_name
+                )
+            }
+            DisplayError::UnknownChangeCode { device_name, code } => {
+                write!(
+                    f,
+                    "changing the mode of {} failed with code {}",
+                    device_name, code
+                )
+            }
+            DisplayError::MonitorNotFound { monitor } => {
+                write!(f, "monitor {} is not connected", monitor)
+            }
+            DisplayError::CommitFailed { device_names, code } => {
+                write!(
+                    f,
+                    "applying the staged modes of {} failed with code {}",
+                    device_names.join(", "),
+                    code
+                )
+            }
+        }
+    }
+}
+
+impl Error for DisplayError {}
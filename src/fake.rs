@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::backend::{
+    DisplayBackend, MonitorDevNode, RawDisplayDevice, CDS_NORESET, CDS_SET_PRIMARY, CDS_TEST,
+    DISPLAY_DEVICE_ACTIVE, DISPLAY_DEVICE_ATTACHED, DISPLAY_DEVICE_ATTACHED_TO_DESKTOP,
+    DISPLAY_DEVICE_PRIMARY_DEVICE, DISP_CHANGE_BADMODE, DISP_CHANGE_BADPARAM,
+    DISP_CHANGE_SUCCESSFUL, SDC_VALIDATE,
+};
+use crate::devnode::{instance_id_from_interface_path, split_monitor_device_id};
+use crate::mode::{compatible_modes, refresh_rates, DisplayMode, Position, RefreshRate};
+use crate::GENERIC_MONITOR_NAME;
+
+/// A scripted adapter output for `FakeBackend`.
+#[derive(Debug, Clone)]
+pub struct FakeAdapter {
+    pub device: RawDisplayDevice,
+    pub monitors: Vec<RawDisplayDevice>,
+    pub modes: Vec<DisplayMode>,
+    pub current: DisplayMode,
+    /// Exact rates the display path accepts at the current resolution; when
+    /// empty, the integer rates of the compatible modes.
+    pub exact_rates: Vec<RefreshRate>,
+    /// Exact current rate; when `None`, the current mode's integer rate.
+    pub current_rate: Option<RefreshRate>,
+    pub position: Position,
+    /// Change staged with `CDS_NORESET`, applied by the next `commit_modes`.
+    pub staged: Option<StagedChange>,
+}
+
+/// A `FakeAdapter` change waiting for `commit_modes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StagedChange {
+    pub mode: DisplayMode,
+    pub position: Option<Position>,
+    pub primary: bool,
+}
+
+impl FakeAdapter {
+    /// An adapter output attached to the desktop with no monitors or modes.
+    pub fn new(device_name: &str) -> Self {
+        FakeAdapter {
+            device: RawDisplayDevice {
+                device_name: device_name.to_string(),
+                device_string: "Fake Display Adapter".to_string(),
+                state_flags: DISPLAY_DEVICE_ATTACHED_TO_DESKTOP,
+                device_id: String::new(),
+                interface_path: String::new(),
+            },
+            monitors: Vec::new(),
+            modes: Vec::new(),
+            current: DisplayMode::default(),
+            exact_rates: Vec::new(),
+            current_rate: None,
+            position: Position::default(),
+            staged: None,
+        }
+    }
+
+    pub fn primary(mut self) -> Self {
+        self.device.state_flags |= DISPLAY_DEVICE_PRIMARY_DEVICE;
+        self
+    }
+
+    pub fn detached(mut self) -> Self {
+        self.device.state_flags &= !DISPLAY_DEVICE_ATTACHED_TO_DESKTOP;
+        self
+    }
+
+    /// Adds an active monitor called `device_string`. Its device name follows
+    /// the `\\.\DISPLAYn\Monitorm` scheme Windows uses, and its IDs are
+    /// derived from the adapter and monitor index.
+    pub fn monitor(self, device_string: &str) -> Self {
+        let index = self.monitors.len();
+        let hardware_id = format!(
+            "FAK{:04}",
+            fake_adapter_number(&self.device.device_name) * 10 + index
+        );
+        self.monitor_with_id(device_string, &hardware_id)
+    }
+
+    /// Adds an active monitor whose device ID and interface path use the PnP
+    /// hardware ID `hardware_id` (e.g. `DEL4123`).
+    pub fn monitor_with_id(mut self, device_string: &str, hardware_id: &str) -> Self {
+        let index = self.monitors.len();
+        let uid = fake_adapter_number(&self.device.device_name) * 256 + index;
+        self.monitors.push(RawDisplayDevice {
+            device_name: format!("{}\\Monitor{}", self.device.device_name, index),
+            device_string: device_string.to_string(),
+            state_flags: DISPLAY_DEVICE_ACTIVE | DISPLAY_DEVICE_ATTACHED,
+            device_id: format!(
+                "MONITOR\\{}\\{{4d36e96e-e325-11ce-bfc1-08002be10318}}\\{:04}",
+                hardware_id, uid
+            ),
+            interface_path: format!(
+                "\\\\?\\DISPLAY#{}#5&fake&0&UID{}#{{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}}",
+                hardware_id, uid
+            ),
+        });
+        self
+    }
+
+    /// Sets the mode list; the first mode becomes the current one.
+    pub fn modes(mut self, modes: &[DisplayMode]) -> Self {
+        self.modes = modes.to_vec();
+        if let Some(first) = modes.first() {
+            self.current = *first;
+        }
+        self
+    }
+
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Position::new(x, y);
+        self
+    }
+
+    pub fn current(mut self, mode: DisplayMode) -> Self {
+        self.current = mode;
+        self
+    }
+
+    /// Sets the exact rates `SetDisplayConfig` accepts, e.g. both 60 Hz and
+    /// 59.94 Hz where `EnumDisplaySettingsW` lists 59 and 60.
+    pub fn exact_rates(mut self, rates: &[RefreshRate]) -> Self {
+        self.exact_rates = rates.to_vec();
+        self
+    }
+
+    pub fn current_rate(mut self, rate: RefreshRate) -> Self {
+        self.current_rate = Some(rate);
+        self
+    }
+
+    /// `modes` as `EnumDisplaySettingsW` lists them: turned to the current
+    /// orientation.
+    fn listed_modes(&self) -> Vec<DisplayMode> {
+        self.modes
+            .iter()
+            .map(|mode| mode.rotated(self.current.orientation))
+            .collect()
+    }
+
+    fn available_exact_rates(&self) -> Vec<RefreshRate> {
+        if !self.exact_rates.is_empty() {
+            return self.exact_rates.clone();
+        }
+        refresh_rates(&compatible_modes(&self.listed_modes(), &self.current))
+            .into_iter()
+            .map(RefreshRate::integer)
+            .collect()
+    }
+}
+
+/// A devnode that matches `monitor` by instance ID, driver key and hardware
+/// ID, as Windows would report it.
+pub fn devnode_for(monitor: &RawDisplayDevice) -> MonitorDevNode {
+    let (hardware_id, driver_key) = split_monitor_device_id(&monitor.device_id).unwrap_or_default();
+    MonitorDevNode {
+        instance_id: instance_id_from_interface_path(&monitor.interface_path).unwrap_or_default(),
+        hardware_ids: vec![hardware_id.to_string()],
+        driver_key: Some(driver_key.to_string()),
+        friendly_name: None,
+        device_desc: Some(GENERIC_MONITOR_NAME.to_string()),
+    }
+}
+
+/// The `n` of a `\\.\DISPLAYn` name, or 0.
+fn fake_adapter_number(device_name: &str) -> usize {
+    device_name
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .unwrap_or(0)
+}
+
+/// A mode change `FakeBackend` was asked to perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedChange {
+    pub device_name: String,
+    pub mode: DisplayMode,
+    /// Set for `apply_layout` calls.
+    pub position: Option<Position>,
+    pub flags: u32,
+}
+
+/// A `SetDisplayConfig` refresh rate change `FakeBackend` was asked to perform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedRate {
+    pub device_name: String,
+    pub rate: RefreshRate,
+    pub flags: u32,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    adapters: Vec<FakeAdapter>,
+    devnodes: Vec<MonitorDevNode>,
+    devnodes_error: Option<u32>,
+    edids: HashMap<String, Vec<u8>>,
+    query_errors: HashMap<String, u32>,
+    apply_results: HashMap<String, i32>,
+    config_errors: HashMap<String, u32>,
+    commit_result: Option<i32>,
+    applied: Vec<AppliedChange>,
+    applied_rates: Vec<AppliedRate>,
+    commits: usize,
+}
+
+/// An in-memory `DisplayBackend` with scriptable adapters, monitors, mode
+/// lists and failure codes.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    state: Mutex<FakeState>,
+}
+
+impl FakeBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_adapter(self, adapter: FakeAdapter) -> Self {
+        self.state.lock().unwrap().adapters.push(adapter);
+        self
+    }
+
+    pub fn with_devnode(self, devnode: MonitorDevNode) -> Self {
+        self.state.lock().unwrap().devnodes.push(devnode);
+        self
+    }
+
+    /// Serves `edid` for the monitor with the given device interface path.
+    pub fn with_edid(self, interface_path: &str, edid: &[u8]) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .edids
+            .insert(interface_path.to_string(), edid.to_vec());
+        self
+    }
+
+    /// Adds `adapter` to a running backend, as if it was plugged in.
+    pub fn add_adapter(&self, adapter: FakeAdapter) {
+        self.state.lock().unwrap().adapters.push(adapter);
+    }
+
+    /// Removes the adapter output `device_name`, as if it was unplugged.
+    pub fn remove_adapter(&self, device_name: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .adapters
+            .retain(|a| a.device.device_name != device_name);
+    }
+
+    /// Makes `device_name` the only primary adapter output.
+    pub fn set_primary(&self, device_name: &str) {
+        for adapter in &mut self.state.lock().unwrap().adapters {
+            if adapter.device.device_name == device_name {
+                adapter.device.state_flags |= DISPLAY_DEVICE_PRIMARY_DEVICE;
+            } else {
+                adapter.device.state_flags &= !DISPLAY_DEVICE_PRIMARY_DEVICE;
+            }
+        }
+    }
+
+    /// Makes the SetupAPI enumeration fail with `error`.
+    pub fn fail_devnodes(&self, error: u32) {
+        self.state.lock().unwrap().devnodes_error = Some(error);
+    }
+
+    /// Makes the current-mode query for `device_name` fail with `error`.
+    pub fn fail_current_mode(&self, device_name: &str, error: u32) {
+        self.state
+            .lock()
+            .unwrap()
+            .query_errors
+            .insert(device_name.to_string(), error);
+    }
+
+    /// Makes every later `apply_mode` on `device_name`, including `CDS_TEST`
+    /// dry runs, return `code` without changing anything.
+    pub fn fail_apply(&self, device_name: &str, code: i32) {
+        self.state
+            .lock()
+            .unwrap()
+            .apply_results
+            .insert(device_name.to_string(), code);
+    }
+
+    /// Makes every later CCD query or change on `device_name` fail with
+    /// `error`, as if the path could not be found.
+    pub fn fail_config(&self, device_name: &str, error: u32) {
+        self.state
+            .lock()
+            .unwrap()
+            .config_errors
+            .insert(device_name.to_string(), error);
+    }
+
+    /// Makes every later `commit_modes` return `code` without applying the
+    /// staged modes.
+    pub fn fail_commit(&self, code: i32) {
+        self.state.lock().unwrap().commit_result = Some(code);
+    }
+
+    /// Removes every scripted failure.
+    pub fn clear_failures(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.devnodes_error = None;
+        state.query_errors.clear();
+        state.apply_results.clear();
+        state.config_errors.clear();
+        state.commit_result = None;
+    }
+
+    /// Changes successfully applied or staged so far, oldest first. Dry runs
+    /// are not recorded.
+    pub fn applied(&self) -> Vec<AppliedChange> {
+        self.state.lock().unwrap().applied.clone()
+    }
+
+    /// Refresh rate changes successfully applied through the CCD path so far,
+    /// oldest first. Validations are not recorded.
+    pub fn applied_rates(&self) -> Vec<AppliedRate> {
+        self.state.lock().unwrap().applied_rates.clone()
+    }
+
+    /// How many times staged modes were committed successfully.
+    pub fn commits(&self) -> usize {
+        self.state.lock().unwrap().commits
+    }
+
+    fn change_layout(
+        &self,
+        device_name: &str,
+        mode: &DisplayMode,
+        position: Option<Position>,
+        flags: u32,
+    ) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        if let Some(&code) = state.apply_results.get(device_name) {
+            return code;
+        }
+        let Some(adapter) = state
+            .adapters
+            .iter_mut()
+            .find(|a| a.device.device_name == device_name)
+        else {
+            return DISP_CHANGE_BADPARAM;
+        };
+        // Listed modes are accepted in any orientation.
+        if !adapter
+            .modes
+            .iter()
+            .any(|listed| listed.rotated(mode.orientation) == *mode)
+        {
+            return DISP_CHANGE_BADMODE;
+        }
+        if flags & CDS_TEST != 0 {
+            return DISP_CHANGE_SUCCESSFUL;
+        }
+        let change = StagedChange {
+            mode: *mode,
+            position,
+            primary: flags & CDS_SET_PRIMARY != 0,
+        };
+        if flags & CDS_NORESET != 0 {
+            adapter.staged = Some(change);
+        } else {
+            state.apply_change(device_name, change);
+        }
+        state.applied.push(AppliedChange {
+            device_name: device_name.to_string(),
+            mode: *mode,
+            position,
+            flags,
+        });
+        DISP_CHANGE_SUCCESSFUL
+    }
+}
+
+impl FakeState {
+    fn apply_change(&mut self, device_name: &str, change: StagedChange) {
+        for adapter in &mut self.adapters {
+            if adapter.device.device_name == device_name {
+                adapter.current = change.mode;
+                adapter.current_rate = None;
+                if let Some(position) = change.position {
+                    adapter.position = position;
+                }
+                if change.primary {
+                    adapter.device.state_flags |= DISPLAY_DEVICE_PRIMARY_DEVICE;
+                }
+            } else if change.primary {
+                adapter.device.state_flags &= !DISPLAY_DEVICE_PRIMARY_DEVICE;
+            }
+        }
+    }
+}
+
+impl DisplayBackend for FakeBackend {
+    fn adapters(&self) -> Vec<RawDisplayDevice> {
+        let state = self.state.lock().unwrap();
+        state.adapters.iter().map(|a| a.device.clone()).collect()
+    }
+
+    fn monitors(&self, adapter_name: &str) -> Vec<RawDisplayDevice> {
+        let state = self.state.lock().unwrap();
+        state
+            .adapters
+            .iter()
+            .find(|a| a.device.device_name == adapter_name)
+            .map(|a| a.monitors.clone())
+            .unwrap_or_default()
+    }
+
+    fn monitor_devnodes(&self) -> Result<Vec<MonitorDevNode>, u32> {
+        let state = self.state.lock().unwrap();
+        match state.devnodes_error {
+            Some(error) => Err(error),
+            None => Ok(state.devnodes.clone()),
+        }
+    }
+
+    fn monitor_edid(&self, interface_path: &str) -> Option<Vec<u8>> {
+        self.state
+            .lock()
+            .unwrap()
+            .edids
+            .get(interface_path)
+            .cloned()
+    }
+
+    fn modes(&self, device_name: &str) -> Vec<DisplayMode> {
+        let state = self.state.lock().unwrap();
+        state
+            .adapters
+            .iter()
+            .find(|a| a.device.device_name == device_name)
+            .map(FakeAdapter::listed_modes)
+            .unwrap_or_default()
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode, u32> {
+        let state = self.state.lock().unwrap();
+        if let Some(&error) = state.query_errors.get(device_name) {
+            return Err(error);
+        }
+        state
+            .adapters
+            .iter()
+            .find(|a| a.device.device_name == device_name)
+            .map(|a| a.current)
+            // ERROR_INVALID_PARAMETER, as EnumDisplaySettingsW reports it.
+            .ok_or(87)
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode, flags: u32) -> i32 {
+        self.change_layout(device_name, mode, None, flags)
+    }
+
+    fn current_position(&self, device_name: &str) -> Result<Position, u32> {
+        let state = self.state.lock().unwrap();
+        if let Some(&error) = state.query_errors.get(device_name) {
+            return Err(error);
+        }
+        state
+            .adapters
+            .iter()
+            .find(|a| a.device.device_name == device_name)
+            .map(|a| a.position)
+            .ok_or(1168)
+    }
+
+    fn apply_layout(
+        &self,
+        device_name: &str,
+        mode: &DisplayMode,
+        position: Position,
+        flags: u32,
+    ) -> i32 {
+        self.change_layout(device_name, mode, Some(position), flags)
+    }
+
+    fn commit_modes(&self) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        if let Some(code) = state.commit_result {
+            return code;
+        }
+        let staged: Vec<(String, StagedChange)> = state
+            .adapters
+            .iter_mut()
+            .filter_map(|a| Some((a.device.device_name.clone(), a.staged.take()?)))
+            .collect();
+        for (device_name, change) in staged {
+            state.apply_change(&device_name, change);
+        }
+        state.commits += 1;
+        DISP_CHANGE_SUCCESSFUL
+    }
+
+    fn current_refresh_rate(&self, device_name: &str) -> Result<RefreshRate, u32> {
+        let state = self.state.lock().unwrap();
+        if let Some(&error) = state.config_errors.get(device_name) {
+            return Err(error);
+        }
+        state
+            .adapters
+            .iter()
+            .find(|a| a.device.device_name == device_name)
+            .map(|a| {
+                a.current_rate
+                    .unwrap_or(RefreshRate::integer(a.current.frequency))
+            })
+            // ERROR_NOT_FOUND, as no active path has this source.
+            .ok_or(1168)
+    }
+
+    fn apply_refresh_rate(&self, device_name: &str, rate: RefreshRate, flags: u32) -> u32 {
+        let mut state = self.state.lock().unwrap();
+        if let Some(&error) = state.config_errors.get(device_name) {
+            return error;
+        }
+        let Some(adapter) = state
+            .adapters
+            .iter_mut()
+            .find(|a| a.device.device_name == device_name)
+        else {
+            return 1168;
+        };
+        if !adapter.available_exact_rates().contains(&rate) {
+            // ERROR_INVALID_PARAMETER, as SetDisplayConfig reports an unsupported mode.
+            return 87;
+        }
+        if flags & SDC_VALIDATE != 0 {
+            return 0;
+        }
+        adapter.current_rate = Some(rate);
+        adapter.current.frequency = rate.dm_frequency();
+        state.applied_rates.push(AppliedRate {
+            device_name: device_name.to_string(),
+            rate,
+            flags,
+        });
+        0
+    }
+}
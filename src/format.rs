@@ -0,0 +1,186 @@
+//! Reading typed values out of `toml::Value` documents.
+//!
+//! Snapshots, profiles and the config file are all checked field by field
+//! with `Fields`, so every error names the offending field, e.g.
+//! `monitors[1].mode.width`.
+
+use std::error::Error;
+use std::fmt;
+
+use toml::value::{Table, Value};
+
+/// Why a snapshot, profile or config document could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The text is not valid TOML.
+    Toml(toml::de::Error),
+    /// The text is not valid JSON, or holds a `null`, which TOML has no
+    /// counterpart for. Line and column are 1-based.
+    Json {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    /// The file was written by a newer version.
+    UnsupportedVersion(i64),
+    /// A field is missing or has the wrong type, e.g. `monitors[1].mode.width`.
+    InvalidField {
+        path: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Toml(error) => write!(f, "invalid TOML: {}", error),
+            FormatError::Json { message, .. } => write!(f, "invalid JSON: {}", message),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            FormatError::InvalidField { path, expected } => {
+                write!(f, "`{}` must be {}", path, expected)
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+/// Typed access to the fields of a table, reporting errors with the path
+/// from the document root.
+pub(crate) struct Fields<'a> {
+    table: &'a Table,
+    path: String,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn root(value: &'a Value) -> Result<Self, FormatError> {
+        match value {
+            Value::Table(table) => Ok(Fields {
+                table,
+                path: String::new(),
+            }),
+            _ => Err(FormatError::InvalidField {
+                path: String::new(),
+                expected: "a table",
+            }),
+        }
+    }
+
+    fn path_of(&self, key: &str) -> String {
+        if self.path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.path, key)
+        }
+    }
+
+    pub(crate) fn invalid(&self, key: &str, expected: &'static str) -> FormatError {
+        FormatError::InvalidField {
+            path: self.path_of(key),
+            expected,
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&'a Value> {
+        self.table.get(key)
+    }
+
+    pub(crate) fn string(&self, key: &str) -> Result<String, FormatError> {
+        match self.get(key) {
+            Some(Value::String(s)) => Ok(s.clone()),
+            _ => Err(self.invalid(key, "a string")),
+        }
+    }
+
+    pub(crate) fn boolean(&self, key: &str) -> Result<bool, FormatError> {
+        match self.get(key) {
+            Some(Value::Boolean(b)) => Ok(*b),
+            _ => Err(self.invalid(key, "true or false")),
+        }
+    }
+
+    pub(crate) fn integer(&self, key: &str) -> Result<i64, FormatError> {
+        match self.get(key) {
+            Some(Value::Integer(i)) => Ok(*i),
+            _ => Err(self.invalid(key, "an integer")),
+        }
+    }
+
+    pub(crate) fn unsigned(&self, key: &str) -> Result<u32, FormatError> {
+        u32::try_from(self.integer(key)?).map_err(|_| self.invalid(key, "a non-negative integer"))
+    }
+
+    pub(crate) fn signed(&self, key: &str) -> Result<i32, FormatError> {
+        i32::try_from(self.integer(key)?).map_err(|_| self.invalid(key, "a 32-bit integer"))
+    }
+
+    /// A string that may be left out.
+    pub(crate) fn optional_string(&self, key: &str) -> Result<Option<String>, FormatError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(_) => self.string(key).map(Some),
+        }
+    }
+
+    /// A non-negative integer that may be left out.
+    pub(crate) fn optional_unsigned(&self, key: &str) -> Result<Option<u32>, FormatError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(_) => self.unsigned(key).map(Some),
+        }
+    }
+
+    pub(crate) fn table(&self, key: &str) -> Result<Fields<'a>, FormatError> {
+        self.optional_table(key)?
+            .ok_or_else(|| self.invalid(key, "a table"))
+    }
+
+    pub(crate) fn optional_table(&self, key: &str) -> Result<Option<Fields<'a>>, FormatError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Table(table)) => Ok(Some(Fields {
+                table,
+                path: self.path_of(key),
+            })),
+            Some(_) => Err(self.invalid(key, "a table")),
+        }
+    }
+
+    /// An array of strings; a missing key is an empty array.
+    pub(crate) fn strings(&self, key: &str) -> Result<Vec<String>, FormatError> {
+        let items = match self.get(key) {
+            None => return Ok(Vec::new()),
+            Some(Value::Array(items)) => items,
+            Some(_) => return Err(self.invalid(key, "an array of strings")),
+        };
+        items
+            .iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s.clone()),
+                _ => Err(self.invalid(key, "an array of strings")),
+            })
+            .collect()
+    }
+
+    /// An array of tables; a missing key is an empty array.
+    pub(crate) fn tables(&self, key: &str) -> Result<Vec<Fields<'a>>, FormatError> {
+        let items = match self.get(key) {
+            None => return Ok(Vec::new()),
+            Some(Value::Array(items)) => items,
+            Some(_) => return Err(self.invalid(key, "an array of tables")),
+        };
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| match item {
+                Value::Table(table) => Ok(Fields {
+                    table,
+                    path: format!("{}[{}]", self.path_of(key), i),
+                }),
+                _ => Err(self.invalid(key, "an array of tables")),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,368 @@
+//! Refresh rates forced while any application runs full screen, e.g. a
+//! monitor's highest rate whenever a game or video covers it.
+//!
+//! Rules live in the config file:
+//!
+//! ```toml
+//! [[fullscreen]]
+//! monitor = "DEL4123"
+//! exclude = ["vlc.exe", 'C:\Program Files\Mozilla Firefox\*.exe']
+//!
+//! [[fullscreen]]
+//! refresh_rate = 120
+//! ```
+//!
+//! `monitor` is matched like a profile's, see `DisplayDevice::matches_id`;
+//! without it the rule applies to every monitor. For each monitor only the
+//! first rule that applies to it counts. `refresh_rate` defaults to the
+//! highest rate the monitor supports at its current resolution. `exclude`
+//! lists applications, written like an app rule's `app`, that never trigger
+//! the rule.
+//!
+//! An application is full screen on a monitor when its window covers the
+//! whole monitor and either has no title bar or Windows reports a
+//! full-screen Direct3D application. Windows reports that state for the
+//! session, not for a window, so it never counts on its own.
+//! `fullscreen_rates_with` turns a `ForegroundWindow` into the rates to
+//! force, which `RateOverrides` applies and later restores like those of app
+//! rules. On Windows, `FullscreenWatcher` reports the foreground window
+//! whenever it changes.
+
+use toml::value::{Table, Value};
+
+use crate::backend::DisplayBackend;
+use crate::format::{Fields, FormatError};
+use crate::rules::{app_matches, ForcedRate};
+use crate::{get_available_refresh_rates_with, DisplayDevice};
+
+/// `QUERY_USER_NOTIFICATION_STATE` values meaning a full-screen application
+/// is running: `QUNS_BUSY` and `QUNS_RUNNING_D3D_FULL_SCREEN`.
+const FULLSCREEN_NOTIFICATION_STATES: [u32; 2] = [2, 3];
+
+/// Whether `state`, as `SHQueryUserNotificationState` reports it, means a
+/// full-screen application is running.
+pub fn fullscreen_notification_state(state: u32) -> bool {
+    FULLSCREEN_NOTIFICATION_STATES.contains(&state)
+}
+
+/// A rectangle in desktop coordinates; `right` and `bottom` are exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub const fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    /// Whether `other` lies entirely within this rectangle.
+    pub fn contains(&self, other: &Rect) -> bool {
+        self.left <= other.left
+            && self.top <= other.top
+            && self.right >= other.right
+            && self.bottom >= other.bottom
+    }
+}
+
+/// What the fullscreen rules look at of the foreground window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForegroundWindow {
+    /// Executable path of the owning process; `None` when it could not be
+    /// queried.
+    pub path: Option<String>,
+    /// The window rectangle in physical pixels.
+    pub bounds: Rect,
+    /// Whether the window has a title bar. Maximized windows have one and
+    /// may cover the monitor when the taskbar hides itself.
+    pub has_caption: bool,
+    /// Whether Windows reports a full-screen application somewhere in the
+    /// session, see `fullscreen_notification_state`. It says nothing about
+    /// which window that is.
+    pub fullscreen_state: bool,
+}
+
+impl ForegroundWindow {
+    /// Whether the window is full screen on the monitor at `monitor`: it
+    /// covers the monitor, and has no title bar unless Windows reports a
+    /// full-screen application.
+    pub fn is_fullscreen_on(&self, monitor: &Rect) -> bool {
+        self.bounds.contains(monitor) && (!self.has_caption || self.fullscreen_state)
+    }
+}
+
+/// A refresh rate to force while an application is full screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FullscreenRule {
+    /// Device interface path, PnP device ID or hardware ID of the monitor;
+    /// `None` means every monitor.
+    pub monitor: Option<String>,
+    /// Rate in hertz; `None` means the highest the monitor supports.
+    pub refresh_rate: Option<u32>,
+    /// Applications that never trigger the rule, written like
+    /// `AppRule::app`.
+    pub exclude: Vec<String>,
+}
+
+impl FullscreenRule {
+    pub fn applies_to(&self, device: &DisplayDevice) -> bool {
+        match &self.monitor {
+            Some(monitor) => device.matches_id(monitor),
+            None => true,
+        }
+    }
+
+    /// Whether the executable at `path` is on the exclusion list.
+    pub fn excludes(&self, path: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|pattern| app_matches(pattern, path))
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        if let Some(monitor) = &self.monitor {
+            table.insert("monitor".to_string(), Value::String(monitor.clone()));
+        }
+        if let Some(rate) = self.refresh_rate {
+            table.insert("refresh_rate".to_string(), Value::Integer(rate.into()));
+        }
+        if !self.exclude.is_empty() {
+            table.insert(
+                "exclude".to_string(),
+                Value::Array(self.exclude.iter().cloned().map(Value::String).collect()),
+            );
+        }
+        Value::Table(table)
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Result<Self, FormatError> {
+        Ok(FullscreenRule {
+            monitor: fields.optional_string("monitor")?,
+            refresh_rate: fields.optional_unsigned("refresh_rate")?,
+            exclude: fields.strings("exclude")?,
+        })
+    }
+}
+
+pub(crate) fn fullscreen_rules_from_fields(
+    fields: &Fields,
+) -> Result<Vec<FullscreenRule>, FormatError> {
+    fields
+        .tables("fullscreen")?
+        .iter()
+        .map(FullscreenRule::from_fields)
+        .collect()
+}
+
+/// The area `device_name` covers on the desktop, from its position and
+/// current resolution.
+pub fn monitor_rect_with(backend: &dyn DisplayBackend, device_name: &str) -> Option<Rect> {
+    let position = backend.current_position(device_name).ok()?;
+    let mode = backend.current_mode(device_name).ok()?;
+    Some(Rect::new(
+        position.x,
+        position.y,
+        position.x + mode.width as i32,
+        position.y + mode.height as i32,
+    ))
+}
+
+/// The rates `rules` want while `window` is in the foreground: one for each
+/// monitor it is full screen on, unless the first rule for that monitor
+/// excludes the application. Monitors whose position or mode cannot be read
+/// are skipped.
+pub fn fullscreen_rates_with(
+    backend: &dyn DisplayBackend,
+    rules: &[FullscreenRule],
+    window: &ForegroundWindow,
+    devices: &[DisplayDevice],
+) -> Vec<ForcedRate> {
+    let mut forced = Vec::new();
+    for device in devices.iter().filter(|device| device.is_attached) {
+        let Some(rule) = rules.iter().find(|rule| rule.applies_to(device)) else {
+            continue;
+        };
+        if window
+            .path
+            .as_deref()
+            .is_some_and(|path| rule.excludes(path))
+        {
+            continue;
+        }
+        let Some(rect) = monitor_rect_with(backend, &device.device_name) else {
+            continue;
+        };
+        if !window.is_fullscreen_on(&rect) {
+            continue;
+        }
+        let refresh_rate = match rule.refresh_rate {
+            Some(rate) => rate,
+            None => match get_available_refresh_rates_with(backend, &device.device_name)
+                .into_iter()
+                .max()
+            {
+                Some(rate) => rate,
+                None => continue,
+            },
+        };
+        forced.push(ForcedRate {
+            device_name: device.device_name.clone(),
+            refresh_rate,
+        });
+    }
+    forced
+}
+
+#[cfg(windows)]
+pub use self::poll::FullscreenWatcher;
+
+#[cfg(windows)]
+mod poll {
+    use std::mem;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::time::Duration;
+
+    use log::{debug, warn};
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::windef::{
+        DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, HWND, RECT,
+    };
+    use winapi::shared::winerror::SUCCEEDED;
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::libloaderapi::{GetModuleHandleW, GetProcAddress};
+    use winapi::um::processthreadsapi::GetCurrentProcessId;
+    use winapi::um::shellapi::{SHQueryUserNotificationState, QUERY_USER_NOTIFICATION_STATE};
+    use winapi::um::winuser::{
+        GetClassNameW, GetForegroundWindow, GetShellWindow, GetWindowLongW, GetWindowRect,
+        GetWindowThreadProcessId, GWL_STYLE, WS_CAPTION,
+    };
+
+    use super::{fullscreen_notification_state, ForegroundWindow, Rect};
+    use crate::rules::executable_path;
+    use crate::watcher::WatcherThread;
+    use crate::{to_wide_string, DisplayError};
+
+    /// `SetThreadDpiAwarenessContext`, which user32 only exports from
+    /// Windows 10 1607 on.
+    type SetThreadDpiAwarenessContextFn =
+        unsafe extern "system" fn(DPI_AWARENESS_CONTEXT) -> DPI_AWARENESS_CONTEXT;
+
+    /// Windows has no event for a window going full screen, so the watcher
+    /// looks this often.
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Window classes of the desktop, which covers every monitor without
+    /// being full screen.
+    const DESKTOP_CLASSES: [&str; 2] = ["Progman", "WorkerW"];
+
+    /// Watches the foreground window on a thread of its own and hands it to
+    /// a callback there whenever it, its bounds or the full-screen state
+    /// change; `None` when the desktop or no window is in the foreground.
+    /// Windows of this process, such as the tray menu, are ignored. Dropping
+    /// the watcher stops it.
+    pub struct FullscreenWatcher {
+        _thread: WatcherThread,
+    }
+
+    impl FullscreenWatcher {
+        /// Starts watching; `callback` runs on the watcher thread.
+        pub fn start<F>(mut callback: F) -> Result<Self, DisplayError>
+        where
+            F: FnMut(Option<ForegroundWindow>) + Send + 'static,
+        {
+            let thread = WatcherThread::start(
+                move |ready| {
+                    let (stop, stopped) = mpsc::channel::<()>();
+                    let _ = ready.send(Ok(stop));
+                    // Window rectangles in physical pixels, like monitor
+                    // positions. Where that fails they stay scaled, so full
+                    // screen may go unnoticed on scaled monitors.
+                    if !make_per_monitor_dpi_aware() {
+                        warn!(os_error = unsafe { GetLastError() }; "Could not make the full-screen watcher DPI aware");
+                    }
+                    let mut last = None;
+                    loop {
+                        let hwnd = unsafe { GetForegroundWindow() };
+                        if !is_own_window(hwnd) {
+                            let window = foreground_window(hwnd);
+                            if window != last {
+                                debug!("Foreground window changed: {:?}", window);
+                                callback(window.clone());
+                                last = window;
+                            }
+                        }
+                        match stopped.recv_timeout(POLL_INTERVAL) {
+                            Err(RecvTimeoutError::Timeout) => {}
+                            _ => break,
+                        }
+                    }
+                },
+                // Dropping the sender ends the loop.
+                drop,
+            )?;
+            Ok(FullscreenWatcher { _thread: thread })
+        }
+    }
+
+    /// Makes the calling thread per-monitor DPI aware. Resolved at run time,
+    /// so the executable still loads before Windows 10 1607, where this
+    /// returns `false`.
+    fn make_per_monitor_dpi_aware() -> bool {
+        let user32 = to_wide_string("user32.dll");
+        let module = unsafe { GetModuleHandleW(user32.as_ptr()) };
+        if module.is_null() {
+            return false;
+        }
+        let address = unsafe { GetProcAddress(module, c"SetThreadDpiAwarenessContext".as_ptr()) };
+        if address.is_null() {
+            return false;
+        }
+        let set_context: SetThreadDpiAwarenessContextFn = unsafe { mem::transmute(address) };
+        !unsafe { set_context(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) }.is_null()
+    }
+
+    fn is_own_window(hwnd: HWND) -> bool {
+        if hwnd.is_null() {
+            return false;
+        }
+        let mut process_id: DWORD = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, &mut process_id) };
+        process_id == unsafe { GetCurrentProcessId() }
+    }
+
+    fn foreground_window(hwnd: HWND) -> Option<ForegroundWindow> {
+        if hwnd.is_null() || hwnd == unsafe { GetShellWindow() } || is_desktop(hwnd) {
+            return None;
+        }
+        let mut rect: RECT = unsafe { mem::zeroed() };
+        if unsafe { GetWindowRect(hwnd, &mut rect) } == 0 {
+            return None;
+        }
+        let style = unsafe { GetWindowLongW(hwnd, GWL_STYLE) } as u32;
+        let mut state: QUERY_USER_NOTIFICATION_STATE = 0;
+        let fullscreen_state = SUCCEEDED(unsafe { SHQueryUserNotificationState(&mut state) })
+            && fullscreen_notification_state(state);
+        Some(ForegroundWindow {
+            path: executable_path(hwnd),
+            bounds: Rect::new(rect.left, rect.top, rect.right, rect.bottom),
+            has_caption: style & WS_CAPTION == WS_CAPTION,
+            fullscreen_state,
+        })
+    }
+
+    fn is_desktop(hwnd: HWND) -> bool {
+        let mut buffer = [0u16; 64];
+        let len = unsafe { GetClassNameW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        let class = String::from_utf16_lossy(&buffer[..len.max(0) as usize]);
+        DESKTOP_CLASSES.contains(&class.as_str())
+    }
+}
@@ -0,0 +1,89 @@
+//! Cached display inventory.
+//!
+//! Building a `TrayMenu` walks every mode of every device, which is slow on
+//! multi-GPU systems. `InventoryCache` keeps the last one so the menu opens
+//! instantly; display change notifications mark it stale and rebuild it in
+//! the background.
+
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+
+use crate::backend::DisplayBackend;
+use crate::tray::TrayMenu;
+use crate::DisplayError;
+
+#[derive(Debug, Default)]
+struct CacheState {
+    menu: Option<Arc<TrayMenu>>,
+    /// Bumped by every `invalidate`, so a rebuild that raced with one does
+    /// not mark the cache fresh.
+    generation: u64,
+    stale: bool,
+}
+
+/// The last `TrayMenu::build` result, shared between threads.
+#[derive(Debug, Default)]
+pub struct InventoryCache {
+    state: Mutex<CacheState>,
+}
+
+impl InventoryCache {
+    pub const fn new() -> Self {
+        InventoryCache {
+            state: Mutex::new(CacheState {
+                menu: None,
+                generation: 0,
+                stale: false,
+            }),
+        }
+    }
+
+    /// The cached inventory, even if stale. `None` until the first build.
+    pub fn get(&self) -> Option<Arc<TrayMenu>> {
+        self.state.lock().unwrap().menu.clone()
+    }
+
+    /// Whether there is no inventory yet or it has been invalidated since it
+    /// was built.
+    pub fn is_stale(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.menu.is_none() || state.stale
+    }
+
+    /// Marks the inventory out of date, e.g. after a display change. The
+    /// stale copy stays readable until it is rebuilt.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.stale = true;
+    }
+
+    /// Rebuilds the inventory. The lock is not held while enumerating, so
+    /// readers keep getting the previous copy meanwhile. On error the
+    /// previous copy is kept.
+    pub fn refresh_with(
+        &self,
+        backend: &dyn DisplayBackend,
+    ) -> Result<Arc<TrayMenu>, DisplayError> {
+        let generation = self.state.lock().unwrap().generation;
+        let menu = Arc::new(TrayMenu::build(backend)?);
+
+        let mut state = self.state.lock().unwrap();
+        state.menu = Some(menu.clone());
+        state.stale = state.generation != generation;
+        debug!(monitors = menu.monitors.len(), stale = state.stale; "Rebuilt display inventory");
+        Ok(menu)
+    }
+
+    /// The cached inventory, rebuilding it first if it is stale.
+    pub fn get_or_refresh_with(
+        &self,
+        backend: &dyn DisplayBackend,
+    ) -> Result<Arc<TrayMenu>, DisplayError> {
+        match self.get() {
+            Some(menu) if !self.is_stale() => Ok(menu),
+            _ => self.refresh_with(backend),
+        }
+    }
+}
@@ -1,308 +1,707 @@
-use std::collections::HashSet;
-use std::mem;
-use std::ptr;
-
-use winapi::shared::guiddef::GUID;
-use winapi::shared::minwindef::DWORD;
-use winapi::um::cfgmgr32::{CM_DRP_DEVICEDESC, CM_DRP_FRIENDLYNAME};
-use winapi::um::errhandlingapi::GetLastError;
-use winapi::um::handleapi::INVALID_HANDLE_VALUE;
-use winapi::um::setupapi::{
-    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
-    SetupDiGetDeviceRegistryPropertyW, DIGCF_PRESENT, DIGCF_PROFILE, HDEVINFO, SP_DEVINFO_DATA,
+pub mod backend;
+pub mod batch;
+pub mod cli;
+pub mod config;
+pub mod devnode;
+pub mod edid;
+pub mod error;
+pub mod fake;
+pub mod format;
+pub mod fullscreen;
+pub mod inventory;
+pub mod logger;
+pub mod mode;
+pub mod options;
+pub mod power;
+pub mod profile;
+pub mod revert;
+pub mod rules;
+pub mod schema;
+pub mod snapshot;
+pub mod tray;
+pub mod watch;
+#[cfg(windows)]
+mod watcher;
+#[cfg(windows)]
+pub mod win32;
+
+pub use backend::DisplayBackend;
+pub use batch::DisplayBatch;
+pub use config::{Config, ConfigError};
+pub use edid::{Edid, EdidError, RefreshCapabilities, RefreshRange};
+pub use error::{ChangeOutcome, DisplayError};
+pub use fake::{devnode_for, FakeAdapter, FakeBackend};
+pub use format::FormatError;
+pub use fullscreen::{fullscreen_rates_with, ForegroundWindow, FullscreenRule, Rect};
+pub use inventory::InventoryCache;
+pub use mode::{
+    compatible_modes, refresh_rates, resolution_modes, DisplayMode, Orientation, Position,
+    RefreshRate, Scaling,
 };
-use winapi::um::wingdi::{
-    DEVMODEW, DISPLAY_DEVICEW, DISPLAY_DEVICE_PRIMARY_DEVICE, DM_DISPLAYFREQUENCY,
-};
-use winapi::um::winnt::WCHAR;
-use winapi::um::winuser::{
-    ChangeDisplaySettingsExW, EnumDisplayDevicesW, EnumDisplaySettingsW, DISP_CHANGE_RESTART,
-    DISP_CHANGE_SUCCESSFUL, ENUM_CURRENT_SETTINGS,
+pub use options::{ChangeOptions, Persistence};
+pub use power::{PowerRule, PowerRules, PowerSource};
+pub use profile::{MonitorResult, Profile, ProfileMonitor, ProfileReport};
+pub use revert::{AutoRevert, PendingChange, RequestedChange, RevertTick};
+pub use rules::{forced_rates, AppRule, ForcedRate, OverrideResult, RateOverride, RateOverrides};
+pub use snapshot::{DisplaySnapshot, MonitorSnapshot};
+pub use watch::{DisplayEvent, DisplayState, DisplayTracker, MonitorState};
+#[cfg(windows)]
+pub use fullscreen::FullscreenWatcher;
+#[cfg(windows)]
+pub use power::PowerWatcher;
+#[cfg(windows)]
+pub use rules::ForegroundWatcher;
+#[cfg(windows)]
+pub use watch::DisplayWatcher;
+#[cfg(windows)]
+pub use win32::Win32Backend;
+
+use backend::{
+    DISPLAY_DEVICE_ACTIVE, DISPLAY_DEVICE_ATTACHED, DISPLAY_DEVICE_ATTACHED_TO_DESKTOP,
+    DISPLAY_DEVICE_PRIMARY_DEVICE, CDS_TEST, SDC_ALLOW_CHANGES, SDC_APPLY, SDC_USE_SUPPLIED_DISPLAY_CONFIG,
+    SDC_VALIDATE,
 };
+use devnode::match_devnode;
+use error::check_change_code;
+use log::{debug, info, warn};
+use serde::Serialize;
+
+pub(crate) const GENERIC_MONITOR_NAME: &str = "Generic PnP Monitor";
 
 pub fn to_wide_string(s: &str) -> Vec<u16> {
     s.encode_utf16().chain(std::iter::once(0)).collect()
 }
 
-pub fn get_available_refresh_rates(device_name_wide: &[u16]) -> Vec<DWORD> {
-    let mut refresh_rates = HashSet::new();
-    let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
-    dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+#[cfg(windows)]
+pub fn get_available_refresh_rates(device_name_wide: &[u16]) -> Vec<u32> {
+    let device_name = String::from_utf16_lossy(device_name_wide)
+        .trim_end_matches('\0')
+        .to_string();
+    get_available_refresh_rates_with(&Win32Backend, &device_name)
+}
+
+/// Refresh rates `device_name` supports at its current resolution and colour
+/// depth. Falls back to every listed rate if the current mode is unknown.
+pub fn get_available_refresh_rates_with(backend: &dyn DisplayBackend, device_name: &str) -> Vec<u32> {
+    let modes = get_display_modes_with(backend, device_name);
+    match backend.current_mode(device_name) {
+        Ok(current) => refresh_rates(&compatible_modes(&modes, &current)),
+        Err(_) => refresh_rates(&modes),
+    }
+}
+
+#[cfg(windows)]
+pub fn get_exact_refresh_rates(device_name: &str) -> Vec<RefreshRate> {
+    get_exact_refresh_rates_with(&Win32Backend, device_name)
+}
 
-    let mut mode_num = 0;
-    loop {
-        let result =
-            unsafe { EnumDisplaySettingsW(device_name_wide.as_ptr(), mode_num, &mut dev_mode) };
+/// Exact refresh rates `device_name` accepts at its current resolution, in
+/// ascending order. Both the integer and the 1000/1001 variant of each rate
+/// are listed when the display path validates them, so 59.94 Hz and 60 Hz
+/// stay separate. Falls back to the integer rates when the CCD API cannot
+/// see the device.
+pub fn get_exact_refresh_rates_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+) -> Vec<RefreshRate> {
+    validated_refresh_rates_with(backend, device_name).unwrap_or_else(|| {
+        get_available_refresh_rates_with(backend, device_name)
+            .into_iter()
+            .map(RefreshRate::integer)
+            .collect()
+    })
+}
 
-        if result == 0 {
-            break;
+/// The exact rates the display path validates with `SDC_VALIDATE`, or
+/// `None` when the CCD API cannot see `device_name`.
+fn validated_refresh_rates_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+) -> Option<Vec<RefreshRate>> {
+    let current = match backend.current_refresh_rate(device_name) {
+        Ok(current) => current,
+        Err(os_error) => {
+            debug!(device = device_name, os_error = os_error; "QueryDisplayConfig failed, listing integer rates");
+            return None;
         }
+    };
 
-        if dev_mode.dmDisplayFrequency > 1 {
-            refresh_rates.insert(dev_mode.dmDisplayFrequency);
+    let mut exact = vec![current];
+    for hz in get_available_refresh_rates_with(backend, device_name) {
+        let candidates = [Some(RefreshRate::integer(hz)), ntsc_candidate(hz)];
+        for candidate in candidates.into_iter().flatten() {
+            if exact.contains(&candidate) {
+                continue;
+            }
+            let flags = SDC_VALIDATE | SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES;
+            if backend.apply_refresh_rate(device_name, candidate, flags) == 0 {
+                exact.push(candidate);
+            }
         }
-        mode_num += 1;
     }
+    exact.sort();
+    Some(exact)
+}
 
-    let mut sorted_rates: Vec<DWORD> = refresh_rates.into_iter().collect();
-    sorted_rates.sort_unstable();
-    sorted_rates
+/// The NTSC rate a listed `hz` may stand for: `dmDisplayFrequency` rounds
+/// down, so a listed 59 Hz may really be 59.94 Hz. Only film and broadcast
+/// rates, multiples of 24 or 30 Hz, have NTSC variants; a listed 60 Hz is
+/// never 60.94 Hz.
+fn ntsc_candidate(hz: u32) -> Option<RefreshRate> {
+    let nominal = hz + 1;
+    (nominal.is_multiple_of(24) || nominal.is_multiple_of(30)).then(|| RefreshRate::ntsc(nominal))
 }
 
-#[derive(Debug, Clone)]
+#[cfg(windows)]
+pub fn get_display_modes(device_name: &str) -> Vec<DisplayMode> {
+    get_display_modes_with(&Win32Backend, device_name)
+}
+
+/// Every distinct mode the driver lists for `device_name`, in driver order.
+pub fn get_display_modes_with(backend: &dyn DisplayBackend, device_name: &str) -> Vec<DisplayMode> {
+    let mut modes: Vec<DisplayMode> = Vec::new();
+    for mode in backend.modes(device_name) {
+        if !modes.contains(&mode) {
+            modes.push(mode);
+        }
+    }
+    modes
+}
+
+/// Serializes with `primary` and `attached` for the flags; see
+/// `crate::schema` for how records add `hardware_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct DisplayDevice {
+    /// Adapter output the monitor is connected to, e.g. `\\.\DISPLAY1`.
+    /// Mode changes are addressed to this name.
     pub device_name: String,
+    /// Human-readable monitor name for UIs.
     pub display_name: String,
+    /// The monitor's own device name, e.g. `\\.\DISPLAY1\Monitor0`.
+    pub monitor_device_name: String,
+    /// The monitor's PnP device ID, e.g. `MONITOR\DEL4123\{4d36e96e-...}\0001`.
+    pub device_id: String,
+    /// The monitor's device interface path. Unlike the `\\.\DISPLAYn` names this
+    /// survives reboots and reconnections, so it is the preferred way to
+    /// address a physical monitor.
+    pub interface_path: String,
+    /// Name of the display adapter driving the monitor, e.g. `NVIDIA GeForce RTX 3080`.
+    pub adapter_name: String,
+    /// Whether the adapter output is the primary display.
+    #[serde(rename = "primary")]
+    pub is_primary: bool,
+    /// Whether the monitor is attached to its adapter output.
+    #[serde(rename = "attached")]
+    pub is_attached: bool,
 }
 
-pub fn get_all_display_devices() -> Vec<DisplayDevice> {
-    let mut devices = Vec::new();
-    let mut adapter_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
-    adapter_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as DWORD;
-
-    // GUID for monitor devices (GUID_DEVCLASS_MONITOR)
-    // {4d36e96e-e325-11ce-bfc1-08002be10318}
-    let guid_devclass_monitor: GUID = GUID {
-        Data1: 0x4d36e96e,
-        Data2: 0xe325,
-        Data3: 0x11ce,
-        Data4: [0xbf, 0xc1, 0x08, 0x00, 0x2b, 0xe1, 0x03, 0x18],
-    };
-
-    let hdevinfo: HDEVINFO = unsafe {
-        SetupDiGetClassDevsW(
-            &guid_devclass_monitor,
-            ptr::null_mut(),
-            ptr::null_mut(),
-            DIGCF_PRESENT | DIGCF_PROFILE, // Only devices that are currently present, and include profile-specific devices
-        )
-    };
+impl DisplayDevice {
+    /// The monitor's hardware ID, e.g. `DEL4123`: manufacturer and product
+    /// code from its PnP device ID.
+    pub fn hardware_id(&self) -> Option<&str> {
+        devnode::split_monitor_device_id(&self.device_id)
+            .and_then(|(hardware_id, _)| hardware_id.rsplit('\\').next())
+            .filter(|hardware_id| !hardware_id.is_empty())
+    }
 
-    if hdevinfo == INVALID_HANDLE_VALUE {
-        eprintln!("Error: SetupDiGetClassDevsW failed. Last Error: {}", unsafe { GetLastError() });
-        return devices;
+    /// Whether `id` names this monitor: its device interface path, its PnP
+    /// device ID or just the hardware ID in it (`DEL4123`), compared
+    /// case-insensitively. A hardware ID matches every monitor of that model.
+    pub fn matches_id(&self, id: &str) -> bool {
+        [
+            Some(self.interface_path.as_str()),
+            Some(self.device_id.as_str()),
+            self.hardware_id(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|candidate| !candidate.is_empty() && candidate.eq_ignore_ascii_case(id))
     }
+}
+
+#[cfg(windows)]
+pub fn get_all_display_devices() -> Result<Vec<DisplayDevice>, DisplayError> {
+    get_all_display_devices_with(&Win32Backend)
+}
+
+pub fn get_all_display_devices_with(
+    backend: &dyn DisplayBackend,
+) -> Result<Vec<DisplayDevice>, DisplayError> {
+    let mut devices = Vec::new();
+
+    let devnodes = backend
+        .monitor_devnodes()
+        .map_err(|os_error| DisplayError::SetupApi { os_error })?;
 
     // Enumerate display adapters
-    for adapter_idx in 0.. {
-        let result = unsafe { EnumDisplayDevicesW(ptr::null_mut(), adapter_idx, &mut adapter_device, 0) };
-        if result == 0 {
-            break; // No more adapters
+    for adapter in backend.adapters() {
+        // Check if the adapter is active and attached to the desktop
+        if adapter.state_flags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP == 0 {
+            continue;
         }
 
-        // Check if the adapter is active and attached to the desktop
-        if adapter_device.StateFlags & winapi::um::wingdi::DISPLAY_DEVICE_ATTACHED_TO_DESKTOP != 0 {
-            let adapter_device_name = String::from_utf16_lossy(&adapter_device.DeviceName)
-                .trim_end_matches('\0')
-                .to_string();
-
-            let mut monitor_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
-            monitor_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as DWORD;
-
-            // Enumerate monitors for this adapter
-            for monitor_idx in 0.. {
-                let result = unsafe {
-                    EnumDisplayDevicesW(
-                        to_wide_string(&adapter_device_name).as_ptr(),
-                        monitor_idx,
-                        &mut monitor_device,
-                        0,
-                    )
-                };
-                if result == 0 {
-                    break; // No more monitors for this adapter
-                }
+        // Enumerate monitors for this adapter
+        for monitor in backend.monitors(&adapter.device_name) {
+            // Check if the monitor is active
+            if monitor.state_flags & DISPLAY_DEVICE_ACTIVE == 0 {
+                continue;
+            }
 
-                // Check if the monitor is active
-                if monitor_device.StateFlags & winapi::um::wingdi::DISPLAY_DEVICE_ACTIVE != 0 {
-                    let mut monitor_display_name = String::from_utf16_lossy(&monitor_device.DeviceString)
-                        .trim_end_matches('\0')
-                        .to_string();
-
-                    println!("DEBUG: Original monitor_device.DeviceString: {}", monitor_display_name);
-
-                    // Try to get a more accurate name using SetupDiGetDeviceRegistryPropertyW
-                    let mut device_info_data: SP_DEVINFO_DATA = unsafe { mem::zeroed() };
-                    device_info_data.cbSize = mem::size_of::<SP_DEVINFO_DATA>() as DWORD;
-
-                    // Find the corresponding device info for the monitor
-                    for i in 0.. {
-                        let enum_dev_result = unsafe {
-                            SetupDiEnumDeviceInfo(hdevinfo, i, &mut device_info_data)
-                        };
-                        if enum_dev_result == 0 {
-                            break;
-                        }
-
-                        let mut buffer: Vec<u16> = vec![0; 256]; // Adjust size as needed
-                        let mut required_size: DWORD = 0;
-                        let mut monitor_display_name_candidate = String::new();
-
-                        // Try to get CM_DRP_FRIENDLYNAME first
-                        let get_friendly_name_result = unsafe {
-                            SetupDiGetDeviceRegistryPropertyW(
-                                hdevinfo,
-                                &mut device_info_data,
-                                CM_DRP_FRIENDLYNAME, // <<< FIRST ATTEMPT: Friendly Name
-                                ptr::null_mut(),
-                                buffer.as_mut_ptr() as *mut u8,
-                                (buffer.len() * mem::size_of::<WCHAR>()) as DWORD,
-                                &mut required_size,
-                            )
-                        };
-
-                        if get_friendly_name_result != 0 {
-                            let friendly_name = String::from_utf16_lossy(&buffer[..(required_size / mem::size_of::<WCHAR>() as DWORD) as usize])
-                                .trim_end_matches('\0')
-                                .to_string();
-                            if !friendly_name.is_empty() && friendly_name != "Generic PnP Monitor" {
-                                monitor_display_name_candidate = friendly_name;
-                                println!("DEBUG: Retrieved display name from CM_DRP_FRIENDLYNAME: {}", monitor_display_name_candidate);
-                            }
-                        }
-
-                        // Fallback to CM_DRP_DEVICEDESC if friendly name was not ideal
-                        if monitor_display_name_candidate.is_empty() || monitor_display_name_candidate == "Generic PnP Monitor" {
-                            let get_desc_result = unsafe {
-                                SetupDiGetDeviceRegistryPropertyW(
-                                    hdevinfo,
-                                    &mut device_info_data,
-                                    CM_DRP_DEVICEDESC, // <<< FALLBACK: Device Description
-                                    ptr::null_mut(),
-                                    buffer.as_mut_ptr() as *mut u8,
-                                    (buffer.len() * mem::size_of::<WCHAR>()) as DWORD,
-                                    &mut required_size,
-                                )
-                            };
-
-                            if get_desc_result != 0 {
-                                let device_description = String::from_utf16_lossy(&buffer[..(required_size / mem::size_of::<WCHAR>() as DWORD) as usize])
-                                    .trim_end_matches('\0')
-                                    .to_string();
-                                if !device_description.is_empty() && device_description != "Generic PnP Monitor" {
-                                    monitor_display_name_candidate = device_description;
-                                    println!("DEBUG: Retrieved display name from CM_DRP_DEVICEDESC (fallback): {}", monitor_display_name_candidate);
-                                }
-                            }
-                        }
-
-                        // Ensure monitor_display_name gets the best candidate
-                        monitor_display_name = if !monitor_display_name_candidate.is_empty() {
-                            monitor_display_name_candidate
-                        } else {
-                            // If all else fails, use the original DeviceString (which might be "Generic PnP Monitor")
-                            String::from_utf16_lossy(&monitor_device.DeviceString)
-                                .trim_end_matches('\0')
-                                .to_string()
-                        };
-                        break; // Found a name, no need to check other device infos
-                    }
-
-                    // Use the adapter's device name for setting refresh rates, but the monitor's display name for UI
-                    devices.push(DisplayDevice {
-                        device_name: adapter_device_name.clone(),
-                        display_name: monitor_display_name,
-                    });
+            debug!(
+                adapter = adapter.device_name.as_str(), device_string = monitor.device_string.as_str();
+                "Found active monitor"
+            );
+
+            // Try to get a more accurate name from the monitor's own devnode, preferring the
+            // friendly name and falling back to the device description.
+            let is_useful = |name: &&String| !name.is_empty() && name.as_str() != GENERIC_MONITOR_NAME;
+            let candidate = match_devnode(&monitor, &devnodes).and_then(|devnode| {
+                if let Some(friendly_name) = devnode.friendly_name.as_ref().filter(is_useful) {
+                    debug!(name = friendly_name.as_str(); "Retrieved display name from CM_DRP_FRIENDLYNAME");
+                    Some(friendly_name.clone())
+                } else if let Some(device_desc) = devnode.device_desc.as_ref().filter(is_useful) {
+                    debug!(name = device_desc.as_str(); "Retrieved display name from CM_DRP_DEVICEDESC (fallback)");
+                    Some(device_desc.clone())
+                } else {
+                    None
                 }
-            }
+            });
+
+            // Use the adapter's device name for setting refresh rates, but the monitor's display name for UI
+            devices.push(DisplayDevice {
+                device_name: adapter.device_name.clone(),
+                // If all else fails, use the original DeviceString (which might be "Generic PnP Monitor")
+                display_name: candidate.unwrap_or(monitor.device_string),
+                monitor_device_name: monitor.device_name,
+                device_id: monitor.device_id,
+                interface_path: monitor.interface_path,
+                adapter_name: adapter.device_string.clone(),
+                is_primary: adapter.state_flags & DISPLAY_DEVICE_PRIMARY_DEVICE != 0,
+                is_attached: monitor.state_flags & DISPLAY_DEVICE_ATTACHED != 0,
+            });
         }
     }
 
-    unsafe { SetupDiDestroyDeviceInfoList(hdevinfo) };
-    devices
+    Ok(devices)
 }
 
-pub fn get_primary_display_device_name() -> Option<String> {
-    let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
-    display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as DWORD;
+#[cfg(windows)]
+pub fn get_monitor_edid(device: &DisplayDevice) -> Result<Edid, EdidError> {
+    get_monitor_edid_with(&Win32Backend, device)
+}
 
-    for i in 0.. {
-        let result = unsafe { EnumDisplayDevicesW(ptr::null_mut(), i, &mut display_device, 0) };
-        if result == 0 {
-            break;
-        }
+/// Reads and parses the EDID of the physical monitor behind `device`.
+pub fn get_monitor_edid_with(
+    backend: &dyn DisplayBackend,
+    device: &DisplayDevice,
+) -> Result<Edid, EdidError> {
+    let blob = backend
+        .monitor_edid(&device.interface_path)
+        .ok_or(EdidError::Unavailable)?;
+    Edid::parse(&blob)
+}
+
+/// A refresh rate the driver lists, checked against the monitor's EDID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshRateSupport {
+    pub rate: RefreshRate,
+    /// Whether the monitor advertises this rate at the current resolution;
+    /// `None` when there is no usable EDID or the current mode is unknown.
+    pub advertised: Option<bool>,
+    /// Whether the driver accepts switching to this rate, by `SDC_VALIDATE`
+    /// or, without the CCD API, a `CDS_TEST` dry run.
+    pub accepted: bool,
+}
 
-        let device_name_str = String::from_utf16_lossy(&display_device.DeviceName)
-            .trim_end_matches('\0')
-            .to_string();
+/// The rates of `get_exact_refresh_rates` alongside what the panel itself
+/// claims to support.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefreshRateReport {
+    pub rates: Vec<RefreshRateSupport>,
+    /// Advertised timings, refresh range and VRR window, if the EDID parsed.
+    pub capabilities: Option<RefreshCapabilities>,
+}
 
-        if display_device.StateFlags & DISPLAY_DEVICE_PRIMARY_DEVICE != 0 {
-            return Some(device_name_str);
+#[cfg(windows)]
+pub fn get_refresh_rate_report(device: &DisplayDevice) -> RefreshRateReport {
+    get_refresh_rate_report_with(&Win32Backend, device)
+}
+
+/// Lists the available refresh rates of `device`, flags the ones its EDID
+/// does not advertise and whether the driver accepts each. Drivers sometimes
+/// offer rates the panel cannot show.
+pub fn get_refresh_rate_report_with(
+    backend: &dyn DisplayBackend,
+    device: &DisplayDevice,
+) -> RefreshRateReport {
+    let capabilities = match get_monitor_edid_with(backend, device) {
+        Ok(edid) => Some(edid.refresh_capabilities()),
+        Err(e) => {
+            debug!(device = device.device_name.as_str(), error = e.to_string(); "No EDID capabilities");
+            None
         }
+    };
+    let current = backend.current_mode(&device.device_name).ok();
+
+    // Exact rates are already validated; the integer fallback is dry-run
+    // through CDS_TEST instead.
+    let (rates, validated) = match validated_refresh_rates_with(backend, &device.device_name) {
+        Some(rates) => (rates, true),
+        None => {
+            let rates = get_available_refresh_rates_with(backend, &device.device_name);
+            (rates.into_iter().map(RefreshRate::integer).collect(), false)
+        }
+    };
+    let rates = rates
+        .into_iter()
+        .map(|rate| RefreshRateSupport {
+            rate,
+            advertised: capabilities
+                .as_ref()
+                .zip(current)
+                .map(|(caps, mode)| caps.advertises(mode.width, mode.height, rate.rounded_hz())),
+            accepted: validated
+                || set_display_refresh_rate_with(
+                    backend,
+                    &device.device_name,
+                    rate.dm_frequency(),
+                    ChangeOptions::dry_run(),
+                )
+                .is_ok(),
+        })
+        .collect();
+
+    RefreshRateReport {
+        rates,
+        capabilities,
     }
-    None
 }
 
-pub fn set_display_refresh_rate(device_name: &str, refresh_rate: DWORD) -> bool {
-    let device_name_wide = to_wide_string(device_name);
-    let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
-    dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+#[cfg(windows)]
+pub fn get_primary_display_device_name() -> Option<String> {
+    get_primary_display_device_name_with(&Win32Backend)
+}
 
-    println!(
-        "DEBUG: Enumerating settings for primary display: {}",
-        device_name
-    );
+pub fn get_primary_display_device_name_with(backend: &dyn DisplayBackend) -> Option<String> {
+    backend
+        .adapters()
+        .into_iter()
+        .find(|adapter| adapter.state_flags & DISPLAY_DEVICE_PRIMARY_DEVICE != 0)
+        .map(|adapter| adapter.device_name)
+}
 
-    let enum_settings_result = unsafe {
-        EnumDisplaySettingsW(
-            device_name_wide.as_ptr(),
-            ENUM_CURRENT_SETTINGS,
-            &mut dev_mode,
-        )
-    };
+#[cfg(windows)]
+pub fn validate_mode(device_name: &str, mode: &DisplayMode) -> Result<ChangeOutcome, DisplayError> {
+    validate_mode_with(&Win32Backend, device_name, mode)
+}
 
-    if enum_settings_result == 0 {
-        eprintln!(
-            "Error: Could not enumerate current display settings for {}. Last Error: {}",
-            device_name,
-            unsafe { GetLastError() }
-        );
-        return false;
+/// Asks the driver whether it would accept `mode` on `device_name` without
+/// changing anything (`CDS_TEST`). Returns `ChangeOutcome::Validated`, or
+/// `RestartRequired` if the mode would only apply after a restart.
+pub fn validate_mode_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    mode: &DisplayMode,
+) -> Result<ChangeOutcome, DisplayError> {
+    let code = backend.apply_mode(device_name, mode, CDS_TEST);
+    match check_change_code(device_name, code) {
+        Ok(ChangeOutcome::Applied) => Ok(ChangeOutcome::Validated),
+        Ok(outcome) => Ok(outcome),
+        Err(error) => {
+            debug!(device = device_name, mode = mode.to_string(), code = code; "Driver rejected the mode");
+            Err(error)
+        }
     }
+}
+
+#[cfg(windows)]
+pub fn set_display_refresh_rate(
+    device_name: &str,
+    refresh_rate: u32,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    set_display_refresh_rate_with(&Win32Backend, device_name, refresh_rate, options)
+}
+
+/// `current` with its refresh rate switched to `refresh_rate`, if the driver
+/// lists that rate for the current resolution and colour depth.
+pub(crate) fn refresh_rate_mode(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    current: &DisplayMode,
+    refresh_rate: u32,
+) -> Result<DisplayMode, DisplayError> {
+    let modes = get_display_modes_with(backend, device_name);
+    let target = compatible_modes(&modes, current)
+        .into_iter()
+        .filter(|mode| mode.frequency == refresh_rate)
+        .min_by_key(|mode| mode.interlaced != current.interlaced)
+        .ok_or_else(|| DisplayError::UnsupportedMode {
+            device_name: device_name.to_string(),
+            mode: DisplayMode {
+                frequency: refresh_rate,
+                ..*current
+            },
+        })?;
+    Ok(DisplayMode {
+        frequency: target.frequency,
+        interlaced: target.interlaced,
+        ..*current
+    })
+}
+
+/// `requested` as the driver lists it for `device_name`, preferring the
+/// scan type `current` uses. Only modes at the current colour depth count.
+pub(crate) fn listed_mode(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    current: &DisplayMode,
+    requested: &DisplayMode,
+) -> Result<DisplayMode, DisplayError> {
+    let target = get_display_modes_with(backend, device_name)
+        .into_iter()
+        .filter(|mode| mode.is_compatible_with(requested) && mode.frequency == requested.frequency)
+        .min_by_key(|mode| mode.interlaced != current.interlaced)
+        .ok_or_else(|| DisplayError::UnsupportedMode {
+            device_name: device_name.to_string(),
+            mode: *requested,
+        })?;
+    Ok(DisplayMode {
+        interlaced: target.interlaced,
+        ..*requested
+    })
+}
+
+/// Switches `device_name` to `refresh_rate` at its current resolution and
+/// colour depth.
+pub fn set_display_refresh_rate_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    refresh_rate: u32,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    debug!(device = device_name, rate = refresh_rate; "Enumerating current display settings");
+
+    let current = backend
+        .current_mode(device_name)
+        .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+            device_name: device_name.to_string(),
+            os_error,
+        })?;
 
     // Only change refresh rate if it's different to avoid unnecessary mode changes
-    if dev_mode.dmDisplayFrequency == refresh_rate {
-        println!(
-            "Refresh rate for {} is already {} Hz. No change needed.",
-            device_name, refresh_rate
-        );
-        return true;
+    if current.frequency == refresh_rate {
+        info!(device = device_name, rate = refresh_rate; "Refresh rate already set, no change needed");
+        return Ok(ChangeOutcome::Unchanged);
+    }
+
+    let mode = refresh_rate_mode(backend, device_name, &current, refresh_rate)?;
+
+    if options.dry_run {
+        return validate_mode_with(backend, device_name, &mode);
     }
 
-    dev_mode.dmDisplayFrequency = refresh_rate;
-    dev_mode.dmFields |= DM_DISPLAYFREQUENCY;
-
-    let change_result = unsafe {
-        ChangeDisplaySettingsExW(
-            device_name_wide.as_ptr(),
-            &mut dev_mode,
-            ptr::null_mut(),
-            0, // 0 for immediate application
-            ptr::null_mut(),
-        )
+    apply_mode_with(backend, device_name, &mode, "refresh rate", options)
+}
+
+/// Applies `mode` through `ChangeDisplaySettingsExW` and logs how it went;
+/// `what` names the change for the log, e.g. `refresh rate`.
+fn apply_mode_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    mode: &DisplayMode,
+    what: &str,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    let code = backend.apply_mode(device_name, mode, options.persistence.cds_flags());
+    let outcome = check_change_code(device_name, code).inspect_err(|_| {
+        debug!(device = device_name, mode = mode.to_string(), orientation = mode.orientation.degrees(), code = code; "ChangeDisplaySettingsExW failed");
+    })?;
+    match outcome {
+        ChangeOutcome::RestartRequired => warn!(
+            device = device_name, mode = mode.to_string(), orientation = mode.orientation.degrees();
+            "Changed {}, but a restart is required for changes to take full effect", what
+        ),
+        _ => info!(
+            device = device_name, mode = mode.to_string(), orientation = mode.orientation.degrees(), persistence = options.persistence.as_str();
+            "Changed {}", what
+        ),
+    }
+    Ok(outcome)
+}
+
+#[cfg(windows)]
+pub fn set_display_mode(
+    device_name: &str,
+    width: u32,
+    height: u32,
+    frequency: u32,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    set_display_mode_with(&Win32Backend, device_name, width, height, frequency, options)
+}
+
+/// Switches `device_name` to `width`x`height` at `frequency` in one mode
+/// change, keeping its colour depth, orientation and scaling.
+pub fn set_display_mode_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    width: u32,
+    height: u32,
+    frequency: u32,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    let current = backend
+        .current_mode(device_name)
+        .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+            device_name: device_name.to_string(),
+            os_error,
+        })?;
+    let requested = DisplayMode {
+        width,
+        height,
+        frequency,
+        ..current
     };
+    if requested == current {
+        info!(device = device_name, mode = current.to_string(); "Display mode already set, no change needed");
+        return Ok(ChangeOutcome::Unchanged);
+    }
 
-    match change_result {
-        DISP_CHANGE_SUCCESSFUL => {
-            println!(
-                "Successfully changed refresh rate for {} to {} Hz.",
-                device_name, refresh_rate
-            );
-            true
-        }
-        DISP_CHANGE_RESTART => {
-            println!("Refresh rate for {} changed, but a restart is required for changes to take full effect.", device_name);
-            true
-        }
-        _ => {
-            eprintln!(
-                "Failed to change refresh rate for {} to {} Hz. Error code: {}. Last Error: {}",
+    let mode = listed_mode(backend, device_name, &current, &requested)?;
+
+    if options.dry_run {
+        return validate_mode_with(backend, device_name, &mode);
+    }
+
+    apply_mode_with(backend, device_name, &mode, "display mode", options)
+}
+
+#[cfg(windows)]
+pub fn get_display_orientation(device_name: &str) -> Result<Orientation, DisplayError> {
+    get_display_orientation_with(&Win32Backend, device_name)
+}
+
+/// The rotation `device_name` is currently running with.
+pub fn get_display_orientation_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+) -> Result<Orientation, DisplayError> {
+    backend
+        .current_mode(device_name)
+        .map(|mode| mode.orientation)
+        .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+            device_name: device_name.to_string(),
+            os_error,
+        })
+}
+
+#[cfg(windows)]
+pub fn set_display_orientation(
+    device_name: &str,
+    orientation: Orientation,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    set_display_orientation_with(&Win32Backend, device_name, orientation, options)
+}
+
+/// Rotates `device_name` to `orientation`, swapping its width and height when
+/// going between landscape and portrait. The driver is asked with `CDS_TEST`
+/// first, so a rejected rotation never reaches the screen.
+pub fn set_display_orientation_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    orientation: Orientation,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    let current = backend
+        .current_mode(device_name)
+        .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+            device_name: device_name.to_string(),
+            os_error,
+        })?;
+    if current.orientation == orientation {
+        info!(device = device_name, orientation = orientation.degrees(); "Orientation already set, no change needed");
+        return Ok(ChangeOutcome::Unchanged);
+    }
+
+    let mode = current.rotated(orientation);
+    let validated = validate_mode_with(backend, device_name, &mode)?;
+    if options.dry_run {
+        return Ok(validated);
+    }
+
+    apply_mode_with(backend, device_name, &mode, "orientation", options)
+}
+
+#[cfg(windows)]
+pub fn set_exact_refresh_rate(
+    device_name: &str,
+    rate: RefreshRate,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    set_exact_refresh_rate_with(&Win32Backend, device_name, rate, options)
+}
+
+/// Switches `device_name` to exactly `rate` at its current resolution through
+/// `SetDisplayConfig`, so 59.94 Hz and 60 Hz can be told apart. Integer rates
+/// fall back to `set_display_refresh_rate` when the CCD API cannot see the
+/// device.
+pub fn set_exact_refresh_rate_with(
+    backend: &dyn DisplayBackend,
+    device_name: &str,
+    rate: RefreshRate,
+    options: ChangeOptions,
+) -> Result<ChangeOutcome, DisplayError> {
+    let current = match backend.current_refresh_rate(device_name) {
+        Ok(current) => current,
+        Err(os_error) if rate.is_integer() => {
+            debug!(device = device_name, os_error = os_error; "QueryDisplayConfig failed, falling back to ChangeDisplaySettingsExW");
+            return set_display_refresh_rate_with(
+                backend,
                 device_name,
-                refresh_rate,
-                change_result,
-                unsafe { GetLastError() }
+                rate.dm_frequency(),
+                options,
             );
-            false
         }
+        Err(os_error) => {
+            return Err(DisplayError::CurrentModeUnavailable {
+                device_name: device_name.to_string(),
+                os_error,
+            })
+        }
+    };
+
+    if current == rate {
+        info!(device = device_name, rate = rate.to_string(); "Refresh rate already set, no change needed");
+        return Ok(ChangeOutcome::Unchanged);
     }
+
+    let flags = SDC_USE_SUPPLIED_DISPLAY_CONFIG | SDC_ALLOW_CHANGES;
+    let error = backend.apply_refresh_rate(device_name, rate, flags | SDC_VALIDATE);
+    if error != 0 {
+        debug!(device = device_name, rate = rate.to_string(), os_error = error; "SetDisplayConfig rejected the rate");
+        return Err(DisplayError::UnsupportedRefreshRate {
+            device_name: device_name.to_string(),
+            rate,
+        });
+    }
+    if options.dry_run {
+        return Ok(ChangeOutcome::Validated);
+    }
+
+    let flags = flags | SDC_APPLY | options.persistence.sdc_flags();
+    let error = backend.apply_refresh_rate(device_name, rate, flags);
+    if error != 0 {
+        warn!(device = device_name, rate = rate.to_string(), os_error = error; "SetDisplayConfig failed");
+        return Err(DisplayError::DisplayConfigFailed {
+            device_name: device_name.to_string(),
+            os_error: error,
+        });
+    }
+    info!(
+        device = device_name, rate = rate.to_string(), persistence = options.persistence.as_str();
+        "Changed refresh rate"
+    );
+    Ok(ChangeOutcome::Applied)
 }
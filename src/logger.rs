@@ -0,0 +1,153 @@
+//! A size-rotated log file sink for the `log` facade, used by the windowless
+//! tray binary where stdout and stderr go nowhere.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::kv::{self, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+const DEFAULT_KEEP: usize = 3;
+
+struct LogFile {
+    file: File,
+    written: u64,
+}
+
+/// Writes one line per record to `path`. Once the file grows past
+/// `max_bytes` it is renamed to `<stem>.1.<ext>` (shifting older files up to
+/// `<stem>.<keep>.<ext>`) and a fresh file is started.
+pub struct RotatingFileLogger {
+    path: PathBuf,
+    level: LevelFilter,
+    max_bytes: u64,
+    keep: usize,
+    file: Mutex<LogFile>,
+}
+
+impl RotatingFileLogger {
+    /// Opens (or creates) `path` for appending, creating parent directories.
+    pub fn new(path: impl Into<PathBuf>, level: LevelFilter) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open_append(&path)?;
+        let written = file.metadata()?.len();
+        Ok(RotatingFileLogger {
+            path,
+            level,
+            max_bytes: DEFAULT_MAX_BYTES,
+            keep: DEFAULT_KEEP,
+            file: Mutex::new(LogFile { file, written }),
+        })
+    }
+
+    /// Rotates once the file exceeds `max_bytes`, keeping `keep` old files.
+    pub fn with_rotation(mut self, max_bytes: u64, keep: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self.keep = keep;
+        self
+    }
+
+    /// Installs this logger as the global `log` sink.
+    pub fn install(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    /// Path of the `index`-th rotated file, e.g. `tray.2.log`.
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, index, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, index),
+        };
+        self.path.with_file_name(name)
+    }
+
+    fn rotate(&self, log_file: &mut LogFile) -> io::Result<()> {
+        if self.keep == 0 {
+            log_file.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.keep));
+            for index in (1..self.keep).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+            log_file.file = open_append(&self.path)?;
+        }
+        log_file.written = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Renders key-value pairs as ` key=value` suffixes.
+struct KvFormatter(String);
+
+impl<'kvs> VisitSource<'kvs> for KvFormatter {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push_str(&format!(" {}={}", key, value));
+        Ok(())
+    }
+}
+
+fn format_record(record: &Record) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut fields = KvFormatter(String::new());
+    let _ = record.key_values().visit(&mut fields);
+    format!(
+        "{}.{:03} {:<5} {}: {}{}\n",
+        timestamp.as_secs(),
+        timestamp.subsec_millis(),
+        record.level(),
+        record.target(),
+        record.args(),
+        fields.0
+    )
+}
+
+impl Log for RotatingFileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(record);
+        let Ok(mut log_file) = self.file.lock() else {
+            return;
+        };
+        if log_file.written > 0 && log_file.written + line.len() as u64 > self.max_bytes {
+            // Keep logging to the current file if rotation fails; losing old
+            // lines is better than losing new ones.
+            let _ = self.rotate(&mut log_file);
+        }
+        if log_file.file.write_all(line.as_bytes()).is_ok() {
+            log_file.written += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut log_file) = self.file.lock() {
+            let _ = log_file.file.flush();
+        }
+    }
+}
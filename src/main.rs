@@ -1,39 +1,394 @@
+#[cfg(windows)]
 use std::mem;
+#[cfg(windows)]
 use std::ptr;
+#[cfg(windows)]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(windows)]
+use std::sync::{Arc, Mutex};
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::time::{Duration, Instant};
 
+#[cfg(windows)]
 use winapi::shared::minwindef::{DWORD, LOWORD, LPARAM, LRESULT, UINT, WPARAM};
+#[cfg(windows)]
 use winapi::shared::windef::{HWND, POINT};
+#[cfg(windows)]
 use winapi::um::errhandlingapi::GetLastError;
+#[cfg(windows)]
 use winapi::um::libloaderapi::GetModuleHandleW;
+#[cfg(windows)]
 use winapi::um::shellapi::{
     Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW,
 };
+#[cfg(windows)]
+use winapi::um::winuser::{
+    FindWindowW, KillTimer, MessageBoxW, PostMessageW, SetDlgItemTextW, SetTimer, IDNO, IDYES,
+    MB_ICONQUESTION, MB_SETFOREGROUND, MB_TOPMOST, MB_YESNO, WM_TIMER,
+};
+#[cfg(windows)]
 use winapi::um::winuser::{
     AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW,
-    DestroyMenu, DispatchMessageW,  GetCursorPos,
+    DestroyMenu, DestroyWindow, DispatchMessageW,  GetCursorPos,
     GetMessageW, LoadIconW, PostQuitMessage, RegisterClassExW, SetForegroundWindow, ShowWindow,
     TrackPopupMenuEx, TranslateMessage, UpdateWindow, CW_USEDEFAULT,  IDC_ARROW, IDI_APPLICATION, MSG, SW_HIDE,
     TPM_LEFTALIGN, TPM_RIGHTBUTTON, TPM_TOPALIGN, WM_COMMAND, WM_CREATE, WM_DESTROY,
     WM_RBUTTONUP, WM_USER, WNDCLASSEXW, WS_EX_APPWINDOW, WS_EX_NOACTIVATE,
     WS_EX_TOOLWINDOW, WS_OVERLAPPEDWINDOW,
 };
+#[cfg(windows)]
+use log::{error, info, warn, LevelFilter};
+#[cfg(windows)]
+use refresh_rate_windows_rs::logger::RotatingFileLogger;
+#[cfg(windows)]
+use refresh_rate_windows_rs::revert::{prompt_text, DEFAULT_REVERT_TIMEOUT};
+#[cfg(windows)]
+use refresh_rate_windows_rs::tray::{persistence_label, MenuCommand, TrayMenu, MENU_EXIT_ID};
+#[cfg(windows)]
+use refresh_rate_windows_rs::config::DEFAULT_TOOLTIP;
+#[cfg(windows)]
 use refresh_rate_windows_rs::{
-    get_available_refresh_rates, get_all_display_devices, set_display_refresh_rate,
-    to_wide_string, DisplayDevice,
+    to_wide_string, AutoRevert, ChangeOptions, Config, ChangeOutcome, DisplayError, DisplayWatcher, InventoryCache,
+    forced_rates, fullscreen_rates_with, get_all_display_devices_with, AppRule, ForcedRate,
+    ForegroundWatcher, ForegroundWindow, FullscreenRule, FullscreenWatcher, Persistence,
+    PowerRules, PowerSource, PowerWatcher, Profile, ProfileReport, RateOverrides,
+    RequestedChange, RevertTick, Win32Backend,
 };
-use std::collections::HashMap;
-use std::sync::LazyLock;
+#[cfg(windows)]
+use refresh_rate_windows_rs::power::current_power_source;
 
+#[cfg(windows)]
 const WM_APP_NOTIFYICON: UINT = WM_USER + 1;
+/// Posted by the confirmation prompt thread: `wParam` is the button, `lParam`
+/// the prompt generation.
+#[cfg(windows)]
+const WM_APP_CONFIRM: UINT = WM_USER + 2;
 
-const MENU_REFRESH_RATE_BASE_ID: UINT = 2000; // Offset to avoid clashes
-const MENU_EXIT_ID: UINT = 9999;
+#[cfg(windows)]
+const REVERT_TIMER_ID: usize = 1;
+#[cfg(windows)]
+const PROMPT_TITLE: &str = "Keep these settings?";
 
-extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
-    static mut ALL_DISPLAY_DEVICES: Option<Vec<DisplayDevice>> = None;
-    static DEVICE_REFRESH_RATES: LazyLock<std::sync::Mutex<HashMap<String, Vec<DWORD>>>> =
-        LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+/// Environment variable selecting the log level (`error` .. `trace`).
+#[cfg(windows)]
+const LOG_LEVEL_ENV: &str = "REFRESH_RATE_LOG";
+
+/// The menu shown by the last right-click; command IDs are resolved against it.
+#[cfg(windows)]
+static TRAY_MENU: Mutex<Option<Arc<TrayMenu>>> = Mutex::new(None);
+
+/// Displays and their modes, rebuilt in the background when they change.
+#[cfg(windows)]
+static INVENTORY: InventoryCache = InventoryCache::new();
+
+/// Whether the display watcher keeps `INVENTORY` up to date.
+#[cfg(windows)]
+static WATCHING_DISPLAYS: AtomicBool = AtomicBool::new(false);
+
+/// Whether a right-click started rebuilding `INVENTORY` that has not finished.
+#[cfg(windows)]
+static REBUILDING_INVENTORY: AtomicBool = AtomicBool::new(false);
+
+/// Whether refresh rate picks survive a restart, as chosen in the menu.
+#[cfg(windows)]
+static PERSISTENCE: Mutex<Persistence> = Mutex::new(Persistence::Temporary);
+
+/// Reverts refresh rate picks that are not confirmed in time.
+#[cfg(windows)]
+static AUTO_REVERT: Mutex<AutoRevert> = Mutex::new(AutoRevert::new(DEFAULT_REVERT_TIMEOUT));
+
+/// Identifies the newest confirmation prompt, so answers from a stale one are ignored.
+#[cfg(windows)]
+static PROMPT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Set by `--dry-run`: menu commands only validate their change.
+#[cfg(windows)]
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Profiles from the config file, listed at the top of the menu.
+#[cfg(windows)]
+static PROFILES: Mutex<Vec<Profile>> = Mutex::new(Vec::new());
+
+/// Refresh rates per power source from the config file.
+#[cfg(windows)]
+static POWER_RULES: Mutex<PowerRules> = Mutex::new(PowerRules::new(Vec::new()));
+
+/// Refresh rates forced while an application is in the foreground.
+#[cfg(windows)]
+static APP_RULES: Mutex<Vec<AppRule>> = Mutex::new(Vec::new());
+
+/// Refresh rates forced while an application is full screen.
+#[cfg(windows)]
+static FULLSCREEN_RULES: Mutex<Vec<FullscreenRule>> = Mutex::new(Vec::new());
+
+/// The rates the app rules want for the current foreground application.
+#[cfg(windows)]
+static APP_FORCED: Mutex<Vec<ForcedRate>> = Mutex::new(Vec::new());
+
+/// The rates the fullscreen rules want for the current foreground window.
+#[cfg(windows)]
+static FULLSCREEN_FORCED: Mutex<Vec<ForcedRate>> = Mutex::new(Vec::new());
+
+/// Monitors whose rate an app or fullscreen rule changed, and the rate to
+/// put back.
+#[cfg(windows)]
+static RATE_OVERRIDES: Mutex<RateOverrides> = Mutex::new(RateOverrides::new());
+
+/// The config file and its settings; `None` when it could not be read, so a
+/// broken file is never overwritten.
+#[cfg(windows)]
+static CONFIG: Mutex<Option<(PathBuf, Config)>> = Mutex::new(None);
+
+/// The options menu commands and rules change modes with: the persistence
+/// chosen in the menu, and `--dry-run`.
+#[cfg(windows)]
+fn current_options() -> ChangeOptions {
+    ChangeOptions {
+        dry_run: DRY_RUN.load(Ordering::Relaxed),
+        persistence: *PERSISTENCE.lock().unwrap(),
+    }
+}
+
+/// Sends `log` output to `%LOCALAPPDATA%\refresh-rate-windows-rs\tray.log`,
+/// falling back to the temp directory. `REFRESH_RATE_LOG` overrides the
+/// configured level.
+#[cfg(windows)]
+fn init_logging(config_level: LevelFilter) {
+    let level = std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(config_level);
+    let log_dir = std::env::var_os("LOCALAPPDATA")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("refresh-rate-windows-rs");
+
+    match RotatingFileLogger::new(log_dir.join("tray.log"), level) {
+        Ok(logger) => {
+            let _ = logger.install();
+        }
+        Err(err) => eprintln!("Failed to open log file in {}: {}", log_dir.display(), err),
+    }
+}
+
+/// Logs how applying a profile or the power rules went, one line per monitor.
+#[cfg(windows)]
+fn log_profile_report(report: &ProfileReport) {
+    for monitor in &report.monitors {
+        let device = monitor.device_name.as_deref().unwrap_or("");
+        match &monitor.result {
+            Ok(outcome) => info!(
+                profile = report.profile.as_str(), monitor = monitor.monitor.as_str(), device = device;
+                "Profile monitor result: {:?}", outcome
+            ),
+            Err(error) => error!(
+                profile = report.profile.as_str(), monitor = monitor.monitor.as_str(), device = device, code = error.change_code();
+                "Failed to apply profile monitor: {}", error
+            ),
+        }
+    }
+}
+
+/// Applies the power rules for `source` if it changed since the last call.
+#[cfg(windows)]
+fn apply_power_rules(source: PowerSource) {
+    // Held so no override starts or ends while the rates are applied.
+    let mut overrides = RATE_OVERRIDES.lock().unwrap();
+    let report = POWER_RULES.lock().unwrap().apply_with(&Win32Backend, source, current_options(), &mut overrides);
+    if let Some(report) = report {
+        log_profile_report(&report);
+    }
+}
+
+/// Forces the rates the app and fullscreen rules want, app rules first,
+/// restoring monitors no rule wants any more.
+#[cfg(windows)]
+fn sync_overrides() {
+    // Held throughout so both watchers' updates apply in order.
+    let mut overrides = RATE_OVERRIDES.lock().unwrap();
+    let mut forced = APP_FORCED.lock().unwrap().clone();
+    for wanted in FULLSCREEN_FORCED.lock().unwrap().iter() {
+        if !forced.iter().any(|f| f.device_name == wanted.device_name) {
+            forced.push(wanted.clone());
+        }
+    }
+    overrides.sync_with(&Win32Backend, &forced, current_options());
+}
+
+/// Updates the rates the app rules want for the new foreground application.
+#[cfg(windows)]
+fn on_foreground_changed(path: Option<String>) {
+    let rules = APP_RULES.lock().unwrap();
+    let matched = path
+        .as_deref()
+        .is_some_and(|path| rules.iter().any(|rule| rule.matches_app(path)));
+    // Most foreground changes concern no rule; skip enumerating displays.
+    if !matched && APP_FORCED.lock().unwrap().is_empty() {
+        return;
+    }
+    let forced = match (path.as_deref(), get_all_display_devices_with(&Win32Backend)) {
+        (Some(path), Ok(devices)) if matched => forced_rates(&rules, path, &devices),
+        (_, Err(error)) => {
+            warn!("Could not enumerate displays for app rules: {}", error);
+            return;
+        }
+        _ => Vec::new(),
+    };
+    drop(rules);
+    *APP_FORCED.lock().unwrap() = forced;
+    sync_overrides();
+}
+
+/// Updates the rates the fullscreen rules want for the new foreground window.
+#[cfg(windows)]
+fn on_fullscreen_changed(window: Option<ForegroundWindow>) {
+    let forced = match window {
+        Some(window) => match get_all_display_devices_with(&Win32Backend) {
+            Ok(devices) => {
+                let rules = FULLSCREEN_RULES.lock().unwrap();
+                fullscreen_rates_with(&Win32Backend, &rules, &window, &devices)
+            }
+            Err(error) => {
+                warn!("Could not enumerate displays for fullscreen rules: {}", error);
+                return;
+            }
+        },
+        None => Vec::new(),
+    };
+    let mut current = FULLSCREEN_FORCED.lock().unwrap();
+    if *current == forced {
+        return;
+    }
+    *current = forced;
+    drop(current);
+    sync_overrides();
+}
+
+/// The inventory to build the menu from. The cached one is shown even when
+/// stale, so the menu never waits for enumeration once the first build is
+/// done. Without the display watcher nothing marks it stale, so each
+/// right-click rebuilds it in the background for the next one.
+#[cfg(windows)]
+fn menu_inventory() -> Result<Arc<TrayMenu>, DisplayError> {
+    let Some(menu) = INVENTORY.get() else {
+        return INVENTORY.refresh_with(&Win32Backend);
+    };
+    if !WATCHING_DISPLAYS.load(Ordering::Relaxed) && !REBUILDING_INVENTORY.swap(true, Ordering::Relaxed) {
+        std::thread::spawn(|| {
+            if let Err(error) = INVENTORY.refresh_with(&Win32Backend) {
+                warn!("Could not enumerate displays: {}", error);
+            }
+            REBUILDING_INVENTORY.store(false, Ordering::Relaxed);
+        });
+    }
+    Ok(menu)
+}
+
+/// Makes a change picked in the menu and, when it took effect, asks whether
+/// to keep it; unanswered, it is reverted when the countdown runs out.
+#[cfg(windows)]
+fn apply_with_confirmation(hwnd: HWND, device_name: &str, change: RequestedChange) {
+    let what = match change {
+        RequestedChange::RefreshRate(_) => "refresh rate",
+        RequestedChange::Mode(_) => "display mode",
+        RequestedChange::Orientation(_) => "orientation",
+    };
+    let mut auto_revert = AUTO_REVERT.lock().unwrap();
+    match auto_revert.request_with(&Win32Backend, device_name, change, current_options(), Instant::now()) {
+        Ok(ChangeOutcome::Validated) => info!(
+            device = device_name, change = change.to_string();
+            "Dry run: the driver would accept the {}", what
+        ),
+        Ok(_) => match auto_revert.pending() {
+            Some(change) => {
+                let text = prompt_text(change, auto_revert.timeout());
+                unsafe { SetTimer(hwnd, REVERT_TIMER_ID, 1000, None) };
+                show_confirmation_prompt(hwnd, text);
+            }
+            // Nothing to confirm, so the change is kept right away.
+            None => keep_over_rules(device_name, change.refresh_rate()),
+        },
+        Err(error) => error!(
+            device = device_name, change = change.to_string(), code = error.change_code();
+            "Failed to change {}: {}", what, error
+        ),
+    }
+}
+
+/// Makes `rate`, picked in the menu or by a profile, the rate `device_name`
+/// goes back to once no app or fullscreen rule forces it, instead of the one
+/// the rule replaced.
+#[cfg(windows)]
+fn keep_over_rules(device_name: &str, rate: Option<u32>) {
+    let Some(rate) = rate else {
+        return;
+    };
+    if RATE_OVERRIDES.lock().unwrap().set_previous(device_name, rate) {
+        info!(device = device_name, rate = rate; "Monitor is forced by a rule, keeping the new rate for afterwards");
+    }
+}
+
+/// Puts back the rates app and fullscreen rules forced, on exit.
+#[cfg(windows)]
+fn restore_forced_rates() {
+    APP_FORCED.lock().unwrap().clear();
+    FULLSCREEN_FORCED.lock().unwrap().clear();
+    RATE_OVERRIDES.lock().unwrap().restore_all_with(&Win32Backend, current_options());
+}
+
+/// Shows the "Keep these settings?" prompt on its own thread, so the countdown
+/// keeps running while it is open. The answer comes back as `WM_APP_CONFIRM`.
+#[cfg(windows)]
+fn show_confirmation_prompt(hwnd: HWND, text: String) {
+    close_confirmation_prompt();
+    let generation = PROMPT_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    let hwnd = hwnd as usize;
+    std::thread::spawn(move || {
+        let text = to_wide_string(&text);
+        let caption = to_wide_string(PROMPT_TITLE);
+        let answer = unsafe {
+            MessageBoxW(
+                ptr::null_mut(),
+                text.as_ptr(),
+                caption.as_ptr(),
+                MB_YESNO | MB_ICONQUESTION | MB_TOPMOST | MB_SETFOREGROUND,
+            )
+        };
+        unsafe { PostMessageW(hwnd as HWND, WM_APP_CONFIRM, answer as WPARAM, generation as LPARAM) };
+    });
+}
+
+/// The open confirmation prompt, if any.
+#[cfg(windows)]
+fn confirmation_prompt() -> HWND {
+    let class = to_wide_string("#32770"); // Dialog box class
+    let caption = to_wide_string(PROMPT_TITLE);
+    unsafe { FindWindowW(class.as_ptr(), caption.as_ptr()) }
+}
+
+#[cfg(windows)]
+fn update_confirmation_prompt(text: &str) {
+    let prompt = confirmation_prompt();
+    if !prompt.is_null() {
+        let text = to_wide_string(text);
+        // 0xFFFF is the ID of a message box's text control
+        unsafe { SetDlgItemTextW(prompt, 0xFFFF, text.as_ptr()) };
+    }
+}
+
+#[cfg(windows)]
+fn close_confirmation_prompt() {
+    let prompt = confirmation_prompt();
+    if !prompt.is_null() {
+        // A Yes/No box has no Cancel button, so it ignores WM_CLOSE; answer No instead.
+        unsafe { PostMessageW(prompt, WM_COMMAND, IDNO as WPARAM, 0) };
+    }
+}
 
+#[cfg(windows)]
+extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     match msg {
         WM_CREATE => {
             let mut nid: NOTIFYICONDATAW = unsafe { mem::zeroed() };
@@ -45,7 +400,16 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARA
 
             nid.hIcon = unsafe { LoadIconW(ptr::null_mut(), IDI_APPLICATION) };
 
-            let tip_text = to_wide_string("Refresh Rate Tray");
+            let tooltip = CONFIG
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map_or_else(|| DEFAULT_TOOLTIP.to_string(), |(_, config)| config.tooltip.clone());
+            let tip_text = to_wide_string(&if DRY_RUN.load(Ordering::Relaxed) {
+                format!("{} (dry run)", tooltip)
+            } else {
+                tooltip
+            });
             unsafe {
                 ptr::copy_nonoverlapping(
                     tip_text.as_ptr(),
@@ -68,76 +432,149 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARA
 
                     let hmenu = unsafe { CreatePopupMenu() };
                     if hmenu.is_null() {
-                        eprintln!("Failed to create popup menu. Last Error: {}", unsafe {
+                        error!("Failed to create popup menu. Last Error: {}", unsafe {
                             GetLastError()
                         });
                         return 0;
                     }
 
-                    // Dynamically get all display devices and their available refresh rates
+                    // Display devices and their available modes, as last enumerated
+                    let menu = menu_inventory().unwrap_or_else(|error| {
+                        error!("Could not enumerate displays: {}", error);
+                        Arc::new(TrayMenu::default())
+                    });
+                    let menu = Arc::new(TrayMenu::clone(&menu).with_profiles(&PROFILES.lock().unwrap()));
+
+                    // Add profiles above the monitors
                     unsafe {
-                        ALL_DISPLAY_DEVICES = Some(get_all_display_devices());
-                        let mut device_refresh_rates_guard = DEVICE_REFRESH_RATES.lock().unwrap();
-                        device_refresh_rates_guard.clear(); // Clear previous rates
-
-                        if let Some(devices) = ALL_DISPLAY_DEVICES.as_ref() {
-                            for device in devices {
-                                let device_name_wide = to_wide_string(&device.device_name);
-                                let rates = get_available_refresh_rates(&device_name_wide);
-                                device_refresh_rates_guard.insert(device.device_name.clone(), rates);
-                            }
+                        for (i, name) in menu.profiles.iter().enumerate() {
+                            let profile_text = to_wide_string(name);
+                            AppendMenuW(hmenu, 0, TrayMenu::profile_command_id(i) as usize, profile_text.as_ptr());
+                        }
+                        if !menu.profiles.is_empty() {
+                            AppendMenuW(hmenu, 0x00000800, 0, ptr::null()); // MF_SEPARATOR
                         }
                     }
 
                     // Add monitor submenus
                     unsafe {
-                        if let Some(devices) = ALL_DISPLAY_DEVICES.as_ref() {
-                            if devices.is_empty() {
-                                let no_monitors_text = to_wide_string("No monitors found");
-                                AppendMenuW(hmenu, 0, 0, no_monitors_text.as_ptr());
-                            } else {
-                                for (i, device) in devices.iter().enumerate() {
-                                    let submenu = CreatePopupMenu();
-                                    if submenu.is_null() {
-                                        eprintln!("Failed to create submenu for {}. Last Error: {}", device.display_name, GetLastError());
-                                        continue;
+                        if menu.monitors.is_empty() {
+                            let no_monitors_text = to_wide_string("No monitors found");
+                            AppendMenuW(hmenu, 0, 0, no_monitors_text.as_ptr());
+                        } else {
+                            for (i, monitor) in menu.monitors.iter().enumerate() {
+                                let submenu = CreatePopupMenu();
+                                if submenu.is_null() {
+                                    error!("Failed to create submenu for {}. Last Error: {}", monitor.device.display_name, GetLastError());
+                                    continue;
+                                }
+
+                                let monitor_menu_text = to_wide_string(&monitor.device.display_name);
+
+                                // Show what the panel advertises above its rates
+                                if let Some(label) = monitor.capabilities_label() {
+                                    let capabilities_text = to_wide_string(&label);
+                                    AppendMenuW(submenu, 0x00000001, 0, capabilities_text.as_ptr()); // MF_GRAYED
+                                    AppendMenuW(submenu, 0x00000800, 0, std::ptr::null()); // MF_SEPARATOR
+                                }
+
+                                // Add refresh rates to submenu
+                                if monitor.rates.is_empty() {
+                                    let no_rates_text = to_wide_string("No rates");
+                                    AppendMenuW(submenu, 0, 0, no_rates_text.as_ptr());
+                                } else {
+                                    for (j, support) in monitor.rates.iter().enumerate() {
+                                        let label = monitor.rate_label(j).unwrap_or_default();
+                                        let rate_menu_text = to_wide_string(&label);
+                                        // MF_GRAYED for rates the driver rejects
+                                        let flags = if support.accepted { 0 } else { 0x00000001 };
+                                        AppendMenuW(
+                                            submenu,
+                                            flags,
+                                            TrayMenu::rate_command_id(i, j) as usize, // Unique ID for each rate
+                                            rate_menu_text.as_ptr(),
+                                        );
                                     }
+                                }
 
-                                    let monitor_display_name = &device.display_name;
-                                    let monitor_menu_text = to_wide_string(monitor_display_name);
-
-                                    // Add refresh rates to submenu
-                                    let device_refresh_rates_guard = DEVICE_REFRESH_RATES.lock().unwrap();
-                                    if let Some(rates) = device_refresh_rates_guard.get(&device.device_name) {
-                                        if rates.is_empty() {
-                                            let no_rates_text = to_wide_string("No rates");
-                                            AppendMenuW(submenu, 0, 0, no_rates_text.as_ptr());
-                                        } else {
-                                            for (j, &rate) in rates.iter().enumerate() {
-                                                let rate_menu_text = to_wide_string(&format!("{} Hz", rate));
-                                                AppendMenuW(
-                                                    submenu,
-                                                    0,
-                                                    (MENU_REFRESH_RATE_BASE_ID + (i * 100) as UINT + j as UINT) as usize, // Unique ID for each rate
-                                                    rate_menu_text.as_ptr(),
-                                                );
-                                            }
+                                // Add resolutions below the rates
+                                if !monitor.resolutions.is_empty() {
+                                    let resolution_menu = CreatePopupMenu();
+                                    if !resolution_menu.is_null() {
+                                        for j in 0..monitor.resolutions.len() {
+                                            let label = monitor.resolution_label(j).unwrap_or_default();
+                                            let resolution_menu_text = to_wide_string(&label);
+                                            // MF_CHECKED for the current resolution
+                                            let flags = if monitor.is_current_resolution(j) { 0x00000008 } else { 0 };
+                                            AppendMenuW(
+                                                resolution_menu,
+                                                flags,
+                                                TrayMenu::resolution_command_id(i, j) as usize,
+                                                resolution_menu_text.as_ptr(),
+                                            );
                                         }
-                                    } else {
-                                        let no_rates_text = to_wide_string("No rates");
-                                        AppendMenuW(submenu, 0, 0, no_rates_text.as_ptr());
+                                        let resolution_text = to_wide_string("Resolution");
+                                        AppendMenuW(submenu, 0x00000800, 0, ptr::null()); // MF_SEPARATOR
+                                        AppendMenuW(submenu, 0x00000010, resolution_menu as usize, resolution_text.as_ptr()); // MF_POPUP
                                     }
+                                }
 
-                                    AppendMenuW(
-                                        hmenu,
-                                        0x00000010, // MF_POPUP
-                                        submenu as usize,
-                                        monitor_menu_text.as_ptr(),
-                                    );
+                                // Add orientations below the resolutions
+                                if !monitor.orientations.is_empty() {
+                                    let orientation_menu = CreatePopupMenu();
+                                    if !orientation_menu.is_null() {
+                                        for (j, support) in monitor.orientations.iter().enumerate() {
+                                            let label = monitor.orientation_label(j).unwrap_or_default();
+                                            let orientation_menu_text = to_wide_string(&label);
+                                            // MF_CHECKED for the current orientation, MF_GRAYED for ones the driver rejects
+                                            let mut flags = if support.accepted { 0 } else { 0x00000001 };
+                                            if monitor.current.map(|mode| mode.orientation) == Some(support.orientation) {
+                                                flags |= 0x00000008;
+                                            }
+                                            AppendMenuW(
+                                                orientation_menu,
+                                                flags,
+                                                TrayMenu::orientation_command_id(i, j) as usize,
+                                                orientation_menu_text.as_ptr(),
+                                            );
+                                        }
+                                        let orientation_text = to_wide_string("Orientation");
+                                        AppendMenuW(submenu, 0x00000010, orientation_menu as usize, orientation_text.as_ptr()); // MF_POPUP
+                                    }
                                 }
+
+                                AppendMenuW(
+                                    hmenu,
+                                    0x00000010, // MF_POPUP
+                                    submenu as usize,
+                                    monitor_menu_text.as_ptr(),
+                                );
                             }
                         }
                     }
+                    *TRAY_MENU.lock().unwrap() = Some(menu);
+
+                    // Add the persistence choice
+                    let current_persistence = *PERSISTENCE.lock().unwrap();
+                    unsafe {
+                        AppendMenuW(hmenu, 0x00000800, 0, ptr::null()); // MF_SEPARATOR
+                        let submenu = CreatePopupMenu();
+                        if !submenu.is_null() {
+                            for (i, &persistence) in Persistence::ALL.iter().enumerate() {
+                                let text = to_wide_string(persistence_label(persistence));
+                                // MF_CHECKED for the current choice
+                                let flags = if persistence == current_persistence { 0x00000008 } else { 0 };
+                                AppendMenuW(
+                                    submenu,
+                                    flags,
+                                    TrayMenu::persistence_command_id(i) as usize,
+                                    text.as_ptr(),
+                                );
+                            }
+                            let persistence_text = to_wide_string("Apply changes");
+                            AppendMenuW(hmenu, 0x00000010, submenu as usize, persistence_text.as_ptr()); // MF_POPUP
+                        }
+                    }
 
                     // Add a separator and Exit
                     let separator_text = to_wide_string("-");
@@ -175,35 +612,87 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARA
         WM_COMMAND => {
             // Handle menu item selections
             let menu_id = LOWORD(wparam as DWORD) as UINT;
-            unsafe {
-                if menu_id >= MENU_REFRESH_RATE_BASE_ID {
-                    // This is a refresh rate selection
-                    let monitor_index = ((menu_id - MENU_REFRESH_RATE_BASE_ID) / 100) as usize;
-                    let rate_index = ((menu_id - MENU_REFRESH_RATE_BASE_ID) % 100) as usize;
-
-                    if let Some(devices) = ALL_DISPLAY_DEVICES.as_ref() {
-                        if monitor_index < devices.len() {
-                            let device = &devices[monitor_index];
-                            let device_refresh_rates_guard = DEVICE_REFRESH_RATES.lock().unwrap();
-                            if let Some(rates) = device_refresh_rates_guard.get(&device.device_name) {
-                                if rate_index < rates.len() {
-                                    let selected_rate = rates[rate_index];
-                                    set_display_refresh_rate(&device.device_name, selected_rate);
-                                } else {
-                                    eprintln!("Error: Refresh rate index out of bounds.");
-                                }
-                            } else {
-                                eprintln!("Error: Refresh rates not found for device: {}", device.device_name);
+            let command = TRAY_MENU
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|menu| menu.command(menu_id));
+            match command {
+                Some(MenuCommand::SetRefreshRate { device_name, rate }) => {
+                    apply_with_confirmation(hwnd, &device_name, RequestedChange::RefreshRate(rate));
+                }
+                Some(MenuCommand::SetDisplayMode { device_name, mode }) => {
+                    apply_with_confirmation(hwnd, &device_name, RequestedChange::Mode(mode));
+                }
+                Some(MenuCommand::SetOrientation { device_name, orientation }) => {
+                    apply_with_confirmation(hwnd, &device_name, RequestedChange::Orientation(orientation));
+                }
+                Some(MenuCommand::ApplyProfile(name)) => {
+                    let profile = PROFILES.lock().unwrap().iter().find(|p| p.name == name).cloned();
+                    let Some(profile) = profile else {
+                        warn!(profile = name.as_str(); "Profile no longer exists");
+                        return 0;
+                    };
+                    let report = profile.apply_with(&Win32Backend, current_options());
+                    log_profile_report(&report);
+                    if !current_options().dry_run {
+                        // One result per entry, in order.
+                        for (entry, monitor) in profile.monitors.iter().zip(&report.monitors) {
+                            if let (Some(device_name), Ok(_)) = (&monitor.device_name, &monitor.result) {
+                                keep_over_rules(device_name, entry.refresh_rate);
                             }
-                        } else {
-                            eprintln!("Error: Monitor index out of bounds.");
                         }
-                    } else {
-                        eprintln!("Error: Display devices not available.");
                     }
-                } else if menu_id == MENU_EXIT_ID {
-                    // Exit
-                    PostQuitMessage(0);
+                }
+                Some(MenuCommand::SetPersistence(persistence)) => {
+                    info!(persistence = persistence.as_str(); "Changed persistence");
+                    *PERSISTENCE.lock().unwrap() = persistence;
+                    if let Some((path, config)) = CONFIG.lock().unwrap().as_mut() {
+                        config.persistence = persistence;
+                        if let Err(error) = config.save_setting(path, "persistence") {
+                            warn!("Could not save the persistence choice: {}", error);
+                        }
+                    }
+                }
+                // Exit
+                Some(MenuCommand::Exit) => unsafe {
+                    DestroyWindow(hwnd);
+                },
+                None => warn!("Unknown menu command {}", menu_id),
+            }
+            0
+        }
+        WM_TIMER if wparam == REVERT_TIMER_ID => {
+            let tick = AUTO_REVERT.lock().unwrap().tick_with(&Win32Backend, Instant::now());
+            match tick {
+                RevertTick::Waiting { remaining } => {
+                    if let Some(change) = AUTO_REVERT.lock().unwrap().pending() {
+                        update_confirmation_prompt(&prompt_text(change, remaining));
+                    }
+                }
+                RevertTick::Reverted { change, result } => {
+                    unsafe { KillTimer(hwnd, REVERT_TIMER_ID) };
+                    close_confirmation_prompt();
+                    if let Err(error) = result {
+                        error!(device = change.device_name.as_str(); "Failed to revert refresh rate: {}", error);
+                    }
+                }
+                RevertTick::Idle => unsafe {
+                    KillTimer(hwnd, REVERT_TIMER_ID);
+                },
+            }
+            0
+        }
+        WM_APP_CONFIRM => {
+            if lparam as usize == PROMPT_GENERATION.load(Ordering::Relaxed) {
+                unsafe { KillTimer(hwnd, REVERT_TIMER_ID) };
+                let mut auto_revert = AUTO_REVERT.lock().unwrap();
+                if wparam as i32 == IDYES {
+                    if let Some(change) = auto_revert.confirm() {
+                        keep_over_rules(&change.device_name, change.requested.refresh_rate());
+                    }
+                } else if let Some(Err(error)) = auto_revert.revert_with(&Win32Backend) {
+                    error!("Failed to revert refresh rate: {}", error);
                 }
             }
             0
@@ -227,7 +716,53 @@ extern "system" fn wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARA
     }
 }
 
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("The refresh rate tray only runs on Windows.");
+    std::process::exit(1);
+}
+
+#[cfg(windows)]
 fn main() {
+    let config_path = Config::default_path();
+    let loaded = config_path.as_deref().map(Config::load_or_create);
+    let config = match &loaded {
+        Some(Ok(config)) => config.clone(),
+        _ => Config::default(),
+    };
+    init_logging(config.log_level);
+    match loaded {
+        Some(Ok(config)) => {
+            info!(profiles = config.profiles.len(); "Loaded config");
+            *CONFIG.lock().unwrap() = config_path.map(|path| (path, config));
+        }
+        Some(Err(error)) => error!("Using default settings, the config file is unusable: {}", error),
+        None => warn!("APPDATA is not set, using default settings"),
+    }
+    *PERSISTENCE.lock().unwrap() = config.persistence;
+    AUTO_REVERT.lock().unwrap().set_timeout(config.revert_timeout);
+    *PROFILES.lock().unwrap() = config.profiles;
+    *POWER_RULES.lock().unwrap() = PowerRules::new(config.power);
+    *APP_RULES.lock().unwrap() = config.apps;
+    *FULLSCREEN_RULES.lock().unwrap() = config.fullscreen;
+
+    for arg in std::env::args().skip(1) {
+        match arg.split_once('=') {
+            None if arg == "--dry-run" => DRY_RUN.store(true, Ordering::Relaxed),
+            Some(("--revert-timeout", seconds)) => match seconds.parse() {
+                Ok(seconds) => AUTO_REVERT
+                    .lock()
+                    .unwrap()
+                    .set_timeout(Duration::from_secs(seconds)),
+                Err(_) => warn!("Ignoring invalid revert timeout {}", seconds),
+            },
+            _ => warn!("Ignoring unknown argument {}", arg),
+        }
+    }
+    if DRY_RUN.load(Ordering::Relaxed) {
+        info!("Dry run: refresh rate changes are only validated");
+    }
+
     // Get the instance handle for the application.
     let hinstance = unsafe { GetModuleHandleW(ptr::null_mut()) };
 
@@ -243,8 +778,8 @@ fn main() {
     wc.hCursor = unsafe { LoadIconW(ptr::null_mut(), IDC_ARROW) as *mut _ };
     wc.lpszClassName = class_name.as_ptr();
 
-    if unsafe { RegisterClassExW(&mut wc) } == 0 {
-        eprintln!("Failed to register window class, error: {}", unsafe {
+    if unsafe { RegisterClassExW(&wc) } == 0 {
+        error!("Failed to register window class, error: {}", unsafe {
             GetLastError()
         });
         return;
@@ -270,7 +805,7 @@ fn main() {
     };
 
     if hwnd.is_null() {
-        eprintln!("Failed to create window, error: {}", unsafe {
+        error!("Failed to create window, error: {}", unsafe {
             GetLastError()
         });
         return;
@@ -280,6 +815,70 @@ fn main() {
     unsafe { ShowWindow(hwnd, SW_HIDE) };
     unsafe { UpdateWindow(hwnd) };
 
+    // Enumerate displays in the background so the first right-click is instant,
+    // and rebuild the inventory whenever they change.
+    std::thread::spawn(|| {
+        if let Err(error) = INVENTORY.refresh_with(&Win32Backend) {
+            warn!("Could not enumerate displays: {}", error);
+        }
+    });
+    let _watcher = match DisplayWatcher::start(|event| {
+        info!("Display change: {:?}", event);
+        INVENTORY.invalidate();
+        if let Err(error) = INVENTORY.refresh_with(&Win32Backend) {
+            warn!("Could not enumerate displays: {}", error);
+        }
+    }) {
+        Ok(watcher) => {
+            WATCHING_DISPLAYS.store(true, Ordering::Relaxed);
+            Some(watcher)
+        }
+        Err(error) => {
+            warn!("The menu shows the displays as of the previous right-click: {}", error);
+            None
+        }
+    };
+
+    // Follow the power source if any monitor has a rate for it.
+    let power_watcher = if POWER_RULES.lock().unwrap().rules().is_empty() {
+        None
+    } else {
+        if let Some(source) = current_power_source() {
+            apply_power_rules(source);
+        }
+        match PowerWatcher::start(apply_power_rules) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                warn!("Power rules only apply at start-up: {}", error);
+                None
+            }
+        }
+    };
+
+    let foreground_watcher = if APP_RULES.lock().unwrap().is_empty() {
+        None
+    } else {
+        match ForegroundWatcher::start(on_foreground_changed) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                warn!("App rules are disabled: {}", error);
+                None
+            }
+        }
+    };
+
+    let fullscreen_watcher = if FULLSCREEN_RULES.lock().unwrap().is_empty() {
+        None
+    } else {
+        match FullscreenWatcher::start(on_fullscreen_changed) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                warn!("Fullscreen rules are disabled: {}", error);
+                None
+            }
+        }
+    };
+
     // Message loop
     let mut msg: MSG = unsafe { mem::zeroed() };
     loop {
@@ -288,7 +887,7 @@ fn main() {
             // WM_QUIT received, exit loop
             break;
         } else if ret == -1 {
-            eprintln!("Error in message loop, error: {}", unsafe {
+            error!("Error in message loop, error: {}", unsafe {
                 GetLastError()
             });
             break;
@@ -299,4 +898,9 @@ fn main() {
             }
         }
     }
+
+    // Stop the rule watchers first so none forces a rate again, then don't
+    // leave monitors at a rate an app or fullscreen rule forced.
+    drop((power_watcher, foreground_watcher, fullscreen_watcher));
+    restore_forced_rates();
 }
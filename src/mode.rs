@@ -0,0 +1,414 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+/// Screen rotation (`dmDisplayOrientation`), clockwise from the panel's
+/// natural orientation. Serializes as its degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(into = "u32")]
+pub enum Orientation {
+    #[default]
+    Landscape,
+    Portrait,
+    LandscapeFlipped,
+    PortraitFlipped,
+}
+
+impl Orientation {
+    /// Converts a `DMDO_*` value; unknown values are treated as the default.
+    pub fn from_dmdo(value: u32) -> Self {
+        match value {
+            1 => Orientation::Portrait,
+            2 => Orientation::LandscapeFlipped,
+            3 => Orientation::PortraitFlipped,
+            _ => Orientation::Landscape,
+        }
+    }
+
+    pub fn to_dmdo(self) -> u32 {
+        match self {
+            Orientation::Landscape => 0,
+            Orientation::Portrait => 1,
+            Orientation::LandscapeFlipped => 2,
+            Orientation::PortraitFlipped => 3,
+        }
+    }
+
+    pub const ALL: [Orientation; 4] = [
+        Orientation::Landscape,
+        Orientation::Portrait,
+        Orientation::LandscapeFlipped,
+        Orientation::PortraitFlipped,
+    ];
+
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub fn degrees(self) -> u32 {
+        self.to_dmdo() * 90
+    }
+
+    /// The orientation rotated `degrees` clockwise, if it is a multiple of 90.
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees % 360 {
+            0 => Some(Orientation::Landscape),
+            90 => Some(Orientation::Portrait),
+            180 => Some(Orientation::LandscapeFlipped),
+            270 => Some(Orientation::PortraitFlipped),
+            _ => None,
+        }
+    }
+
+    /// Whether the desktop is taller than wide in this orientation.
+    pub fn is_portrait(self) -> bool {
+        matches!(self, Orientation::Portrait | Orientation::PortraitFlipped)
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Orientation::Landscape => "Landscape",
+            Orientation::Portrait => "Portrait",
+            Orientation::LandscapeFlipped => "Landscape (flipped)",
+            Orientation::PortraitFlipped => "Portrait (flipped)",
+        })
+    }
+}
+
+impl From<Orientation> for u32 {
+    fn from(orientation: Orientation) -> Self {
+        orientation.degrees()
+    }
+}
+
+/// How a low-resolution mode is presented on a fixed-resolution panel
+/// (`dmDisplayFixedOutput`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    #[default]
+    Default,
+    Stretch,
+    Center,
+}
+
+impl Scaling {
+    /// Converts a `DMDFO_*` value; unknown values are treated as the default.
+    pub fn from_dmdfo(value: u32) -> Self {
+        match value {
+            1 => Scaling::Stretch,
+            2 => Scaling::Center,
+            _ => Scaling::Default,
+        }
+    }
+
+    pub fn to_dmdfo(self) -> u32 {
+        match self {
+            Scaling::Default => 0,
+            Scaling::Stretch => 1,
+            Scaling::Center => 2,
+        }
+    }
+
+    pub const ALL: [Scaling; 3] = [Scaling::Default, Scaling::Stretch, Scaling::Center];
+
+    /// The name used in snapshot and profile files.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scaling::Default => "default",
+            Scaling::Stretch => "stretch",
+            Scaling::Center => "center",
+        }
+    }
+}
+
+/// An exact refresh rate as the CCD API (`DISPLAYCONFIG_RATIONAL`) reports
+/// it, e.g. 60000/1001 for the 59.94 Hz `DEVMODEW` can only call 59 Hz.
+///
+/// Rates compare equal when they agree to the millihertz, so 60000/1001 and
+/// 59940/1000 are the same rate.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(into = "RateFields")]
+pub struct RefreshRate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl RefreshRate {
+    pub const fn new(numerator: u32, denominator: u32) -> Self {
+        RefreshRate {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Exactly `hz` hertz.
+    pub const fn integer(hz: u32) -> Self {
+        RefreshRate::new(hz, 1)
+    }
+
+    /// The NTSC-style variant of `hz`, slowed down by 1000/1001
+    /// (60 → 59.94, 24 → 23.976).
+    pub const fn ntsc(hz: u32) -> Self {
+        RefreshRate::new(hz * 1000, 1001)
+    }
+
+    /// The rate in millihertz, rounded to nearest.
+    pub fn millihz(&self) -> u32 {
+        if self.denominator == 0 {
+            return 0;
+        }
+        let denominator = u64::from(self.denominator);
+        ((u64::from(self.numerator) * 1000 + denominator / 2) / denominator) as u32
+    }
+
+    /// The integer rate `DEVMODEW::dmDisplayFrequency` reports for this rate,
+    /// which Windows rounds down.
+    pub fn dm_frequency(&self) -> u32 {
+        self.numerator.checked_div(self.denominator).unwrap_or(0)
+    }
+
+    /// The rate rounded to whole hertz, as users name it (59.94 → 60).
+    pub fn rounded_hz(&self) -> u32 {
+        (self.millihz() + 500) / 1000
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.millihz().is_multiple_of(1000)
+    }
+}
+
+impl From<u32> for RefreshRate {
+    fn from(hz: u32) -> Self {
+        RefreshRate::integer(hz)
+    }
+}
+
+impl PartialEq for RefreshRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.millihz() == other.millihz()
+    }
+}
+
+impl Eq for RefreshRate {}
+
+impl std::hash::Hash for RefreshRate {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.millihz().hash(state);
+    }
+}
+
+impl PartialOrd for RefreshRate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RefreshRate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.millihz().cmp(&other.millihz())
+    }
+}
+
+impl fmt::Display for RefreshRate {
+    /// `60 Hz`, `59.94 Hz`, `23.976 Hz`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millihz = self.millihz();
+        if millihz.is_multiple_of(1000) {
+            return write!(f, "{} Hz", millihz / 1000);
+        }
+        let fraction = format!("{:03}", millihz % 1000);
+        write!(
+            f,
+            "{}.{} Hz",
+            millihz / 1000,
+            fraction.trim_end_matches('0')
+        )
+    }
+}
+
+impl FromStr for RefreshRate {
+    type Err = String;
+
+    /// `144`, `59.94` or `59.94 Hz`. Fractions that are the 1000/1001
+    /// variant of a whole rate parse as that variant, so `59.94` is
+    /// `RefreshRate::ntsc(60)`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid refresh rate `{}`, expected e.g. 144 or 59.94", s);
+        let text = s.trim();
+        let text = text
+            .strip_suffix("Hz")
+            .or_else(|| text.strip_suffix("hz"))
+            .unwrap_or(text)
+            .trim_end();
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if whole.is_empty() || fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let hz: u32 = whole.parse().map_err(|_| invalid())?;
+        let millihz = format!("{:0<3}", fraction)
+            .parse::<u32>()
+            .ok()
+            .and_then(|fraction| hz.checked_mul(1000)?.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        if millihz == 0 {
+            return Err(invalid());
+        }
+        if millihz.is_multiple_of(1000) {
+            return Ok(RefreshRate::integer(hz));
+        }
+        let ntsc = (hz + 1)
+            .checked_mul(1000)
+            .map(|numerator| RefreshRate::new(numerator, 1001));
+        if let Some(ntsc) = ntsc.filter(|ntsc| ntsc.millihz() == millihz) {
+            return Ok(ntsc);
+        }
+        Ok(RefreshRate::new(millihz, 1000))
+    }
+}
+
+/// How a `RefreshRate` serializes: the exact fraction, plus `hz` for
+/// readers that only want a number.
+#[derive(Serialize)]
+struct RateFields {
+    numerator: u32,
+    denominator: u32,
+    /// Rounded to the millihertz, e.g. 59.94.
+    hz: f64,
+}
+
+impl From<RefreshRate> for RateFields {
+    fn from(rate: RefreshRate) -> Self {
+        RateFields {
+            numerator: rate.numerator,
+            denominator: rate.denominator,
+            hz: f64::from(rate.millihz()) / 1000.0,
+        }
+    }
+}
+
+/// Top-left corner of a monitor on the virtual desktop (`dmPosition`). The
+/// primary monitor is always at 0,0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub const fn new(x: i32, y: i32) -> Self {
+        Position { x, y }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+/// A display mode as reported by `EnumDisplaySettingsW`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pel: u32,
+    pub frequency: u32,
+    pub interlaced: bool,
+    pub scaling: Scaling,
+    pub orientation: Orientation,
+}
+
+impl DisplayMode {
+    /// A progressive, unrotated mode with the driver's default scaling.
+    pub fn new(width: u32, height: u32, bits_per_pel: u32, frequency: u32) -> Self {
+        DisplayMode {
+            width,
+            height,
+            bits_per_pel,
+            frequency,
+            ..Default::default()
+        }
+    }
+
+    /// This mode turned to `orientation`. `dmPelsWidth` and `dmPelsHeight`
+    /// describe the rotated desktop, so they swap when going between
+    /// landscape and portrait.
+    pub fn rotated(self, orientation: Orientation) -> Self {
+        let (width, height) = if self.orientation.is_portrait() == orientation.is_portrait() {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+        DisplayMode {
+            width,
+            height,
+            orientation,
+            ..self
+        }
+    }
+
+    /// Whether `other` has the same resolution and colour depth, i.e. whether
+    /// switching between the two only changes the refresh rate.
+    pub fn is_compatible_with(&self, other: &DisplayMode) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.bits_per_pel == other.bits_per_pel
+    }
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{} @ {} Hz", self.width, self.height, self.frequency)?;
+        if self.interlaced {
+            write!(f, " (interlaced)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The modes in `modes` that only differ from `current` in refresh rate.
+pub fn compatible_modes(modes: &[DisplayMode], current: &DisplayMode) -> Vec<DisplayMode> {
+    modes
+        .iter()
+        .filter(|mode| mode.is_compatible_with(current))
+        .copied()
+        .collect()
+}
+
+/// Sorted, de-duplicated refresh rates of `modes`, skipping the 0 and 1 Hz
+/// placeholders drivers use for "hardware default".
+pub fn refresh_rates(modes: &[DisplayMode]) -> Vec<u32> {
+    let mut rates: Vec<u32> = modes
+        .iter()
+        .map(|mode| mode.frequency)
+        .filter(|&frequency| frequency > 1)
+        .collect();
+    rates.sort_unstable();
+    rates.dedup();
+    rates
+}
+
+/// One mode per resolution in `modes` at `current`'s colour depth, largest
+/// first. Each keeps `current`'s refresh rate where the driver lists it and
+/// uses the highest listed rate otherwise.
+pub fn resolution_modes(modes: &[DisplayMode], current: &DisplayMode) -> Vec<DisplayMode> {
+    let mut resolutions: Vec<DisplayMode> = Vec::new();
+    for mode in modes
+        .iter()
+        .filter(|mode| mode.bits_per_pel == current.bits_per_pel && mode.frequency > 1)
+    {
+        match resolutions.iter_mut().find(|r| r.is_compatible_with(mode)) {
+            Some(best) => {
+                if best.frequency != current.frequency
+                    && (mode.frequency == current.frequency || mode.frequency > best.frequency)
+                {
+                    *best = *mode;
+                }
+            }
+            None => resolutions.push(*mode),
+        }
+    }
+    resolutions.sort_by_key(|mode| std::cmp::Reverse((mode.width * mode.height, mode.width)));
+    resolutions
+}
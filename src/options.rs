@@ -0,0 +1,101 @@
+//! Options controlling how mode changes are applied.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::backend::{CDS_GLOBAL, CDS_UPDATEREGISTRY, SDC_SAVE_TO_DATABASE};
+
+/// Whether a mode change survives a restart.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Persistence {
+    /// Dynamic change only (flags `0`); undone by a reboot or driver reset.
+    #[default]
+    Temporary,
+    /// Also saved to the current user's registry settings
+    /// (`CDS_UPDATEREGISTRY`, `SDC_SAVE_TO_DATABASE`).
+    Persistent,
+    /// Saved for every user (`CDS_UPDATEREGISTRY | CDS_GLOBAL`). The CCD
+    /// database has no per-user split, so exact rates persist as `Persistent`.
+    Global,
+}
+
+impl Persistence {
+    pub const ALL: [Persistence; 3] = [
+        Persistence::Temporary,
+        Persistence::Persistent,
+        Persistence::Global,
+    ];
+
+    /// `ChangeDisplaySettingsExW` flags for this choice.
+    pub fn cds_flags(self) -> u32 {
+        match self {
+            Persistence::Temporary => 0,
+            Persistence::Persistent => CDS_UPDATEREGISTRY,
+            Persistence::Global => CDS_UPDATEREGISTRY | CDS_GLOBAL,
+        }
+    }
+
+    /// `SetDisplayConfig` flags for this choice.
+    pub fn sdc_flags(self) -> u32 {
+        match self {
+            Persistence::Temporary => 0,
+            Persistence::Persistent | Persistence::Global => SDC_SAVE_TO_DATABASE,
+        }
+    }
+
+    /// The name used in the config file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Persistence::Temporary => "temporary",
+            Persistence::Persistent => "persistent",
+            Persistence::Global => "global",
+        }
+    }
+}
+
+impl fmt::Display for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Persistence::ALL
+            .into_iter()
+            .find(|p| p.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "unknown persistence `{}`, expected temporary, persistent or global",
+                    s
+                )
+            })
+    }
+}
+
+/// How a mode change request is carried out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeOptions {
+    /// Only ask the driver whether it would accept the change (`CDS_TEST` or
+    /// `SDC_VALIDATE`) and report `ChangeOutcome::Validated`.
+    pub dry_run: bool,
+    pub persistence: Persistence,
+}
+
+impl ChangeOptions {
+    pub fn dry_run() -> Self {
+        ChangeOptions {
+            dry_run: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_persistence(persistence: Persistence) -> Self {
+        ChangeOptions {
+            persistence,
+            ..Default::default()
+        }
+    }
+}
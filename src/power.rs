@@ -0,0 +1,279 @@
+//! Refresh rates that follow the power source, e.g. 144 Hz plugged in and
+//! 60 Hz on battery.
+//!
+//! Rules live in the config file, one per monitor:
+//!
+//! ```toml
+//! [[power]]
+//! monitor = "DEL4123"
+//! ac = 144
+//! battery = 60
+//! ```
+//!
+//! `monitor` is matched like a profile's, see `DisplayDevice::matches_id`;
+//! either rate may be left out to leave the monitor alone on that source.
+//!
+//! `PowerRules` decides what to switch when the power source changes and
+//! only ever sees `PowerSource` values, so it works with synthetic events.
+//! Monitors an app or fullscreen rule currently forces keep that rate; the
+//! power rate becomes the one their `RateOverrides` entry puts back.
+//! On Windows, `PowerWatcher` reads `GetSystemPowerStatus` whenever
+//! `WM_POWERBROADCAST` reports a power status change.
+
+use std::fmt;
+
+use log::info;
+use toml::value::{Table, Value};
+
+use crate::backend::DisplayBackend;
+use crate::format::{Fields, FormatError};
+use crate::get_all_display_devices_with;
+use crate::options::ChangeOptions;
+use crate::profile::{Profile, ProfileMonitor, ProfileReport};
+use crate::rules::RateOverrides;
+
+/// Where the system draws power from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+impl PowerSource {
+    /// From `SYSTEM_POWER_STATUS::ACLineStatus`: 0 is offline, 1 online and
+    /// 255 unknown.
+    pub fn from_ac_line_status(status: u8) -> Option<Self> {
+        match status {
+            0 => Some(PowerSource::Battery),
+            1 => Some(PowerSource::Ac),
+            _ => None,
+        }
+    }
+
+    /// The config key for this source's rate.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PowerSource::Ac => "ac",
+            PowerSource::Battery => "battery",
+        }
+    }
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PowerSource::Ac => "AC power",
+            PowerSource::Battery => "battery",
+        })
+    }
+}
+
+/// The refresh rates one monitor should run at on each power source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerRule {
+    /// Device interface path, PnP device ID or hardware ID of the monitor.
+    pub monitor: String,
+    /// Rate in hertz on AC power; `None` leaves the monitor alone.
+    pub ac: Option<u32>,
+    /// Rate in hertz on battery; `None` leaves the monitor alone.
+    pub battery: Option<u32>,
+}
+
+impl PowerRule {
+    pub fn rate(&self, source: PowerSource) -> Option<u32> {
+        match source {
+            PowerSource::Ac => self.ac,
+            PowerSource::Battery => self.battery,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        table.insert("monitor".to_string(), Value::String(self.monitor.clone()));
+        for source in [PowerSource::Ac, PowerSource::Battery] {
+            if let Some(rate) = self.rate(source) {
+                table.insert(source.as_str().to_string(), Value::Integer(rate.into()));
+            }
+        }
+        Value::Table(table)
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Result<Self, FormatError> {
+        Ok(PowerRule {
+            monitor: fields.string("monitor")?,
+            ac: fields.optional_unsigned("ac")?,
+            battery: fields.optional_unsigned("battery")?,
+        })
+    }
+}
+
+pub(crate) fn power_rules_from_fields(fields: &Fields) -> Result<Vec<PowerRule>, FormatError> {
+    fields
+        .tables("power")?
+        .iter()
+        .map(PowerRule::from_fields)
+        .collect()
+}
+
+/// Turns power source changes into refresh rate changes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerRules {
+    rules: Vec<PowerRule>,
+    /// The last source seen; `None` before the first.
+    source: Option<PowerSource>,
+}
+
+impl PowerRules {
+    pub const fn new(rules: Vec<PowerRule>) -> Self {
+        PowerRules {
+            rules,
+            source: None,
+        }
+    }
+
+    pub fn rules(&self) -> &[PowerRule] {
+        &self.rules
+    }
+
+    pub fn source(&self) -> Option<PowerSource> {
+        self.source
+    }
+
+    /// Records that the system runs on `source` and returns what to apply:
+    /// the rates for `source`, as a profile, when it differs from the last
+    /// source seen. The first source seen always counts as a change, so the
+    /// rules hold from start-up. `None` when nothing needs switching.
+    pub fn update(&mut self, source: PowerSource) -> Option<Profile> {
+        if self.source.replace(source) == Some(source) {
+            return None;
+        }
+        let monitors: Vec<_> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                Some(ProfileMonitor {
+                    monitor: rule.monitor.clone(),
+                    resolution: None,
+                    refresh_rate: Some(rule.rate(source)?),
+                })
+            })
+            .collect();
+        if monitors.is_empty() {
+            return None;
+        }
+        Some(Profile {
+            name: source.to_string(),
+            monitors,
+        })
+    }
+
+    /// `update`, then applies the rates in one batch. Monitors in
+    /// `overrides` are not switched; their rate is what the override puts
+    /// back instead. Returns the report, or `None` when the source did not
+    /// change.
+    pub fn apply_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+        source: PowerSource,
+        options: ChangeOptions,
+        overrides: &mut RateOverrides,
+    ) -> Option<ProfileReport> {
+        let mut profile = self.update(source)?;
+        info!(source = source.as_str(); "Power source changed, applying power rules");
+        if !overrides.active().is_empty() {
+            // Without devices the profile reports the failure itself.
+            if let Ok(devices) = get_all_display_devices_with(backend) {
+                profile.monitors.retain(|entry| {
+                    let device = devices.iter().find(|device| entry.matches(device));
+                    match (device, entry.refresh_rate) {
+                        (Some(device), Some(rate))
+                            if overrides.set_previous(&device.device_name, rate) =>
+                        {
+                            info!(device = device.device_name.as_str(), rate = rate; "Monitor is forced by a rule, deferring its power rate");
+                            false
+                        }
+                        _ => true,
+                    }
+                });
+            }
+        }
+        Some(profile.apply_with(backend, options))
+    }
+}
+
+#[cfg(windows)]
+pub use self::window::{current_power_source, PowerWatcher};
+
+#[cfg(windows)]
+mod window {
+    use std::mem;
+
+    use log::debug;
+    use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+    use winapi::um::winuser::{
+        PBT_APMPOWERSTATUSCHANGE, PBT_APMRESUMEAUTOMATIC, WM_POWERBROADCAST,
+    };
+
+    use super::PowerSource;
+    use crate::watcher::{MessageWindow, WindowHandler};
+    use crate::DisplayError;
+
+    const CLASS_NAME: &str = "RefreshRatePowerWatcher";
+
+    /// The current power source, from `GetSystemPowerStatus`. `None` when
+    /// the call fails or Windows does not know.
+    pub fn current_power_source() -> Option<PowerSource> {
+        let mut status: SYSTEM_POWER_STATUS = unsafe { mem::zeroed() };
+        if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+            debug!(os_error = unsafe { GetLastError() }; "GetSystemPowerStatus failed");
+            return None;
+        }
+        PowerSource::from_ac_line_status(status.ACLineStatus)
+    }
+
+    struct WatcherWindow {
+        callback: Box<dyn FnMut(PowerSource) + Send>,
+    }
+
+    impl WindowHandler for WatcherWindow {
+        fn message(&mut self, msg: UINT, wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
+            match msg {
+                // The source may also have changed while the system slept.
+                WM_POWERBROADCAST
+                    if wparam == PBT_APMPOWERSTATUSCHANGE || wparam == PBT_APMRESUMEAUTOMATIC =>
+                {
+                    if let Some(source) = current_power_source() {
+                        debug!(source = source.as_str(); "Power status changed");
+                        (self.callback)(source);
+                    }
+                    Some(1)
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Watches for power status changes on a thread of its own and hands the
+    /// power source to a callback there after each one. Repeated reports of
+    /// the same source are possible. Dropping the watcher stops it.
+    pub struct PowerWatcher {
+        _window: MessageWindow,
+    }
+
+    impl PowerWatcher {
+        /// Starts watching; `callback` runs on the watcher thread.
+        pub fn start<F>(callback: F) -> Result<Self, DisplayError>
+        where
+            F: FnMut(PowerSource) + Send + 'static,
+        {
+            let window = MessageWindow::start(CLASS_NAME, move || {
+                Ok(WatcherWindow {
+                    callback: Box::new(callback),
+                })
+            })?;
+            Ok(PowerWatcher { _window: window })
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Named sets of per-monitor modes, e.g. "Gaming" or "Battery".
+//!
+//! Profiles live in the config file as an array of tables:
+//!
+//! ```toml
+//! [[profiles]]
+//! name = "Gaming"
+//!
+//! [[profiles.monitors]]
+//! monitor = "DEL4123"
+//! resolution = "2560x1440"
+//! refresh_rate = 144
+//! ```
+//!
+//! `monitor` names the physical monitor rather than its `\\.\DISPLAYn`
+//! output, see `DisplayDevice::matches_id`. `resolution` and `refresh_rate`
+//! are both optional; what is left out stays as it is.
+
+use std::fmt;
+
+use log::{info, warn};
+use toml::value::{Table, Value};
+
+use crate::backend::DisplayBackend;
+use crate::batch::DisplayBatch;
+use crate::error::{ChangeOutcome, DisplayError};
+use crate::format::{Fields, FormatError};
+use crate::mode::{resolution_modes, DisplayMode};
+use crate::options::ChangeOptions;
+use crate::{
+    get_all_display_devices_with, get_display_modes_with, listed_mode, refresh_rate_mode,
+    DisplayDevice,
+};
+
+/// What a profile wants for one monitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileMonitor {
+    /// Device interface path, PnP device ID or hardware ID of the monitor.
+    pub monitor: String,
+    /// Width and height; `None` keeps the current resolution.
+    pub resolution: Option<(u32, u32)>,
+    /// Refresh rate in hertz; `None` keeps the current rate when the
+    /// resolution offers it, or picks the highest.
+    pub refresh_rate: Option<u32>,
+}
+
+impl ProfileMonitor {
+    /// Whether `device` is the monitor this entry means, see
+    /// `DisplayDevice::matches_id`.
+    pub fn matches(&self, device: &DisplayDevice) -> bool {
+        device.matches_id(&self.monitor)
+    }
+
+    /// The mode `device_name` should switch to, given its `current` one.
+    fn target_mode(
+        &self,
+        backend: &dyn DisplayBackend,
+        device_name: &str,
+        current: &DisplayMode,
+    ) -> Result<DisplayMode, DisplayError> {
+        let Some((width, height)) = self.resolution else {
+            return match self.refresh_rate {
+                Some(rate) if rate != current.frequency => {
+                    refresh_rate_mode(backend, device_name, current, rate)
+                }
+                _ => Ok(*current),
+            };
+        };
+        let frequency = match self.refresh_rate {
+            Some(rate) => rate,
+            None => {
+                let modes = get_display_modes_with(backend, device_name);
+                resolution_modes(&modes, current)
+                    .into_iter()
+                    .find(|mode| (mode.width, mode.height) == (width, height))
+                    .map_or(current.frequency, |mode| mode.frequency)
+            }
+        };
+        let requested = DisplayMode {
+            width,
+            height,
+            frequency,
+            ..*current
+        };
+        if requested == *current {
+            return Ok(requested);
+        }
+        listed_mode(backend, device_name, current, &requested)
+    }
+
+    fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        table.insert("monitor".to_string(), Value::String(self.monitor.clone()));
+        if let Some((width, height)) = self.resolution {
+            table.insert(
+                "resolution".to_string(),
+                Value::String(format!("{}x{}", width, height)),
+            );
+        }
+        if let Some(rate) = self.refresh_rate {
+            table.insert("refresh_rate".to_string(), Value::Integer(rate.into()));
+        }
+        Value::Table(table)
+    }
+
+    fn from_fields(fields: &Fields) -> Result<Self, FormatError> {
+        let resolution = match fields.optional_string("resolution")? {
+            None => None,
+            Some(text) => {
+                let parsed = text
+                    .split_once(['x', 'X'])
+                    .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)));
+                Some(parsed.ok_or_else(|| fields.invalid("resolution", "like \"1920x1080\""))?)
+            }
+        };
+        Ok(ProfileMonitor {
+            monitor: fields.string("monitor")?,
+            resolution,
+            refresh_rate: fields.optional_unsigned("refresh_rate")?,
+        })
+    }
+}
+
+/// A named set of monitor modes, applied together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub monitors: Vec<ProfileMonitor>,
+}
+
+impl Profile {
+    #[cfg(windows)]
+    pub fn apply(&self, options: ChangeOptions) -> ProfileReport {
+        self.apply_with(&crate::Win32Backend, options)
+    }
+
+    /// Switches every connected monitor of the profile in one
+    /// `DisplayBatch`. Monitors that are not connected, or whose mode the
+    /// driver does not list, are reported as failed and left out; if the
+    /// batch itself fails, no monitor changes and each reports the error.
+    /// Each monitor is matched by one entry at most, so entries naming the
+    /// hardware ID of identical monitors take them in enumeration order.
+    pub fn apply_with(
+        &self,
+        backend: &dyn DisplayBackend,
+        options: ChangeOptions,
+    ) -> ProfileReport {
+        let devices = match get_all_display_devices_with(backend) {
+            Ok(devices) => devices,
+            Err(error) => {
+                let results = self
+                    .monitors
+                    .iter()
+                    .map(|entry| MonitorResult::failed(entry, None, error.clone()))
+                    .collect();
+                return ProfileReport::new(self, results);
+            }
+        };
+
+        let mut unmatched: Vec<_> = devices.iter().collect();
+        let mut results = Vec::new();
+        let mut batch = DisplayBatch::new();
+        let mut batched = Vec::new();
+        for entry in &self.monitors {
+            let found = unmatched.iter().position(|device| entry.matches(device));
+            let Some(device) = found.map(|index| unmatched.remove(index)) else {
+                let error = DisplayError::MonitorNotFound {
+                    monitor: entry.monitor.clone(),
+                };
+                results.push(MonitorResult::failed(entry, None, error));
+                continue;
+            };
+            let device_name = device.device_name.clone();
+            let target = backend
+                .current_mode(&device_name)
+                .map_err(|os_error| DisplayError::CurrentModeUnavailable {
+                    device_name: device_name.clone(),
+                    os_error,
+                })
+                .and_then(|current| {
+                    let mode = entry.target_mode(backend, &device_name, &current)?;
+                    Ok((current, mode))
+                });
+            match target {
+                Ok((current, mode)) if mode == current => results.push(MonitorResult {
+                    monitor: entry.monitor.clone(),
+                    device_name: Some(device_name),
+                    result: Ok(ChangeOutcome::Unchanged),
+                }),
+                Ok((_, mode)) => {
+                    batch = batch.mode(&device_name, mode);
+                    batched.push(results.len());
+                    results.push(MonitorResult {
+                        monitor: entry.monitor.clone(),
+                        device_name: Some(device_name),
+                        result: Ok(ChangeOutcome::Applied),
+                    });
+                }
+                Err(error) => results.push(MonitorResult::failed(entry, Some(device_name), error)),
+            }
+        }
+
+        if !batch.is_empty() {
+            let result = batch.apply_with(backend, options);
+            for &index in &batched {
+                results[index].result = result.clone();
+            }
+        }
+        let report = ProfileReport::new(self, results);
+        if report.is_success() {
+            info!(profile = self.name.as_str(); "Applied profile");
+        } else {
+            warn!(profile = self.name.as_str(), failed = report.failures().count(); "Applied profile with failures");
+        }
+        report
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        table.insert("name".to_string(), Value::String(self.name.clone()));
+        table.insert(
+            "monitors".to_string(),
+            Value::Array(self.monitors.iter().map(ProfileMonitor::to_value).collect()),
+        );
+        Value::Table(table)
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Result<Self, FormatError> {
+        Ok(Profile {
+            name: fields.string("name")?,
+            monitors: fields
+                .tables("monitors")?
+                .iter()
+                .map(ProfileMonitor::from_fields)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Reads the `profiles` array of a config document. Profile names must be
+/// unique, ignoring case.
+pub fn profiles_from_value(value: &Value) -> Result<Vec<Profile>, FormatError> {
+    profiles_from_fields(&Fields::root(value)?)
+}
+
+pub(crate) fn profiles_from_fields(fields: &Fields) -> Result<Vec<Profile>, FormatError> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    for (i, profile_fields) in fields.tables("profiles")?.iter().enumerate() {
+        let profile = Profile::from_fields(profile_fields)?;
+        if profiles
+            .iter()
+            .any(|p| p.name.eq_ignore_ascii_case(&profile.name))
+        {
+            return Err(FormatError::InvalidField {
+                path: format!("profiles[{}].name", i),
+                expected: "a unique name",
+            });
+        }
+        profiles.push(profile);
+    }
+    Ok(profiles)
+}
+
+/// Reads the profiles of a TOML config file.
+pub fn profiles_from_toml(text: &str) -> Result<Vec<Profile>, FormatError> {
+    let value = toml::from_str(text).map_err(FormatError::Toml)?;
+    profiles_from_value(&value)
+}
+
+/// How applying a profile went for one of its monitors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorResult {
+    /// The profile's identifier for the monitor.
+    pub monitor: String,
+    /// The adapter output it was found on, if it was found.
+    pub device_name: Option<String>,
+    pub result: Result<ChangeOutcome, DisplayError>,
+}
+
+impl MonitorResult {
+    fn failed(entry: &ProfileMonitor, device_name: Option<String>, error: DisplayError) -> Self {
+        MonitorResult {
+            monitor: entry.monitor.clone(),
+            device_name,
+            result: Err(error),
+        }
+    }
+}
+
+/// Per-monitor results of applying a profile, in profile order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub profile: String,
+    pub monitors: Vec<MonitorResult>,
+}
+
+impl ProfileReport {
+    fn new(profile: &Profile, monitors: Vec<MonitorResult>) -> Self {
+        ProfileReport {
+            profile: profile.name.clone(),
+            monitors,
+        }
+    }
+
+    /// Whether every monitor ended up in its profile mode.
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &MonitorResult> {
+        self.monitors
+            .iter()
+            .filter(|monitor| monitor.result.is_err())
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    /// One line per monitor, e.g. `DEL4123 (\\.\DISPLAY1): applied`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Profile {}:", self.profile)?;
+        for monitor in &self.monitors {
+            write!(f, "\n  {}", monitor.monitor)?;
+            if let Some(device_name) = &monitor.device_name {
+                write!(f, " ({})", device_name)?;
+            }
+            match &monitor.result {
+                Ok(ChangeOutcome::Applied) => write!(f, ": applied")?,
+                Ok(ChangeOutcome::Unchanged) => write!(f, ": unchanged")?,
+                Ok(ChangeOutcome::RestartRequired) => write!(f, ": applied after a restart")?,
+                Ok(ChangeOutcome::Validated) => write!(f, ": would be applied")?,
+                Err(error) => write!(f, ": {}", error)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+//! "Keep these settings?" confirmation for display changes.
+//!
+//! `AutoRevert` snapshots the current mode before a change and restores it
+//! once the countdown runs out, unless the change was confirmed. Time is
+//! passed in by the caller, so the state machine runs the same against
+//! `FakeBackend` in tests as on a `WM_TIMER` in the tray.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::backend::DisplayBackend;
+use crate::error::{check_change_code, ChangeOutcome, DisplayError};
+use crate::mode::{DisplayMode, Orientation, RefreshRate};
+use crate::options::{ChangeOptions, Persistence};
+use crate::{set_display_mode_with, set_display_orientation_with, set_exact_refresh_rate_with};
+
+pub const DEFAULT_REVERT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A change `AutoRevert` can make and take back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestedChange {
+    /// An exact refresh rate, through `set_exact_refresh_rate`.
+    RefreshRate(RefreshRate),
+    /// The resolution and refresh rate of a mode, through `set_display_mode`.
+    Mode(DisplayMode),
+    /// A rotation, through `set_display_orientation`.
+    Orientation(Orientation),
+}
+
+impl RequestedChange {
+    /// The `dmDisplayFrequency` the change switches to, if it sets a rate.
+    pub fn refresh_rate(&self) -> Option<u32> {
+        match self {
+            RequestedChange::RefreshRate(rate) => Some(rate.dm_frequency()),
+            RequestedChange::Mode(mode) => Some(mode.frequency),
+            RequestedChange::Orientation(_) => None,
+        }
+    }
+
+    fn apply_with(
+        self,
+        backend: &dyn DisplayBackend,
+        device_name: &str,
+        options: ChangeOptions,
+    ) -> Result<ChangeOutcome, DisplayError> {
+        match self {
+            RequestedChange::RefreshRate(rate) => {
+                set_exact_refresh_rate_with(backend, device_name, rate, options)
+            }
+            RequestedChange::Mode(mode) => set_display_mode_with(
+                backend,
+                device_name,
+                mode.width,
+                mode.height,
+                mode.frequency,
+                options,
+            ),
+            RequestedChange::Orientation(orientation) => {
+                set_display_orientation_with(backend, device_name, orientation, options)
+            }
+        }
+    }
+}
+
+impl fmt::Display for RequestedChange {
+    /// `59.94 Hz`, `1920x1080 @ 144 Hz`, `Portrait`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestedChange::RefreshRate(rate) => rate.fmt(f),
+            RequestedChange::Mode(mode) => mode.fmt(f),
+            RequestedChange::Orientation(orientation) => orientation.fmt(f),
+        }
+    }
+}
+
+/// A change awaiting confirmation and what to restore without it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingChange {
+    pub device_name: String,
+    pub requested: RequestedChange,
+    /// The `DEVMODEW` settings before the change.
+    pub previous_mode: DisplayMode,
+    /// The exact rate before the change, if the CCD API reported one.
+    pub previous_rate: Option<RefreshRate>,
+    pub persistence: Persistence,
+    pub deadline: Instant,
+}
+
+/// What a `tick` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertTick {
+    /// Nothing is waiting for confirmation.
+    Idle,
+    /// The countdown is still running.
+    Waiting { remaining: Duration },
+    /// The countdown ran out and the previous mode was restored, or the
+    /// restore failed.
+    Reverted {
+        change: PendingChange,
+        result: Result<ChangeOutcome, DisplayError>,
+    },
+}
+
+/// Apply-with-confirmation state machine. At most one change is pending;
+/// starting another restores the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoRevert {
+    timeout: Duration,
+    pending: Option<PendingChange>,
+}
+
+impl AutoRevert {
+    pub const fn new(timeout: Duration) -> Self {
+        AutoRevert {
+            timeout,
+            pending: None,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Changes the countdown length for later changes. A zero timeout keeps
+    /// changes without asking.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn pending(&self) -> Option<&PendingChange> {
+        self.pending.as_ref()
+    }
+
+    /// Time left before the pending change is reverted.
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.pending
+            .as_ref()
+            .map(|change| change.deadline.saturating_duration_since(now))
+    }
+
+    /// Snapshots `device_name`, switches it to `rate` and starts the
+    /// countdown. Outcomes other than `Applied` leave nothing to confirm.
+    pub fn apply_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+        device_name: &str,
+        rate: RefreshRate,
+        options: ChangeOptions,
+        now: Instant,
+    ) -> Result<ChangeOutcome, DisplayError> {
+        let requested = RequestedChange::RefreshRate(rate);
+        self.request_with(backend, device_name, requested, options, now)
+    }
+
+    /// Like `apply_with`, for any `RequestedChange`.
+    pub fn request_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+        device_name: &str,
+        requested: RequestedChange,
+        options: ChangeOptions,
+        now: Instant,
+    ) -> Result<ChangeOutcome, DisplayError> {
+        if let Some(change) = self.pending.take() {
+            let _ = restore_with(backend, &change);
+        }
+
+        // Without a snapshot there is nothing to go back to, so do not change anything.
+        let previous_mode = backend.current_mode(device_name).map_err(|os_error| {
+            DisplayError::CurrentModeUnavailable {
+                device_name: device_name.to_string(),
+                os_error,
+            }
+        })?;
+        let previous_rate = backend.current_refresh_rate(device_name).ok();
+
+        let outcome = requested.apply_with(backend, device_name, options)?;
+        if outcome == ChangeOutcome::Applied && !self.timeout.is_zero() {
+            self.pending = Some(PendingChange {
+                device_name: device_name.to_string(),
+                requested,
+                previous_mode,
+                previous_rate,
+                persistence: options.persistence,
+                deadline: now + self.timeout,
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// Keeps the pending change.
+    pub fn confirm(&mut self) -> Option<PendingChange> {
+        let change = self.pending.take()?;
+        info!(device = change.device_name.as_str(), change = change.requested.to_string(); "Kept display change");
+        Some(change)
+    }
+
+    /// Restores the pending change's snapshot right away.
+    pub fn revert_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+    ) -> Option<Result<ChangeOutcome, DisplayError>> {
+        let change = self.pending.take()?;
+        Some(restore_with(backend, &change))
+    }
+
+    /// Advances the countdown to `now`, reverting once it has run out.
+    pub fn tick_with(&mut self, backend: &dyn DisplayBackend, now: Instant) -> RevertTick {
+        let Some(change) = self.pending.take_if(|change| change.deadline <= now) else {
+            return match self.remaining(now) {
+                Some(remaining) => RevertTick::Waiting { remaining },
+                None => RevertTick::Idle,
+            };
+        };
+        let result = restore_with(backend, &change);
+        RevertTick::Reverted { change, result }
+    }
+}
+
+impl Default for AutoRevert {
+    fn default() -> Self {
+        AutoRevert::new(DEFAULT_REVERT_TIMEOUT)
+    }
+}
+
+/// Text for the confirmation prompt.
+pub fn prompt_text(change: &PendingChange, remaining: Duration) -> String {
+    format!(
+        "Keep {} on {}?\nReverting in {} seconds.",
+        change.requested,
+        change.device_name,
+        remaining.as_secs_f64().ceil() as u64
+    )
+}
+
+/// Puts `change.device_name` back into its snapshotted mode, with the same
+/// persistence the change used.
+fn restore_with(
+    backend: &dyn DisplayBackend,
+    change: &PendingChange,
+) -> Result<ChangeOutcome, DisplayError> {
+    let device_name = change.device_name.as_str();
+    let code = backend.apply_mode(
+        device_name,
+        &change.previous_mode,
+        change.persistence.cds_flags(),
+    );
+    let outcome = check_change_code(device_name, code).inspect_err(|error| {
+        warn!(device = device_name, mode = change.previous_mode.to_string(); "Could not restore the previous mode: {}", error);
+    })?;
+
+    // DEVMODEW cannot tell 59.94 Hz from 59 Hz; put the exact rate back too.
+    if let Some(rate) = change.previous_rate {
+        if backend.current_refresh_rate(device_name).ok() != Some(rate) {
+            return set_exact_refresh_rate_with(
+                backend,
+                device_name,
+                rate,
+                ChangeOptions::with_persistence(change.persistence),
+            );
+        }
+    }
+
+    info!(device = device_name, mode = change.previous_mode.to_string(); "Restored previous mode");
+    Ok(outcome)
+}
@@ -0,0 +1,467 @@
+//! Refresh rates forced while an application is in the foreground, e.g.
+//! 144 Hz while a game runs and back to 60 Hz afterwards.
+//!
+//! Rules live in the config file:
+//!
+//! ```toml
+//! [[apps]]
+//! app = "eldenring.exe"
+//! monitor = "DEL4123"
+//! refresh_rate = 144
+//!
+//! [[apps]]
+//! app = 'C:\Program Files\Adobe\**\*.exe'
+//! refresh_rate = 60
+//! ```
+//!
+//! `app` is an executable name, or a path when it contains a `\` or `/`. Both
+//! are matched case-insensitively and may use `?` and `*`, which stop at
+//! path separators, and `**`, which does not. `monitor` is matched like a
+//! profile's, see `DisplayDevice::matches_id`; without it the rule applies
+//! to the primary monitor.
+//!
+//! `forced_rates` picks the rates for the foreground application and
+//! `RateOverrides` switches to them, remembering each monitor's previous
+//! rate to put back once no rule wants it any more. On Windows,
+//! `ForegroundWatcher` reports the executable of every new foreground
+//! window.
+
+use log::{info, warn};
+use toml::value::{Table, Value};
+
+use crate::backend::DisplayBackend;
+use crate::error::{ChangeOutcome, DisplayError};
+use crate::format::{Fields, FormatError};
+use crate::options::ChangeOptions;
+use crate::{set_display_refresh_rate_with, DisplayDevice};
+
+/// A refresh rate to force while an application is in the foreground.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppRule {
+    /// Executable name or path, optionally with wildcards.
+    pub app: String,
+    /// Device interface path, PnP device ID or hardware ID of the monitor;
+    /// `None` means the primary monitor.
+    pub monitor: Option<String>,
+    /// Rate in hertz.
+    pub refresh_rate: u32,
+}
+
+impl AppRule {
+    /// Whether the executable at `path` is the application this rule means.
+    pub fn matches_app(&self, path: &str) -> bool {
+        app_matches(&self.app, path)
+    }
+
+    /// The connected monitor this rule switches.
+    fn device<'a>(&self, devices: &'a [DisplayDevice]) -> Option<&'a DisplayDevice> {
+        match &self.monitor {
+            Some(monitor) => devices.iter().find(|device| device.matches_id(monitor)),
+            None => devices.iter().find(|device| device.is_primary),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut table = Table::new();
+        table.insert("app".to_string(), Value::String(self.app.clone()));
+        if let Some(monitor) = &self.monitor {
+            table.insert("monitor".to_string(), Value::String(monitor.clone()));
+        }
+        table.insert(
+            "refresh_rate".to_string(),
+            Value::Integer(self.refresh_rate.into()),
+        );
+        Value::Table(table)
+    }
+
+    pub(crate) fn from_fields(fields: &Fields) -> Result<Self, FormatError> {
+        Ok(AppRule {
+            app: fields.string("app")?,
+            monitor: fields.optional_string("monitor")?,
+            refresh_rate: fields.unsigned("refresh_rate")?,
+        })
+    }
+}
+
+pub(crate) fn app_rules_from_fields(fields: &Fields) -> Result<Vec<AppRule>, FormatError> {
+    fields
+        .tables("apps")?
+        .iter()
+        .map(AppRule::from_fields)
+        .collect()
+}
+
+/// Whether the executable at `path` matches `pattern`, an executable name
+/// or path with optional wildcards as described above.
+pub(crate) fn app_matches(pattern: &str, path: &str) -> bool {
+    let pattern = normalize(pattern);
+    let path = normalize(path);
+    if pattern.contains(&'\\') {
+        return glob(&pattern, &path);
+    }
+    let start = path.iter().rposition(|&c| c == '\\').map_or(0, |i| i + 1);
+    glob(&pattern, &path[start..])
+}
+
+/// Lower case with `\` as the only separator.
+fn normalize(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| if c == '/' { '\\' } else { c })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether `text` matches the wildcard `pattern`, both normalized. On a
+/// mismatch only the last `*`, or failing that the last `**`, takes one more
+/// character, so the time stays linear in the number of wildcards.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    // Pattern index after the wildcard and text index it has matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    let mut double_star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    loop {
+        match (pattern.get(p), text.get(t)) {
+            (Some('*'), _) if pattern.get(p + 1) == Some(&'*') => {
+                p += 2;
+                double_star = Some((p, t));
+                star = None;
+                continue;
+            }
+            (Some('*'), _) => {
+                p += 1;
+                star = Some((p, t));
+                continue;
+            }
+            (Some('?'), Some(&c)) if c != '\\' => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            (Some(&p_char), Some(&c)) if p_char != '?' && p_char == c => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            (None, None) => return true,
+            _ => {}
+        }
+        // A `*` stops at a separator; a `**` before it can then move on.
+        match (star, double_star) {
+            (Some((star_p, star_t)), _) if star_t < text.len() && text[star_t] != '\\' => {
+                star = Some((star_p, star_t + 1));
+                (p, t) = (star_p, star_t + 1);
+            }
+            (_, Some((double_p, double_t))) if double_t < text.len() => {
+                double_star = Some((double_p, double_t + 1));
+                star = None;
+                (p, t) = (double_p, double_t + 1);
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// A refresh rate some rule wants a monitor to run at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForcedRate {
+    /// Adapter output of the monitor, e.g. `\\.\DISPLAY1`.
+    pub device_name: String,
+    pub refresh_rate: u32,
+}
+
+/// The rates `rules` want while the executable at `path` is in the
+/// foreground. When several rules name the same monitor, the first wins.
+pub fn forced_rates(rules: &[AppRule], path: &str, devices: &[DisplayDevice]) -> Vec<ForcedRate> {
+    let mut forced: Vec<ForcedRate> = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.matches_app(path)) {
+        let Some(device) = rule.device(devices) else {
+            continue;
+        };
+        if forced.iter().any(|f| f.device_name == device.device_name) {
+            continue;
+        }
+        forced.push(ForcedRate {
+            device_name: device.device_name.clone(),
+            refresh_rate: rule.refresh_rate,
+        });
+    }
+    forced
+}
+
+/// A monitor switched away from its own rate by a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateOverride {
+    pub device_name: String,
+    /// The rate the rule forced.
+    pub refresh_rate: u32,
+    /// The rate to put back afterwards.
+    pub previous: u32,
+}
+
+/// How forcing or restoring one monitor's rate went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverrideResult {
+    pub device_name: String,
+    /// The rate switched to.
+    pub refresh_rate: u32,
+    pub result: Result<ChangeOutcome, DisplayError>,
+}
+
+/// The rates rules currently force, and what they replaced.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateOverrides {
+    active: Vec<RateOverride>,
+}
+
+impl RateOverrides {
+    pub const fn new() -> Self {
+        RateOverrides { active: Vec::new() }
+    }
+
+    pub fn active(&self) -> &[RateOverride] {
+        &self.active
+    }
+
+    /// Makes `forced` the set of forced rates: monitors no rule wants any
+    /// more go back to their previous rate, newly wanted ones are switched
+    /// after noting their current rate. A monitor moving from one forced
+    /// rate to another keeps its original previous rate. Returns a result
+    /// per rate change attempted.
+    pub fn sync_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+        forced: &[ForcedRate],
+        options: ChangeOptions,
+    ) -> Vec<OverrideResult> {
+        let mut results = Vec::new();
+        let (kept, released): (Vec<_>, Vec<_>) = self
+            .active
+            .drain(..)
+            .partition(|active| forced.iter().any(|f| f.device_name == active.device_name));
+        self.active = kept;
+        for active in released {
+            results.push(restore(backend, &active, options));
+        }
+
+        for wanted in forced {
+            let index = self
+                .active
+                .iter()
+                .position(|active| active.device_name == wanted.device_name);
+            let previous = match index {
+                Some(i) if self.active[i].refresh_rate == wanted.refresh_rate => continue,
+                Some(i) => self.active[i].previous,
+                None => match backend.current_mode(&wanted.device_name) {
+                    Ok(mode) => mode.frequency,
+                    Err(os_error) => {
+                        results.push(OverrideResult {
+                            device_name: wanted.device_name.clone(),
+                            refresh_rate: wanted.refresh_rate,
+                            result: Err(DisplayError::CurrentModeUnavailable {
+                                device_name: wanted.device_name.clone(),
+                                os_error,
+                            }),
+                        });
+                        continue;
+                    }
+                },
+            };
+            let result = set_display_refresh_rate_with(
+                backend,
+                &wanted.device_name,
+                wanted.refresh_rate,
+                options,
+            );
+            match &result {
+                Ok(_) => {
+                    info!(device = wanted.device_name.as_str(), rate = wanted.refresh_rate, previous = previous; "Forced refresh rate");
+                    let active = RateOverride {
+                        device_name: wanted.device_name.clone(),
+                        refresh_rate: wanted.refresh_rate,
+                        previous,
+                    };
+                    match index {
+                        Some(i) => self.active[i] = active,
+                        None => self.active.push(active),
+                    }
+                }
+                // The monitor stays at whatever rate it has now.
+                Err(error) => {
+                    warn!(device = wanted.device_name.as_str(), rate = wanted.refresh_rate; "Could not force refresh rate: {}", error)
+                }
+            }
+            results.push(OverrideResult {
+                device_name: wanted.device_name.clone(),
+                refresh_rate: wanted.refresh_rate,
+                result,
+            });
+        }
+        results
+    }
+
+    /// Makes `rate` the rate `device_name` goes back to once no rule forces
+    /// it any more, e.g. when the power rules switch it meanwhile. Returns
+    /// `false`, changing nothing, when no rule forces `device_name`.
+    pub fn set_previous(&mut self, device_name: &str, rate: u32) -> bool {
+        match self
+            .active
+            .iter_mut()
+            .find(|active| active.device_name == device_name)
+        {
+            Some(active) => {
+                active.previous = rate;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts every monitor back to its previous rate, e.g. on exit.
+    pub fn restore_all_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+        options: ChangeOptions,
+    ) -> Vec<OverrideResult> {
+        self.sync_with(backend, &[], options)
+    }
+}
+
+fn restore(
+    backend: &dyn DisplayBackend,
+    active: &RateOverride,
+    options: ChangeOptions,
+) -> OverrideResult {
+    let result =
+        set_display_refresh_rate_with(backend, &active.device_name, active.previous, options);
+    match &result {
+        Ok(_) => {
+            info!(device = active.device_name.as_str(), rate = active.previous; "Restored refresh rate")
+        }
+        Err(error) => {
+            warn!(device = active.device_name.as_str(), rate = active.previous; "Could not restore refresh rate: {}", error)
+        }
+    }
+    OverrideResult {
+        device_name: active.device_name.clone(),
+        refresh_rate: active.previous,
+        result,
+    }
+}
+
+#[cfg(windows)]
+pub(crate) use self::hook::executable_path;
+#[cfg(windows)]
+pub use self::hook::ForegroundWatcher;
+
+#[cfg(windows)]
+mod hook {
+    use std::cell::RefCell;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, FALSE};
+    use winapi::shared::ntdef::LONG;
+    use winapi::shared::windef::{HWINEVENTHOOK, HWND};
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::{GetCurrentThreadId, OpenProcess};
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    use winapi::um::winuser::{
+        GetWindowThreadProcessId, PostThreadMessageW, SetWinEventHook, UnhookWinEvent,
+        EVENT_SYSTEM_FOREGROUND, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_QUIT,
+    };
+
+    use crate::watcher::{run_message_loop, WatcherThread};
+    use crate::DisplayError;
+
+    type Callback = Box<dyn FnMut(Option<String>) + Send>;
+
+    thread_local! {
+        /// The callback of the watcher running on this thread. Win event
+        /// procedures get no user data, so it is looked up here.
+        static CALLBACK: RefCell<Option<Callback>> = RefCell::new(None);
+    }
+
+    /// Watches for foreground window changes on a thread of its own and
+    /// hands the executable path of each new foreground window to a callback
+    /// there; `None` when the process could not be queried. Windows of this
+    /// process, such as the tray menu, are ignored. Dropping the watcher
+    /// stops it.
+    pub struct ForegroundWatcher {
+        _thread: WatcherThread,
+    }
+
+    impl ForegroundWatcher {
+        /// Starts watching; `callback` runs on the watcher thread.
+        pub fn start<F>(callback: F) -> Result<Self, DisplayError>
+        where
+            F: FnMut(Option<String>) + Send + 'static,
+        {
+            let callback: Callback = Box::new(callback);
+            let thread = WatcherThread::start(
+                move |ready| {
+                    // Out-of-context hooks call back on this thread's message loop.
+                    let hook = unsafe {
+                        SetWinEventHook(
+                            EVENT_SYSTEM_FOREGROUND,
+                            EVENT_SYSTEM_FOREGROUND,
+                            ptr::null_mut(),
+                            Some(foreground_proc),
+                            0,
+                            0,
+                            WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+                        )
+                    };
+                    if hook.is_null() {
+                        let os_error = unsafe { GetLastError() };
+                        let _ = ready.send(Err(DisplayError::WatcherUnavailable { os_error }));
+                        return;
+                    }
+                    CALLBACK.with(|cell| *cell.borrow_mut() = Some(callback));
+                    let _ = ready.send(Ok(unsafe { GetCurrentThreadId() }));
+                    run_message_loop();
+                    unsafe { UnhookWinEvent(hook) };
+                },
+                |thread_id| unsafe {
+                    PostThreadMessageW(thread_id, WM_QUIT, 0, 0);
+                },
+            )?;
+            Ok(ForegroundWatcher { _thread: thread })
+        }
+    }
+
+    unsafe extern "system" fn foreground_proc(
+        _hook: HWINEVENTHOOK,
+        _event: DWORD,
+        hwnd: HWND,
+        _object: LONG,
+        _child: LONG,
+        _thread: DWORD,
+        _time: DWORD,
+    ) {
+        let path = executable_path(hwnd);
+        CALLBACK.with(|cell| {
+            if let Some(callback) = cell.borrow_mut().as_mut() {
+                callback(path);
+            }
+        });
+    }
+
+    /// The image path of the process that owns `hwnd`.
+    pub(crate) fn executable_path(hwnd: HWND) -> Option<String> {
+        let mut process_id: DWORD = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, &mut process_id) };
+        if process_id == 0 {
+            return None;
+        }
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, process_id) };
+        if process.is_null() {
+            return None;
+        }
+        let mut buffer = [0u16; 1024];
+        let mut len = buffer.len() as DWORD;
+        let ok = unsafe { QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut len) };
+        unsafe { CloseHandle(process) };
+        (ok != 0).then(|| String::from_utf16_lossy(&buffer[..len as usize]))
+    }
+}
@@ -0,0 +1,121 @@
+//! Machine-readable output: the records `refresh-rate --json` writes, built
+//! on the `serde` serialization the display types derive.
+//!
+//! With `--json` every command writes NDJSON, one JSON object per line. Each
+//! object is one record with two fixed fields:
+//!
+//! - `schema`: the version of this schema, `SCHEMA_VERSION`.
+//! - `type`: which record it is, see below.
+//!
+//! Within a schema version fields are only ever added, so readers should
+//! ignore fields they do not know. Removing, renaming or changing the meaning
+//! of a field bumps the version. Optional fields are left out rather than
+//! `null`.
+//!
+//! Records:
+//!
+//! - `monitor` (`list`): `index`, the number `<monitor>` accepts; `device`;
+//!   `mode`, `rate` and `position`, if they could be read.
+//! - `refresh_rate` (`get`, `modes`): `device_name`; `rate`; `current`,
+//!   whether the monitor runs at it.
+//! - `primary` (`primary`): `device_name`.
+//! - `change` (`set`): `device_name`; `rate`; `outcome`, one of `applied`,
+//!   `unchanged`, `restart_required` or `validated` (dry run).
+//! - `error` (any command, written to stderr): `exit_code`; `message`.
+//!
+//! Nested values:
+//!
+//! - device: `device_name` (`\\.\DISPLAY1`), `display_name`,
+//!   `monitor_device_name`, `device_id`, `interface_path`, `hardware_id`
+//!   (optional, `DEL4123`), `adapter_name`, `primary`, `attached`.
+//! - mode: `width`, `height`, `bits_per_pel`, `frequency` (whole hertz as
+//!   `DEVMODEW` reports it), `interlaced`, `scaling` (`default`, `stretch`
+//!   or `center`), `orientation` (0, 90, 180 or 270).
+//! - rate: `numerator` and `denominator` of the exact rate, and `hz`, the
+//!   rate as a number rounded to the millihertz (59.94).
+//! - position: `x`, `y` of the monitor's top-left corner on the virtual
+//!   desktop; the primary monitor is at 0, 0.
+
+use serde::{Serialize, Serializer};
+
+use crate::error::ChangeOutcome;
+use crate::mode::{DisplayMode, Position, RefreshRate};
+use crate::DisplayDevice;
+
+/// Version of the record layout described above.
+pub const SCHEMA_VERSION: i64 = 1;
+
+/// One line of NDJSON output; the variant is the record's `type`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record<'a> {
+    Monitor {
+        index: usize,
+        #[serde(serialize_with = "serialize_device")]
+        device: &'a DisplayDevice,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        mode: Option<DisplayMode>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rate: Option<RefreshRate>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        position: Option<Position>,
+    },
+    RefreshRate {
+        device_name: &'a str,
+        rate: RefreshRate,
+        current: bool,
+    },
+    Primary {
+        device_name: &'a str,
+    },
+    Change {
+        device_name: &'a str,
+        rate: RefreshRate,
+        outcome: ChangeOutcome,
+    },
+    Error {
+        exit_code: i32,
+        message: String,
+    },
+}
+
+impl Record<'_> {
+    /// The record as a single line of JSON, without the line break.
+    pub fn to_json(&self) -> String {
+        let line = Line {
+            schema: SCHEMA_VERSION,
+            record: self,
+        };
+        // Records hold finite floats and string keys only, which JSON can
+        // represent.
+        serde_json::to_string(&line).expect("record is valid JSON")
+    }
+}
+
+/// A record with its schema version in front.
+#[derive(Serialize)]
+struct Line<'a> {
+    schema: i64,
+    #[serde(flatten)]
+    record: &'a Record<'a>,
+}
+
+/// The device fields plus `hardware_id`, which is derived from `device_id`.
+#[derive(Serialize)]
+struct Device<'a> {
+    #[serde(flatten)]
+    device: &'a DisplayDevice,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hardware_id: Option<&'a str>,
+}
+
+fn serialize_device<S: Serializer>(
+    device: &&DisplayDevice,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    Device {
+        device,
+        hardware_id: device.hardware_id(),
+    }
+    .serialize(serializer)
+}
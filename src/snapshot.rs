@@ -0,0 +1,313 @@
+//! Saving and restoring the whole display layout.
+//!
+//! A `DisplaySnapshot` records every adapter output's mode, exact refresh
+//! rate, desktop position and primary flag. It is written as TOML or JSON:
+//!
+//! ```toml
+//! version = 1
+//!
+//! [[monitors]]
+//! device_name = '\\.\DISPLAY1'
+//! display_name = "DELL U2720Q"
+//! interface_path = '\\?\DISPLAY#DEL4123#...'
+//! primary = true
+//!
+//! [monitors.mode]
+//! width = 3840
+//! height = 2160
+//! bits_per_pel = 32
+//! frequency = 59
+//! interlaced = false
+//! scaling = "default"
+//! orientation = 0
+//!
+//! [monitors.position]
+//! x = 0
+//! y = 0
+//!
+//! [monitors.rate]
+//! numerator = 60000
+//! denominator = 1001
+//! ```
+//!
+//! `rate` is left out when the CCD API did not report one.
+
+use log::info;
+use toml::value::{Table, Value};
+
+use crate::backend::DisplayBackend;
+use crate::batch::DisplayBatch;
+use crate::error::{ChangeOutcome, DisplayError};
+use crate::format::{Fields, FormatError};
+use crate::mode::{DisplayMode, Orientation, Position, RefreshRate, Scaling};
+use crate::options::ChangeOptions;
+use crate::{get_all_display_devices_with, set_exact_refresh_rate_with};
+
+/// The snapshot format version this crate writes and reads.
+pub const SNAPSHOT_VERSION: i64 = 1;
+
+/// One adapter output as it was when the snapshot was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorSnapshot {
+    pub device_name: String,
+    pub display_name: String,
+    /// Used to find the monitor again when its `\\.\DISPLAYn` name changed.
+    pub interface_path: String,
+    pub mode: DisplayMode,
+    /// The exact rate, if the CCD API reported one.
+    pub rate: Option<RefreshRate>,
+    pub position: Position,
+    pub primary: bool,
+}
+
+/// Every active adapter output at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisplaySnapshot {
+    pub monitors: Vec<MonitorSnapshot>,
+}
+
+impl DisplaySnapshot {
+    /// Records the current layout. Monitors mirroring the same adapter output
+    /// are recorded once.
+    pub fn capture_with(backend: &dyn DisplayBackend) -> Result<Self, DisplayError> {
+        let mut monitors: Vec<MonitorSnapshot> = Vec::new();
+        for device in get_all_display_devices_with(backend)? {
+            if monitors.iter().any(|m| m.device_name == device.device_name) {
+                continue;
+            }
+            let unavailable = |os_error| DisplayError::CurrentModeUnavailable {
+                device_name: device.device_name.clone(),
+                os_error,
+            };
+            let mode = backend
+                .current_mode(&device.device_name)
+                .map_err(unavailable)?;
+            let position = backend
+                .current_position(&device.device_name)
+                .map_err(unavailable)?;
+            monitors.push(MonitorSnapshot {
+                rate: backend.current_refresh_rate(&device.device_name).ok(),
+                device_name: device.device_name,
+                display_name: device.display_name,
+                interface_path: device.interface_path,
+                mode,
+                position,
+                primary: device.is_primary,
+            });
+        }
+        Ok(DisplaySnapshot { monitors })
+    }
+
+    #[cfg(windows)]
+    pub fn capture() -> Result<Self, DisplayError> {
+        Self::capture_with(&crate::Win32Backend)
+    }
+
+    /// Puts every recorded monitor back into its mode, position and primary
+    /// flag in one `DisplayBatch`, then restores exact rates `DEVMODEW`
+    /// cannot express. Monitors are found by interface path, or by device
+    /// name when none was recorded; a recorded monitor that is not connected
+    /// fails the restore before anything changes.
+    pub fn restore_with(
+        &self,
+        backend: &dyn DisplayBackend,
+        options: ChangeOptions,
+    ) -> Result<ChangeOutcome, DisplayError> {
+        let devices = get_all_display_devices_with(backend)?;
+        let mut batch = DisplayBatch::new();
+        let mut targets = Vec::new();
+        for monitor in &self.monitors {
+            let device = devices
+                .iter()
+                .find(|device| {
+                    if monitor.interface_path.is_empty() {
+                        device.device_name == monitor.device_name
+                    } else {
+                        device.interface_path == monitor.interface_path
+                    }
+                })
+                .ok_or_else(|| DisplayError::MonitorNotFound {
+                    monitor: monitor.display_name.clone(),
+                })?;
+            batch = batch.layout(
+                &device.device_name,
+                monitor.mode,
+                monitor.position,
+                monitor.primary,
+            );
+            targets.push((device.device_name.as_str(), monitor.rate));
+        }
+
+        let mut outcome = batch.apply_with(backend, options)?;
+        for (device_name, rate) in targets {
+            let Some(rate) = rate else { continue };
+            if backend.current_refresh_rate(device_name).ok() == Some(rate) {
+                continue;
+            }
+            match set_exact_refresh_rate_with(backend, device_name, rate, options)? {
+                ChangeOutcome::Unchanged => {}
+                rate_outcome if outcome == ChangeOutcome::Unchanged => outcome = rate_outcome,
+                ChangeOutcome::RestartRequired => outcome = ChangeOutcome::RestartRequired,
+                _ => {}
+            }
+        }
+        if outcome == ChangeOutcome::Applied {
+            info!(monitors = self.monitors.len(); "Restored display snapshot");
+        }
+        Ok(outcome)
+    }
+
+    #[cfg(windows)]
+    pub fn restore(&self, options: ChangeOptions) -> Result<ChangeOutcome, DisplayError> {
+        self.restore_with(&crate::Win32Backend, options)
+    }
+
+    pub fn to_value(&self) -> Value {
+        let monitors = self.monitors.iter().map(monitor_to_value).collect();
+        let mut table = Table::new();
+        table.insert("version".to_string(), Value::Integer(SNAPSHOT_VERSION));
+        table.insert("monitors".to_string(), Value::Array(monitors));
+        Value::Table(table)
+    }
+
+    pub fn from_value(value: &Value) -> Result<Self, FormatError> {
+        let fields = Fields::root(value)?;
+        let version = fields.integer("version")?;
+        if version != SNAPSHOT_VERSION {
+            return Err(FormatError::UnsupportedVersion(version));
+        }
+        let monitors = fields
+            .tables("monitors")?
+            .iter()
+            .map(monitor_from_fields)
+            .collect::<Result<_, _>>()?;
+        Ok(DisplaySnapshot { monitors })
+    }
+
+    pub fn to_toml(&self) -> String {
+        // Tables of strings, integers and booleans always serialize.
+        toml::to_string(&self.to_value()).expect("snapshot is valid TOML")
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, FormatError> {
+        let value = toml::from_str(text).map_err(FormatError::Toml)?;
+        Self::from_value(&value)
+    }
+
+    pub fn to_json(&self) -> String {
+        // Snapshots hold no floats, so nothing can be NaN or infinite.
+        let mut text =
+            serde_json::to_string_pretty(&self.to_value()).expect("snapshot is valid JSON");
+        text.push('\n');
+        text
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, FormatError> {
+        let value = serde_json::from_str(text).map_err(|error| FormatError::Json {
+            message: error.to_string(),
+            line: error.line(),
+            column: error.column(),
+        })?;
+        Self::from_value(&value)
+    }
+}
+
+fn monitor_to_value(monitor: &MonitorSnapshot) -> Value {
+    let mut table = Table::new();
+    let mut insert = |key: &str, value: Value| table.insert(key.to_string(), value);
+    insert("device_name", Value::String(monitor.device_name.clone()));
+    insert("display_name", Value::String(monitor.display_name.clone()));
+    insert(
+        "interface_path",
+        Value::String(monitor.interface_path.clone()),
+    );
+    insert("primary", Value::Boolean(monitor.primary));
+    insert("mode", mode_to_value(&monitor.mode));
+    insert("position", position_to_value(monitor.position));
+    if let Some(rate) = monitor.rate {
+        insert("rate", rate_to_value(rate));
+    }
+    Value::Table(table)
+}
+
+fn monitor_from_fields(fields: &Fields) -> Result<MonitorSnapshot, FormatError> {
+    Ok(MonitorSnapshot {
+        device_name: fields.string("device_name")?,
+        display_name: fields.string("display_name")?,
+        interface_path: fields.string("interface_path")?,
+        mode: mode_from_fields(&fields.table("mode")?)?,
+        rate: fields
+            .optional_table("rate")?
+            .map(|rate| rate_from_fields(&rate))
+            .transpose()?,
+        position: position_from_fields(&fields.table("position")?)?,
+        primary: fields.boolean("primary")?,
+    })
+}
+
+pub(crate) fn mode_to_value(mode: &DisplayMode) -> Value {
+    let mut table = Table::new();
+    let mut insert = |key: &str, value: Value| table.insert(key.to_string(), value);
+    insert("width", Value::Integer(mode.width.into()));
+    insert("height", Value::Integer(mode.height.into()));
+    insert("bits_per_pel", Value::Integer(mode.bits_per_pel.into()));
+    insert("frequency", Value::Integer(mode.frequency.into()));
+    insert("interlaced", Value::Boolean(mode.interlaced));
+    insert("scaling", Value::String(mode.scaling.as_str().to_string()));
+    insert(
+        "orientation",
+        Value::Integer(mode.orientation.degrees().into()),
+    );
+    Value::Table(table)
+}
+
+pub(crate) fn mode_from_fields(fields: &Fields) -> Result<DisplayMode, FormatError> {
+    let scaling = fields.string("scaling")?;
+    let scaling = Scaling::ALL
+        .into_iter()
+        .find(|s| s.as_str() == scaling)
+        .ok_or_else(|| fields.invalid("scaling", "default, stretch or center"))?;
+    let orientation = Orientation::from_degrees(fields.unsigned("orientation")?)
+        .ok_or_else(|| fields.invalid("orientation", "0, 90, 180 or 270"))?;
+    Ok(DisplayMode {
+        width: fields.unsigned("width")?,
+        height: fields.unsigned("height")?,
+        bits_per_pel: fields.unsigned("bits_per_pel")?,
+        frequency: fields.unsigned("frequency")?,
+        interlaced: fields.boolean("interlaced")?,
+        scaling,
+        orientation,
+    })
+}
+
+pub(crate) fn rate_to_value(rate: RefreshRate) -> Value {
+    let mut table = Table::new();
+    table.insert(
+        "numerator".to_string(),
+        Value::Integer(rate.numerator.into()),
+    );
+    table.insert(
+        "denominator".to_string(),
+        Value::Integer(rate.denominator.into()),
+    );
+    Value::Table(table)
+}
+
+pub(crate) fn rate_from_fields(fields: &Fields) -> Result<RefreshRate, FormatError> {
+    let denominator = fields.unsigned("denominator")?;
+    if denominator == 0 {
+        return Err(fields.invalid("denominator", "a positive integer"));
+    }
+    Ok(RefreshRate::new(fields.unsigned("numerator")?, denominator))
+}
+
+pub(crate) fn position_to_value(position: Position) -> Value {
+    let mut table = Table::new();
+    table.insert("x".to_string(), Value::Integer(position.x.into()));
+    table.insert("y".to_string(), Value::Integer(position.y.into()));
+    Value::Table(table)
+}
+
+pub(crate) fn position_from_fields(fields: &Fields) -> Result<Position, FormatError> {
+    Ok(Position::new(fields.signed("x")?, fields.signed("y")?))
+}
@@ -0,0 +1,302 @@
+//! Platform-independent model of the tray menu: what the menu shows and what
+//! each command ID means. `main.rs` only turns it into Win32 menus.
+
+use crate::backend::DisplayBackend;
+use crate::edid::RefreshCapabilities;
+use crate::mode::{resolution_modes, DisplayMode, Orientation, RefreshRate};
+use crate::options::Persistence;
+use crate::profile::Profile;
+use crate::{
+    get_all_display_devices_with, get_display_modes_with, get_refresh_rate_report_with,
+    validate_mode_with, DisplayDevice, DisplayError, RefreshRateSupport,
+};
+
+pub const MENU_EXIT_ID: u32 = 1700;
+pub const MENU_PROFILE_BASE_ID: u32 = 1800;
+pub const MENU_PERSISTENCE_BASE_ID: u32 = 1900;
+pub const MENU_REFRESH_RATE_BASE_ID: u32 = 2000; // Offset to avoid clashes
+pub const MENU_RESOLUTION_BASE_ID: u32 = 10_000;
+pub const MENU_ORIENTATION_BASE_ID: u32 = 20_000;
+/// How many rates, resolutions or orientations a monitor submenu lists at
+/// most. Each monitor gets this many command IDs per kind, so a longer list
+/// would run into the next monitor's.
+pub const MENU_ITEMS_PER_MONITOR: usize = 100;
+/// How many monitor submenus the menu lists at most: as many as fit between
+/// the rate and resolution base IDs.
+pub const MENU_MAX_MONITORS: usize =
+    (MENU_RESOLUTION_BASE_ID - MENU_REFRESH_RATE_BASE_ID) as usize / MENU_ITEMS_PER_MONITOR;
+
+// Exit sits below the other IDs, and the last monitor's resolutions end
+// before the first orientation, as its rates end before the first resolution.
+const _: () = assert!(MENU_EXIT_ID < MENU_PROFILE_BASE_ID);
+const _: () = assert!(
+    MENU_RESOLUTION_BASE_ID as usize + MENU_MAX_MONITORS * MENU_ITEMS_PER_MONITOR
+        <= MENU_ORIENTATION_BASE_ID as usize
+);
+
+/// An orientation choice and whether the driver accepts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrientationSupport {
+    pub orientation: Orientation,
+    /// Whether `CDS_TEST` accepted the rotated mode.
+    pub accepted: bool,
+}
+
+/// One monitor submenu.
+#[derive(Debug, Clone)]
+pub struct MonitorMenu {
+    pub device: DisplayDevice,
+    /// At most `MENU_ITEMS_PER_MONITOR` rates.
+    pub rates: Vec<RefreshRateSupport>,
+    pub capabilities: Option<RefreshCapabilities>,
+    /// The mode the monitor is running, if it could be read.
+    pub current: Option<DisplayMode>,
+    /// One mode per resolution, see `resolution_modes`; the largest
+    /// `MENU_ITEMS_PER_MONITOR` of them.
+    pub resolutions: Vec<DisplayMode>,
+    /// Every orientation, in `Orientation::ALL` order. Empty when the current
+    /// mode could not be read.
+    pub orientations: Vec<OrientationSupport>,
+}
+
+impl MonitorMenu {
+    /// Menu text for the `rate_index`-th rate, e.g. `165 Hz (not advertised)`.
+    pub fn rate_label(&self, rate_index: usize) -> Option<String> {
+        let support = self.rates.get(rate_index)?;
+        Some(match support.advertised {
+            Some(false) => format!("{} (not advertised)", support.rate),
+            _ => support.rate.to_string(),
+        })
+    }
+
+    /// Menu text for the `resolution_index`-th resolution, e.g. `1920x1080`,
+    /// or `1920x1080 @ 75 Hz` when it would also change the refresh rate.
+    pub fn resolution_label(&self, resolution_index: usize) -> Option<String> {
+        let mode = self.resolutions.get(resolution_index)?;
+        let label = format!("{}x{}", mode.width, mode.height);
+        Some(match self.current {
+            Some(current) if current.frequency != mode.frequency => {
+                format!("{} @ {} Hz", label, mode.frequency)
+            }
+            _ => label,
+        })
+    }
+
+    /// Menu text for the `orientation_index`-th orientation, e.g. `90° Portrait`.
+    pub fn orientation_label(&self, orientation_index: usize) -> Option<String> {
+        let orientation = self.orientations.get(orientation_index)?.orientation;
+        Some(format!("{}° {}", orientation.degrees(), orientation))
+    }
+
+    /// Whether the monitor is running the `resolution_index`-th resolution.
+    pub fn is_current_resolution(&self, resolution_index: usize) -> bool {
+        match (self.current, self.resolutions.get(resolution_index)) {
+            (Some(current), Some(mode)) => current.is_compatible_with(mode),
+            _ => false,
+        }
+    }
+
+    /// Informational line describing what the panel advertises, e.g.
+    /// `Panel 48-144 Hz, VRR 48-144 Hz`.
+    pub fn capabilities_label(&self) -> Option<String> {
+        let capabilities = self.capabilities.as_ref()?;
+        let range = capabilities.refresh_range?;
+        let mut label = format!("Panel {}-{} Hz", range.min_hz, range.max_hz);
+        if let Some(vrr) = capabilities.vrr {
+            label.push_str(&format!(", VRR {}-{} Hz", vrr.min_hz, vrr.max_hz));
+        }
+        Some(label)
+    }
+}
+
+/// What a menu command ID resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuCommand {
+    SetRefreshRate {
+        device_name: String,
+        rate: RefreshRate,
+    },
+    SetDisplayMode {
+        device_name: String,
+        mode: DisplayMode,
+    },
+    SetOrientation {
+        device_name: String,
+        orientation: Orientation,
+    },
+    /// Apply the profile with this name.
+    ApplyProfile(String),
+    SetPersistence(Persistence),
+    Exit,
+}
+
+/// Menu text for a persistence choice.
+pub fn persistence_label(persistence: Persistence) -> &'static str {
+    match persistence {
+        Persistence::Temporary => "Until restart",
+        Persistence::Persistent => "Keep after restart",
+        Persistence::Global => "Keep after restart (all users)",
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrayMenu {
+    pub monitors: Vec<MonitorMenu>,
+    /// Profile names, shown above the monitor submenus.
+    pub profiles: Vec<String>,
+}
+
+impl TrayMenu {
+    /// Enumerates the display devices, up to `MENU_MAX_MONITORS` of them,
+    /// their refresh rates and resolutions and what their EDID advertises.
+    pub fn build(backend: &dyn DisplayBackend) -> Result<Self, DisplayError> {
+        let monitors = get_all_display_devices_with(backend)?
+            .into_iter()
+            .take(MENU_MAX_MONITORS)
+            .map(|device| {
+                let report = get_refresh_rate_report_with(backend, &device);
+                let current = backend.current_mode(&device.device_name).ok();
+                let resolutions = current
+                    .map(|current| {
+                        let modes = get_display_modes_with(backend, &device.device_name);
+                        let mut resolutions = resolution_modes(&modes, &current);
+                        resolutions.truncate(MENU_ITEMS_PER_MONITOR);
+                        resolutions
+                    })
+                    .unwrap_or_default();
+                let orientations = current
+                    .map(|current| {
+                        Orientation::ALL
+                            .iter()
+                            .map(|&orientation| OrientationSupport {
+                                orientation,
+                                accepted: orientation == current.orientation
+                                    || validate_mode_with(
+                                        backend,
+                                        &device.device_name,
+                                        &current.rotated(orientation),
+                                    )
+                                    .is_ok(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let mut rates = report.rates;
+                rates.truncate(MENU_ITEMS_PER_MONITOR);
+                MonitorMenu {
+                    device,
+                    rates,
+                    capabilities: report.capabilities,
+                    current,
+                    resolutions,
+                    orientations,
+                }
+            })
+            .collect();
+        Ok(TrayMenu {
+            monitors,
+            profiles: Vec::new(),
+        })
+    }
+
+    /// Lists `profiles` in the menu, at most 100 of them.
+    pub fn with_profiles(mut self, profiles: &[Profile]) -> Self {
+        self.profiles = profiles
+            .iter()
+            .take(100)
+            .map(|profile| profile.name.clone())
+            .collect();
+        self
+    }
+
+    /// Command ID of the `rate_index`-th rate in the `monitor_index`-th submenu.
+    pub fn rate_command_id(monitor_index: usize, rate_index: usize) -> u32 {
+        monitor_command_id(MENU_REFRESH_RATE_BASE_ID, monitor_index, rate_index)
+    }
+
+    /// Command ID of the `resolution_index`-th resolution in the
+    /// `monitor_index`-th submenu.
+    pub fn resolution_command_id(monitor_index: usize, resolution_index: usize) -> u32 {
+        monitor_command_id(MENU_RESOLUTION_BASE_ID, monitor_index, resolution_index)
+    }
+
+    /// Command ID of the `orientation_index`-th orientation in the
+    /// `monitor_index`-th submenu.
+    pub fn orientation_command_id(monitor_index: usize, orientation_index: usize) -> u32 {
+        monitor_command_id(MENU_ORIENTATION_BASE_ID, monitor_index, orientation_index)
+    }
+
+    /// Command ID of the `index`-th profile.
+    pub fn profile_command_id(index: usize) -> u32 {
+        MENU_PROFILE_BASE_ID + index as u32
+    }
+
+    /// Command ID of the `index`-th entry of `Persistence::ALL`.
+    pub fn persistence_command_id(index: usize) -> u32 {
+        MENU_PERSISTENCE_BASE_ID + index as u32
+    }
+
+    /// Resolves a command ID produced by this menu.
+    pub fn command(&self, menu_id: u32) -> Option<MenuCommand> {
+        if menu_id == MENU_EXIT_ID {
+            return Some(MenuCommand::Exit);
+        }
+        if (MENU_PROFILE_BASE_ID..MENU_PERSISTENCE_BASE_ID).contains(&menu_id) {
+            let name = self
+                .profiles
+                .get((menu_id - MENU_PROFILE_BASE_ID) as usize)?;
+            return Some(MenuCommand::ApplyProfile(name.clone()));
+        }
+        if let Some(index) = menu_id.checked_sub(MENU_PERSISTENCE_BASE_ID) {
+            if let Some(&persistence) = Persistence::ALL.get(index as usize) {
+                return Some(MenuCommand::SetPersistence(persistence));
+            }
+        }
+        if menu_id >= MENU_ORIENTATION_BASE_ID {
+            let (monitor_index, orientation_index) =
+                split_monitor_command_id(menu_id, MENU_ORIENTATION_BASE_ID);
+            let monitor = self.monitors.get(monitor_index)?;
+            return Some(MenuCommand::SetOrientation {
+                device_name: monitor.device.device_name.clone(),
+                orientation: monitor.orientations.get(orientation_index)?.orientation,
+            });
+        }
+        if menu_id >= MENU_RESOLUTION_BASE_ID {
+            let (monitor_index, resolution_index) =
+                split_monitor_command_id(menu_id, MENU_RESOLUTION_BASE_ID);
+            let monitor = self.monitors.get(monitor_index)?;
+            return Some(MenuCommand::SetDisplayMode {
+                device_name: monitor.device.device_name.clone(),
+                mode: *monitor.resolutions.get(resolution_index)?,
+            });
+        }
+        if menu_id < MENU_REFRESH_RATE_BASE_ID {
+            return None;
+        }
+
+        let (monitor_index, rate_index) =
+            split_monitor_command_id(menu_id, MENU_REFRESH_RATE_BASE_ID);
+        let monitor = self.monitors.get(monitor_index)?;
+        let support = monitor.rates.get(rate_index)?;
+        Some(MenuCommand::SetRefreshRate {
+            device_name: monitor.device.device_name.clone(),
+            rate: support.rate,
+        })
+    }
+}
+
+/// The command ID of the `index`-th entry of a monitor submenu list whose IDs
+/// start at `base`.
+fn monitor_command_id(base: u32, monitor_index: usize, index: usize) -> u32 {
+    debug_assert!(index < MENU_ITEMS_PER_MONITOR);
+    base + (monitor_index * MENU_ITEMS_PER_MONITOR + index) as u32
+}
+
+/// The monitor and entry index of a command ID from `monitor_command_id`.
+fn split_monitor_command_id(menu_id: u32, base: u32) -> (usize, usize) {
+    let offset = (menu_id - base) as usize;
+    (
+        offset / MENU_ITEMS_PER_MONITOR,
+        offset % MENU_ITEMS_PER_MONITOR,
+    )
+}
@@ -0,0 +1,285 @@
+//! Display change notifications.
+//!
+//! `DisplayTracker` keeps the last seen `DisplayState` and turns each fresh
+//! snapshot into `DisplayEvent`s; it works against any backend. On Windows,
+//! `DisplayWatcher` drives a tracker from a hidden window that listens for
+//! `WM_DISPLAYCHANGE` and monitor arrival and removal.
+
+use crate::backend::DisplayBackend;
+use crate::mode::DisplayMode;
+use crate::{get_all_display_devices_with, DisplayDevice, DisplayError};
+
+/// A monitor and the mode its adapter output was running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorState {
+    pub device: DisplayDevice,
+    /// `None` when the current mode could not be read.
+    pub mode: Option<DisplayMode>,
+}
+
+impl MonitorState {
+    /// What identifies the monitor across snapshots: its device interface
+    /// path, or its monitor device name when it has none.
+    fn key(&self) -> &str {
+        if self.device.interface_path.is_empty() {
+            &self.device.monitor_device_name
+        } else {
+            &self.device.interface_path
+        }
+    }
+}
+
+/// Every active monitor at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisplayState {
+    pub monitors: Vec<MonitorState>,
+}
+
+impl DisplayState {
+    pub fn capture_with(backend: &dyn DisplayBackend) -> Result<Self, DisplayError> {
+        let monitors = get_all_display_devices_with(backend)?
+            .into_iter()
+            .map(|device| {
+                let mode = backend.current_mode(&device.device_name).ok();
+                MonitorState { device, mode }
+            })
+            .collect();
+        Ok(DisplayState { monitors })
+    }
+
+    /// The first monitor on the primary adapter output.
+    pub fn primary(&self) -> Option<&DisplayDevice> {
+        self.monitors
+            .iter()
+            .map(|monitor| &monitor.device)
+            .find(|device| device.is_primary)
+    }
+
+    fn find(&self, key: &str) -> Option<&MonitorState> {
+        self.monitors.iter().find(|monitor| monitor.key() == key)
+    }
+
+    /// What changed from `self` to `new`: removals first, then additions,
+    /// mode changes and finally a primary change.
+    pub fn diff(&self, new: &DisplayState) -> Vec<DisplayEvent> {
+        let mut events = Vec::new();
+        for old in &self.monitors {
+            if new.find(old.key()).is_none() {
+                events.push(DisplayEvent::MonitorRemoved(old.device.clone()));
+            }
+        }
+        for monitor in &new.monitors {
+            if self.find(monitor.key()).is_none() {
+                events.push(DisplayEvent::MonitorAdded(monitor.device.clone()));
+            }
+        }
+        for monitor in &new.monitors {
+            let Some(old) = self.find(monitor.key()) else {
+                continue;
+            };
+            if let (Some(old_mode), Some(new_mode)) = (old.mode, monitor.mode) {
+                if old_mode != new_mode {
+                    events.push(DisplayEvent::ModeChanged {
+                        device: monitor.device.clone(),
+                        old: old_mode,
+                        new: new_mode,
+                    });
+                }
+            }
+        }
+
+        let (old_primary, new_primary) = (self.primary(), new.primary());
+        if old_primary.map(|d| &d.device_name) != new_primary.map(|d| &d.device_name) {
+            events.push(DisplayEvent::PrimaryChanged {
+                old: old_primary.cloned(),
+                new: new_primary.cloned(),
+            });
+        }
+        events
+    }
+}
+
+/// Something that changed between two display snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayEvent {
+    MonitorAdded(DisplayDevice),
+    MonitorRemoved(DisplayDevice),
+    /// The monitor's adapter output switched mode, e.g. a new refresh rate,
+    /// resolution or orientation.
+    ModeChanged {
+        device: DisplayDevice,
+        old: DisplayMode,
+        new: DisplayMode,
+    },
+    /// Another adapter output became the primary display. `None` when no
+    /// active monitor is primary.
+    PrimaryChanged {
+        old: Option<DisplayDevice>,
+        new: Option<DisplayDevice>,
+    },
+}
+
+/// Remembers the last display snapshot and reports what changed since.
+#[derive(Debug, Clone, Default)]
+pub struct DisplayTracker {
+    state: DisplayState,
+}
+
+impl DisplayTracker {
+    /// Starts from the current state of `backend`.
+    pub fn new_with(backend: &dyn DisplayBackend) -> Result<Self, DisplayError> {
+        Ok(DisplayTracker {
+            state: DisplayState::capture_with(backend)?,
+        })
+    }
+
+    pub fn state(&self) -> &DisplayState {
+        &self.state
+    }
+
+    /// Takes a new snapshot and returns what changed since the last one. On
+    /// error the previous snapshot is kept.
+    pub fn refresh_with(
+        &mut self,
+        backend: &dyn DisplayBackend,
+    ) -> Result<Vec<DisplayEvent>, DisplayError> {
+        let state = DisplayState::capture_with(backend)?;
+        let events = self.state.diff(&state);
+        self.state = state;
+        Ok(events)
+    }
+}
+
+#[cfg(windows)]
+pub use self::window::DisplayWatcher;
+
+#[cfg(windows)]
+mod window {
+    use std::mem;
+    use std::ptr;
+    use std::sync::mpsc::{self, Receiver};
+
+    use log::{debug, warn};
+    use winapi::shared::guiddef::GUID;
+    use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+    use winapi::shared::windef::HWND;
+    use winapi::um::dbt::{
+        DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DBT_DEVTYP_DEVICEINTERFACE,
+        DEV_BROADCAST_DEVICEINTERFACE_W,
+    };
+    use winapi::um::errhandlingapi::GetLastError;
+    use winapi::um::winuser::{
+        RegisterDeviceNotificationW, UnregisterDeviceNotification, DEVICE_NOTIFY_WINDOW_HANDLE,
+        WM_DEVICECHANGE, WM_DISPLAYCHANGE,
+    };
+
+    use super::{DisplayEvent, DisplayTracker};
+    use crate::watcher::{MessageWindow, WindowHandler};
+    use crate::{DisplayError, Win32Backend};
+
+    // GUID for monitor device interfaces (GUID_DEVINTERFACE_MONITOR)
+    // {e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}
+    const GUID_DEVINTERFACE_MONITOR: GUID = GUID {
+        Data1: 0xe6f07b5f,
+        Data2: 0xee97,
+        Data3: 0x4a90,
+        Data4: [0xb0, 0x76, 0x33, 0xf5, 0x7b, 0xf4, 0xea, 0xa7],
+    };
+
+    const CLASS_NAME: &str = "RefreshRateDisplayWatcher";
+
+    struct WatcherWindow {
+        tracker: DisplayTracker,
+        callback: Box<dyn FnMut(DisplayEvent) + Send>,
+        notification: *mut winapi::ctypes::c_void,
+    }
+
+    impl WindowHandler for WatcherWindow {
+        fn created(&mut self, hwnd: HWND) -> Result<(), DisplayError> {
+            let mut filter: DEV_BROADCAST_DEVICEINTERFACE_W = unsafe { mem::zeroed() };
+            filter.dbcc_size = mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32;
+            filter.dbcc_devicetype = DBT_DEVTYP_DEVICEINTERFACE;
+            filter.dbcc_classguid = GUID_DEVINTERFACE_MONITOR;
+            self.notification = unsafe {
+                RegisterDeviceNotificationW(
+                    hwnd as *mut _,
+                    &mut filter as *mut _ as *mut _,
+                    DEVICE_NOTIFY_WINDOW_HANDLE,
+                )
+            };
+            if self.notification.is_null() {
+                return Err(DisplayError::WatcherUnavailable {
+                    os_error: unsafe { GetLastError() },
+                });
+            }
+            Ok(())
+        }
+
+        fn message(&mut self, msg: UINT, wparam: WPARAM, _lparam: LPARAM) -> Option<LRESULT> {
+            match msg {
+                WM_DISPLAYCHANGE => self.refresh(),
+                WM_DEVICECHANGE
+                    if wparam == DBT_DEVICEARRIVAL || wparam == DBT_DEVICEREMOVECOMPLETE =>
+                {
+                    self.refresh()
+                }
+                _ => {}
+            }
+            None
+        }
+    }
+
+    impl WatcherWindow {
+        fn refresh(&mut self) {
+            match self.tracker.refresh_with(&Win32Backend) {
+                Ok(events) => {
+                    for event in events {
+                        debug!("Display change: {:?}", event);
+                        (self.callback)(event);
+                    }
+                }
+                Err(error) => warn!("Could not re-read the display configuration: {}", error),
+            }
+        }
+    }
+
+    impl Drop for WatcherWindow {
+        fn drop(&mut self) {
+            if !self.notification.is_null() {
+                unsafe { UnregisterDeviceNotification(self.notification) };
+            }
+        }
+    }
+
+    /// Watches for display changes on a thread of its own and hands every
+    /// `DisplayEvent` to a callback there. Dropping the watcher stops it.
+    pub struct DisplayWatcher {
+        _window: MessageWindow,
+    }
+
+    impl DisplayWatcher {
+        /// Starts watching; `callback` runs on the watcher thread.
+        pub fn start<F>(callback: F) -> Result<Self, DisplayError>
+        where
+            F: FnMut(DisplayEvent) + Send + 'static,
+        {
+            let window = MessageWindow::start(CLASS_NAME, move || {
+                Ok(WatcherWindow {
+                    tracker: DisplayTracker::new_with(&Win32Backend)?,
+                    callback: Box::new(callback),
+                    notification: ptr::null_mut(),
+                })
+            })?;
+            Ok(DisplayWatcher { _window: window })
+        }
+
+        /// Starts watching and delivers events over a channel instead.
+        pub fn channel() -> Result<(Self, Receiver<DisplayEvent>), DisplayError> {
+            let (sender, receiver) = mpsc::channel();
+            let watcher = DisplayWatcher::start(move |event| {
+                let _ = sender.send(event);
+            })?;
+            Ok((watcher, receiver))
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! What the Windows watchers share: a thread that is stopped and joined on
+//! drop, its message loop, and a hidden window to receive broadcasts with.
+
+use std::mem;
+use std::ptr;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::ERROR_CLASS_ALREADY_EXISTS;
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::winuser::{
+    CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GetMessageW,
+    GetWindowLongPtrW, PostMessageW, PostQuitMessage, RegisterClassExW, SetWindowLongPtrW,
+    TranslateMessage, CREATESTRUCTW, GWLP_USERDATA, MSG, WM_CLOSE, WM_DESTROY, WM_NCCREATE,
+    WNDCLASSEXW, WS_EX_TOOLWINDOW,
+};
+
+use crate::{to_wide_string, DisplayError};
+
+/// A watcher's thread. Dropping it stops the thread and waits for it.
+pub(crate) struct WatcherThread {
+    stop: Option<Box<dyn FnOnce() + Send>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatcherThread {
+    /// Runs `body` on a new thread and waits until it reports through its
+    /// sender whether it started. `stop` gets what it reported and must make
+    /// `body` return.
+    pub(crate) fn start<T, B, S>(body: B, stop: S) -> Result<Self, DisplayError>
+    where
+        T: Send + 'static,
+        B: FnOnce(SyncSender<Result<T, DisplayError>>) + Send + 'static,
+        S: FnOnce(T) + Send + 'static,
+    {
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+        let thread = thread::spawn(move || body(ready_tx));
+        match ready_rx.recv() {
+            Ok(Ok(started)) => Ok(WatcherThread {
+                stop: Some(Box::new(move || stop(started))),
+                thread: Some(thread),
+            }),
+            Ok(Err(error)) => {
+                let _ = thread.join();
+                Err(error)
+            }
+            Err(_) => Err(DisplayError::WatcherUnavailable { os_error: 0 }),
+        }
+    }
+}
+
+impl Drop for WatcherThread {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Dispatches the calling thread's messages until `WM_QUIT`.
+pub(crate) fn run_message_loop() {
+    let mut msg: MSG = unsafe { mem::zeroed() };
+    while unsafe { GetMessageW(&mut msg, ptr::null_mut(), 0, 0) } > 0 {
+        unsafe {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+/// What a `MessageWindow` does with its messages. It lives on the window's
+/// thread and is dropped when the window is destroyed.
+pub(crate) trait WindowHandler: 'static {
+    /// Runs once the window exists, e.g. to register for notifications. An
+    /// error destroys the window and fails `MessageWindow::start`.
+    fn created(&mut self, _hwnd: HWND) -> Result<(), DisplayError> {
+        Ok(())
+    }
+
+    /// Handles `msg`; `None` leaves it to `DefWindowProcW`.
+    fn message(&mut self, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT>;
+}
+
+/// A hidden window running its message loop on a thread of its own.
+/// Dropping it closes the window and waits for the thread.
+pub(crate) struct MessageWindow {
+    _thread: WatcherThread,
+}
+
+impl MessageWindow {
+    /// Creates a window of class `class_name` on a new thread, with the
+    /// handler `handler` builds there.
+    pub(crate) fn start<H, F>(class_name: &'static str, handler: F) -> Result<Self, DisplayError>
+    where
+        H: WindowHandler,
+        F: FnOnce() -> Result<H, DisplayError> + Send + 'static,
+    {
+        let thread = WatcherThread::start(
+            move |ready| {
+                let hwnd = match handler().and_then(|handler| create_window(class_name, handler)) {
+                    Ok(hwnd) => hwnd,
+                    Err(error) => {
+                        let _ = ready.send(Err(error));
+                        return;
+                    }
+                };
+                let _ = ready.send(Ok(hwnd as usize));
+                run_message_loop();
+            },
+            |hwnd| unsafe {
+                PostMessageW(hwnd as HWND, WM_CLOSE, 0, 0);
+            },
+        )?;
+        Ok(MessageWindow { _thread: thread })
+    }
+}
+
+fn create_window<H: WindowHandler>(class_name: &str, handler: H) -> Result<HWND, DisplayError> {
+    let last_error = || DisplayError::WatcherUnavailable {
+        os_error: unsafe { GetLastError() },
+    };
+
+    let class_name = to_wide_string(class_name);
+    let hinstance = unsafe { GetModuleHandleW(ptr::null()) };
+    let mut wc: WNDCLASSEXW = unsafe { mem::zeroed() };
+    wc.cbSize = mem::size_of::<WNDCLASSEXW>() as UINT;
+    wc.lpfnWndProc = Some(window_proc::<H>);
+    wc.hInstance = hinstance;
+    wc.lpszClassName = class_name.as_ptr();
+    if unsafe { RegisterClassExW(&wc) } == 0
+        && unsafe { GetLastError() } != ERROR_CLASS_ALREADY_EXISTS
+    {
+        return Err(last_error());
+    }
+
+    let state = Box::into_raw(Box::new(handler));
+    // A hidden top-level window: message-only windows miss broadcasts such
+    // as WM_DISPLAYCHANGE and WM_POWERBROADCAST.
+    let hwnd = unsafe {
+        CreateWindowExW(
+            WS_EX_TOOLWINDOW,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            hinstance,
+            state as *mut _,
+        )
+    };
+    if hwnd.is_null() {
+        let error = last_error();
+        drop(unsafe { Box::from_raw(state) });
+        return Err(error);
+    }
+
+    if let Err(error) = unsafe { (*state).created(hwnd) } {
+        unsafe { DestroyWindow(hwnd) };
+        return Err(error);
+    }
+    Ok(hwnd)
+}
+
+unsafe extern "system" fn window_proc<H: WindowHandler>(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_NCCREATE {
+        let create = lparam as *const CREATESTRUCTW;
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, (*create).lpCreateParams as isize);
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let state = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut H;
+    if state.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    if msg == WM_DESTROY {
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+        drop(Box::from_raw(state));
+        PostQuitMessage(0);
+        return 0;
+    }
+    match (*state).message(msg, wparam, lparam) {
+        Some(result) => result,
+        None => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
@@ -0,0 +1,551 @@
+use std::mem;
+use std::ptr;
+
+use winapi::shared::guiddef::GUID;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::minwindef::HKEY;
+use winapi::shared::winerror::{
+    ERROR_INSUFFICIENT_BUFFER, ERROR_MORE_DATA, ERROR_NOT_FOUND, ERROR_SUCCESS,
+};
+use winapi::um::cfgmgr32::{
+    CM_DRP_DEVICEDESC, CM_DRP_DRIVER, CM_DRP_FRIENDLYNAME, CM_DRP_HARDWAREID,
+};
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+use winapi::um::setupapi::{
+    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
+    SetupDiGetDeviceInstanceIdW, SetupDiGetDeviceRegistryPropertyW, DIGCF_PRESENT, DIGCF_PROFILE,
+    HDEVINFO, SP_DEVINFO_DATA,
+};
+use winapi::um::wingdi::{
+    DEVMODEW, DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER,
+    DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_PATH_MODE_IDX_INVALID,
+    DISPLAYCONFIG_RATIONAL, DISPLAYCONFIG_SOURCE_DEVICE_NAME, DISPLAYCONFIG_TOPOLOGY_ID,
+    DISPLAY_DEVICEW, DM_BITSPERPEL, DM_DISPLAYFIXEDOUTPUT, DM_DISPLAYFLAGS, DM_DISPLAYFREQUENCY,
+    DM_DISPLAYORIENTATION, DM_INTERLACED, DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION,
+    QDC_ONLY_ACTIVE_PATHS,
+};
+use winapi::um::winnt::{KEY_READ, LONG, WCHAR};
+use winapi::um::winreg::{
+    RegCloseKey, RegOpenKeyExW, RegQueryValueExW, HKEY_LOCAL_MACHINE, LSTATUS,
+};
+use winapi::um::winuser::{
+    ChangeDisplaySettingsExW, EnumDisplayDevicesW, EnumDisplaySettingsW,
+    EDD_GET_DEVICE_INTERFACE_NAME, ENUM_CURRENT_SETTINGS,
+};
+
+use crate::backend::{DisplayBackend, MonitorDevNode, RawDisplayDevice};
+use crate::devnode::instance_id_from_interface_path;
+use crate::edid::EDID_BLOCK_LEN;
+use crate::mode::{DisplayMode, Orientation, Position, RefreshRate, Scaling};
+use crate::to_wide_string;
+
+// GUID for monitor devices (GUID_DEVCLASS_MONITOR)
+// {4d36e96e-e325-11ce-bfc1-08002be10318}
+const GUID_DEVCLASS_MONITOR: GUID = GUID {
+    Data1: 0x4d36e96e,
+    Data2: 0xe325,
+    Data3: 0x11ce,
+    Data4: [0xbf, 0xc1, 0x08, 0x00, 0x2b, 0xe1, 0x03, 0x18],
+};
+
+// The CCD entry points are missing from winapi 0.3.
+#[link(name = "user32")]
+extern "system" {
+    fn GetDisplayConfigBufferSizes(flags: u32, num_paths: *mut u32, num_modes: *mut u32) -> LONG;
+    fn QueryDisplayConfig(
+        flags: u32,
+        num_paths: *mut u32,
+        paths: *mut DISPLAYCONFIG_PATH_INFO,
+        num_modes: *mut u32,
+        modes: *mut DISPLAYCONFIG_MODE_INFO,
+        topology: *mut DISPLAYCONFIG_TOPOLOGY_ID,
+    ) -> LONG;
+    fn SetDisplayConfig(
+        num_paths: u32,
+        paths: *mut DISPLAYCONFIG_PATH_INFO,
+        num_modes: u32,
+        modes: *mut DISPLAYCONFIG_MODE_INFO,
+        flags: u32,
+    ) -> LONG;
+    fn DisplayConfigGetDeviceInfo(packet: *mut DISPLAYCONFIG_DEVICE_INFO_HEADER) -> LONG;
+}
+
+/// `DisplayBackend` backed by the real Win32 display and SetupAPI calls.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Win32Backend;
+
+fn from_wide(buffer: &[u16]) -> String {
+    String::from_utf16_lossy(buffer)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn enum_display_device(
+    parent_ptr: *const u16,
+    index: DWORD,
+    flags: DWORD,
+) -> Option<DISPLAY_DEVICEW> {
+    let mut display_device: DISPLAY_DEVICEW = unsafe { mem::zeroed() };
+    display_device.cb = mem::size_of::<DISPLAY_DEVICEW>() as DWORD;
+
+    let result = unsafe { EnumDisplayDevicesW(parent_ptr, index, &mut display_device, flags) };
+    (result != 0).then_some(display_device)
+}
+
+fn enum_display_devices(parent: Option<&str>) -> Vec<RawDisplayDevice> {
+    let parent_wide = parent.map(to_wide_string);
+    let parent_ptr = parent_wide.as_ref().map_or(ptr::null(), |p| p.as_ptr());
+
+    let mut devices = Vec::new();
+    for index in 0.. {
+        let Some(display_device) = enum_display_device(parent_ptr, index, 0) else {
+            break;
+        };
+
+        // Monitors report their device interface path in DeviceID when asked to.
+        let interface_path = match parent {
+            Some(_) => enum_display_device(parent_ptr, index, EDD_GET_DEVICE_INTERFACE_NAME)
+                .map(|device| from_wide(&device.DeviceID))
+                .unwrap_or_default(),
+            None => String::new(),
+        };
+
+        devices.push(RawDisplayDevice {
+            device_name: from_wide(&display_device.DeviceName),
+            device_string: from_wide(&display_device.DeviceString),
+            state_flags: display_device.StateFlags,
+            device_id: from_wide(&display_device.DeviceID),
+            interface_path,
+        });
+    }
+    devices
+}
+
+/// Reads a SetupAPI registry property as raw UTF-16, growing the buffer when
+/// the call reports `ERROR_INSUFFICIENT_BUFFER`.
+fn devnode_property_raw(
+    hdevinfo: HDEVINFO,
+    device_info_data: &mut SP_DEVINFO_DATA,
+    property: DWORD,
+) -> Option<Vec<u16>> {
+    let mut buffer: Vec<u16> = vec![0; 256];
+    loop {
+        let mut required_size: DWORD = 0;
+        let result = unsafe {
+            SetupDiGetDeviceRegistryPropertyW(
+                hdevinfo,
+                device_info_data,
+                property,
+                ptr::null_mut(),
+                buffer.as_mut_ptr() as *mut u8,
+                (buffer.len() * mem::size_of::<WCHAR>()) as DWORD,
+                &mut required_size,
+            )
+        };
+
+        let required_len = (required_size as usize).div_ceil(mem::size_of::<WCHAR>());
+        if result != 0 {
+            buffer.truncate(required_len.min(buffer.len()));
+            return Some(buffer);
+        }
+        if unsafe { GetLastError() } != ERROR_INSUFFICIENT_BUFFER || required_len <= buffer.len() {
+            return None;
+        }
+        buffer.resize(required_len, 0);
+    }
+}
+
+fn devnode_property(
+    hdevinfo: HDEVINFO,
+    device_info_data: &mut SP_DEVINFO_DATA,
+    property: DWORD,
+) -> Option<String> {
+    devnode_property_raw(hdevinfo, device_info_data, property).map(|buffer| from_wide(&buffer))
+}
+
+/// Reads a `REG_MULTI_SZ` property such as `CM_DRP_HARDWAREID`.
+fn devnode_multi_sz_property(
+    hdevinfo: HDEVINFO,
+    device_info_data: &mut SP_DEVINFO_DATA,
+    property: DWORD,
+) -> Vec<String> {
+    devnode_property_raw(hdevinfo, device_info_data, property)
+        .map(|buffer| {
+            buffer
+                .split(|&c| c == 0)
+                .filter(|s| !s.is_empty())
+                .map(String::from_utf16_lossy)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn devnode_instance_id(hdevinfo: HDEVINFO, device_info_data: &mut SP_DEVINFO_DATA) -> String {
+    let mut buffer: Vec<u16> = vec![0; 256];
+    loop {
+        let mut required_size: DWORD = 0;
+        let result = unsafe {
+            SetupDiGetDeviceInstanceIdW(
+                hdevinfo,
+                device_info_data,
+                buffer.as_mut_ptr(),
+                buffer.len() as DWORD,
+                &mut required_size,
+            )
+        };
+
+        if result != 0 {
+            return from_wide(&buffer);
+        }
+        if unsafe { GetLastError() } != ERROR_INSUFFICIENT_BUFFER
+            || required_size as usize <= buffer.len()
+        {
+            return String::new();
+        }
+        buffer.resize(required_size as usize, 0);
+    }
+}
+
+fn devmode_to_mode(dev_mode: &DEVMODEW) -> DisplayMode {
+    let (display_flags, display) = unsafe { (*dev_mode.u2.dmDisplayFlags(), dev_mode.u1.s2()) };
+    DisplayMode {
+        width: dev_mode.dmPelsWidth,
+        height: dev_mode.dmPelsHeight,
+        bits_per_pel: dev_mode.dmBitsPerPel,
+        frequency: dev_mode.dmDisplayFrequency,
+        interlaced: display_flags & DM_INTERLACED != 0,
+        scaling: Scaling::from_dmdfo(display.dmDisplayFixedOutput),
+        orientation: Orientation::from_dmdo(display.dmDisplayOrientation),
+    }
+}
+
+fn query_devmode(device_name_wide: &[u16], mode_num: DWORD) -> Option<DEVMODEW> {
+    let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+    dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+
+    let result =
+        unsafe { EnumDisplaySettingsW(device_name_wide.as_ptr(), mode_num, &mut dev_mode) };
+    (result != 0).then_some(dev_mode)
+}
+
+/// `ChangeDisplaySettingsExW` for `device_name` with the fields of `mode`, and
+/// `position` if given.
+fn change_display_settings(
+    device_name: &str,
+    mode: &DisplayMode,
+    position: Option<Position>,
+    flags: u32,
+) -> i32 {
+    let device_name_wide = to_wide_string(device_name);
+    // Start from the current DEVMODEW so fields we do not model are preserved.
+    let mut dev_mode =
+        query_devmode(&device_name_wide, ENUM_CURRENT_SETTINGS).unwrap_or_else(|| {
+            let mut dev_mode: DEVMODEW = unsafe { mem::zeroed() };
+            dev_mode.dmSize = mem::size_of::<DEVMODEW>() as u16;
+            dev_mode
+        });
+
+    dev_mode.dmPelsWidth = mode.width;
+    dev_mode.dmPelsHeight = mode.height;
+    dev_mode.dmBitsPerPel = mode.bits_per_pel;
+    dev_mode.dmDisplayFrequency = mode.frequency;
+    dev_mode.dmFields |= DM_PELSWIDTH
+        | DM_PELSHEIGHT
+        | DM_BITSPERPEL
+        | DM_DISPLAYFREQUENCY
+        | DM_DISPLAYFLAGS
+        | DM_DISPLAYORIENTATION;
+    unsafe {
+        let display_flags = dev_mode.u2.dmDisplayFlags_mut();
+        if mode.interlaced {
+            *display_flags |= DM_INTERLACED;
+        } else {
+            *display_flags &= !DM_INTERLACED;
+        }
+        let display = dev_mode.u1.s2_mut();
+        display.dmDisplayOrientation = mode.orientation.to_dmdo();
+        display.dmDisplayFixedOutput = mode.scaling.to_dmdfo();
+    }
+    // Only ask for a scaling mode explicitly; many drivers reject the field otherwise.
+    if mode.scaling != Scaling::Default {
+        dev_mode.dmFields |= DM_DISPLAYFIXEDOUTPUT;
+    }
+    if let Some(position) = position {
+        let display = unsafe { dev_mode.u1.s2_mut() };
+        display.dmPosition.x = position.x;
+        display.dmPosition.y = position.y;
+        dev_mode.dmFields |= DM_POSITION;
+    }
+
+    unsafe {
+        ChangeDisplaySettingsExW(
+            device_name_wide.as_ptr(),
+            &mut dev_mode,
+            ptr::null_mut(),
+            flags,
+            ptr::null_mut(),
+        )
+    }
+}
+
+/// The active paths and modes of the current display configuration.
+struct DisplayConfig {
+    paths: Vec<DISPLAYCONFIG_PATH_INFO>,
+    modes: Vec<DISPLAYCONFIG_MODE_INFO>,
+}
+
+impl DisplayConfig {
+    /// Queries the active configuration, retrying if it changes between
+    /// sizing the buffers and reading them.
+    fn query() -> Result<Self, u32> {
+        loop {
+            let (mut num_paths, mut num_modes) = (0u32, 0u32);
+            let status = unsafe {
+                GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut num_paths, &mut num_modes)
+            };
+            if status != ERROR_SUCCESS as LONG {
+                return Err(status as u32);
+            }
+
+            let mut paths: Vec<DISPLAYCONFIG_PATH_INFO> =
+                vec![unsafe { mem::zeroed() }; num_paths as usize];
+            let mut modes: Vec<DISPLAYCONFIG_MODE_INFO> =
+                vec![unsafe { mem::zeroed() }; num_modes as usize];
+            let status = unsafe {
+                QueryDisplayConfig(
+                    QDC_ONLY_ACTIVE_PATHS,
+                    &mut num_paths,
+                    paths.as_mut_ptr(),
+                    &mut num_modes,
+                    modes.as_mut_ptr(),
+                    ptr::null_mut(),
+                )
+            };
+            if status == ERROR_INSUFFICIENT_BUFFER as LONG {
+                continue;
+            }
+            if status != ERROR_SUCCESS as LONG {
+                return Err(status as u32);
+            }
+            paths.truncate(num_paths as usize);
+            modes.truncate(num_modes as usize);
+            return Ok(DisplayConfig { paths, modes });
+        }
+    }
+
+    /// Index of the path whose source is the GDI device `device_name`.
+    fn path_index(&self, device_name: &str) -> Option<usize> {
+        self.paths.iter().position(|path| {
+            let mut source_name: DISPLAYCONFIG_SOURCE_DEVICE_NAME = unsafe { mem::zeroed() };
+            source_name.header._type = DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME;
+            source_name.header.size = mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32;
+            source_name.header.adapterId = path.sourceInfo.adapterId;
+            source_name.header.id = path.sourceInfo.id;
+            let status = unsafe { DisplayConfigGetDeviceInfo(&mut source_name.header) };
+            status == ERROR_SUCCESS as LONG
+                && from_wide(&source_name.viewGdiDeviceName).eq_ignore_ascii_case(device_name)
+        })
+    }
+}
+
+impl DisplayBackend for Win32Backend {
+    fn adapters(&self) -> Vec<RawDisplayDevice> {
+        enum_display_devices(None)
+    }
+
+    fn monitors(&self, adapter_name: &str) -> Vec<RawDisplayDevice> {
+        enum_display_devices(Some(adapter_name))
+    }
+
+    fn monitor_devnodes(&self) -> Result<Vec<MonitorDevNode>, u32> {
+        let hdevinfo: HDEVINFO = unsafe {
+            SetupDiGetClassDevsW(
+                &GUID_DEVCLASS_MONITOR,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                DIGCF_PRESENT | DIGCF_PROFILE, // Only devices that are currently present, and include profile-specific devices
+            )
+        };
+
+        if hdevinfo == INVALID_HANDLE_VALUE {
+            return Err(unsafe { GetLastError() });
+        }
+
+        let mut devnodes = Vec::new();
+        for index in 0.. {
+            let mut device_info_data: SP_DEVINFO_DATA = unsafe { mem::zeroed() };
+            device_info_data.cbSize = mem::size_of::<SP_DEVINFO_DATA>() as DWORD;
+
+            if unsafe { SetupDiEnumDeviceInfo(hdevinfo, index, &mut device_info_data) } == 0 {
+                break;
+            }
+
+            devnodes.push(MonitorDevNode {
+                instance_id: devnode_instance_id(hdevinfo, &mut device_info_data),
+                hardware_ids: devnode_multi_sz_property(
+                    hdevinfo,
+                    &mut device_info_data,
+                    CM_DRP_HARDWAREID,
+                ),
+                driver_key: devnode_property(hdevinfo, &mut device_info_data, CM_DRP_DRIVER),
+                friendly_name: devnode_property(
+                    hdevinfo,
+                    &mut device_info_data,
+                    CM_DRP_FRIENDLYNAME,
+                ),
+                device_desc: devnode_property(hdevinfo, &mut device_info_data, CM_DRP_DEVICEDESC),
+            });
+        }
+
+        unsafe { SetupDiDestroyDeviceInfoList(hdevinfo) };
+        Ok(devnodes)
+    }
+
+    fn monitor_edid(&self, interface_path: &str) -> Option<Vec<u8>> {
+        let instance_id = instance_id_from_interface_path(interface_path)?;
+        let key_path = to_wide_string(&format!(
+            "SYSTEM\\CurrentControlSet\\Enum\\{}\\Device Parameters",
+            instance_id
+        ));
+        let value_name = to_wide_string("EDID");
+
+        let mut hkey: HKEY = ptr::null_mut();
+        let status = unsafe {
+            RegOpenKeyExW(
+                HKEY_LOCAL_MACHINE,
+                key_path.as_ptr(),
+                0,
+                KEY_READ,
+                &mut hkey,
+            )
+        };
+        if status != ERROR_SUCCESS as LSTATUS {
+            return None;
+        }
+
+        // Base block plus up to three extensions covers every monitor seen in practice;
+        // grow once if the driver stored more.
+        let mut buffer = vec![0u8; 4 * EDID_BLOCK_LEN];
+        let mut size = buffer.len() as DWORD;
+        let mut status = unsafe {
+            RegQueryValueExW(
+                hkey,
+                value_name.as_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                buffer.as_mut_ptr(),
+                &mut size,
+            )
+        };
+        if status == ERROR_MORE_DATA as LSTATUS {
+            buffer.resize(size as usize, 0);
+            status = unsafe {
+                RegQueryValueExW(
+                    hkey,
+                    value_name.as_ptr(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    buffer.as_mut_ptr(),
+                    &mut size,
+                )
+            };
+        }
+        unsafe { RegCloseKey(hkey) };
+
+        if status != ERROR_SUCCESS as LSTATUS {
+            return None;
+        }
+        buffer.truncate(size as usize);
+        Some(buffer)
+    }
+
+    fn modes(&self, device_name: &str) -> Vec<DisplayMode> {
+        let device_name_wide = to_wide_string(device_name);
+        (0..)
+            .map_while(|mode_num| query_devmode(&device_name_wide, mode_num))
+            .map(|dev_mode| devmode_to_mode(&dev_mode))
+            .collect()
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode, u32> {
+        let device_name_wide = to_wide_string(device_name);
+        query_devmode(&device_name_wide, ENUM_CURRENT_SETTINGS)
+            .map(|dev_mode| devmode_to_mode(&dev_mode))
+            .ok_or_else(|| unsafe { GetLastError() })
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode, flags: u32) -> i32 {
+        change_display_settings(device_name, mode, None, flags)
+    }
+
+    fn current_position(&self, device_name: &str) -> Result<Position, u32> {
+        let device_name_wide = to_wide_string(device_name);
+        let dev_mode = query_devmode(&device_name_wide, ENUM_CURRENT_SETTINGS)
+            .ok_or_else(|| unsafe { GetLastError() })?;
+        let position = unsafe { dev_mode.u1.s2().dmPosition };
+        Ok(Position::new(position.x, position.y))
+    }
+
+    fn apply_layout(
+        &self,
+        device_name: &str,
+        mode: &DisplayMode,
+        position: Position,
+        flags: u32,
+    ) -> i32 {
+        change_display_settings(device_name, mode, Some(position), flags)
+    }
+
+    fn commit_modes(&self) -> i32 {
+        unsafe {
+            ChangeDisplaySettingsExW(
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+            )
+        }
+    }
+
+    fn current_refresh_rate(&self, device_name: &str) -> Result<RefreshRate, u32> {
+        let config = DisplayConfig::query()?;
+        let index = config.path_index(device_name).ok_or(ERROR_NOT_FOUND)?;
+        let rate = config.paths[index].targetInfo.refreshRate;
+        if rate.Denominator == 0 {
+            return Err(ERROR_NOT_FOUND);
+        }
+        Ok(RefreshRate::new(rate.Numerator, rate.Denominator))
+    }
+
+    fn apply_refresh_rate(&self, device_name: &str, rate: RefreshRate, flags: u32) -> u32 {
+        let mut config = match DisplayConfig::query() {
+            Ok(config) => config,
+            Err(error) => return error,
+        };
+        let Some(index) = config.path_index(device_name) else {
+            return ERROR_NOT_FOUND;
+        };
+
+        // Drop the path's target mode so Windows picks one with the requested
+        // rate; the source mode keeps the resolution.
+        let target = &mut config.paths[index].targetInfo;
+        target.refreshRate = DISPLAYCONFIG_RATIONAL {
+            Numerator: rate.numerator,
+            Denominator: rate.denominator,
+        };
+        target.modeInfoIdx = DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+
+        let status = unsafe {
+            SetDisplayConfig(
+                config.paths.len() as u32,
+                config.paths.as_mut_ptr(),
+                config.modes.len() as u32,
+                config.modes.as_mut_ptr(),
+                flags,
+            )
+        };
+        status as u32
+    }
+}
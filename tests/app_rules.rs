@@ -0,0 +1,138 @@
+mod common;
+
+use refresh_rate_windows_rs::{
+    forced_rates, get_all_display_devices_with, AppRule, ChangeOptions, ChangeOutcome, Config,
+    DisplayBackend, FakeAdapter, FakeBackend, ForcedRate, RateOverrides,
+};
+
+use common::{desktop, desktop_modes, DISPLAY1, DISPLAY2};
+
+const GAME: &str = r"C:\Games\Elden Ring\Game\eldenring.exe";
+const EDITOR: &str = r"C:\Program Files\Adobe\Adobe Premiere Pro 2024\Adobe Premiere Pro.exe";
+
+fn backend() -> FakeBackend {
+    let modes = desktop_modes();
+    FakeBackend::new()
+        .with_adapter(
+            desktop()
+                .monitor_with_id("Dell U2720Q", "DEL4123")
+                .current(modes[1]),
+        )
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor_with_id("LG Ultragear", "GSM5B7F")
+                .modes(&modes),
+        )
+}
+
+fn rule(app: &str, monitor: Option<&str>, refresh_rate: u32) -> AppRule {
+    AppRule {
+        app: app.to_string(),
+        monitor: monitor.map(str::to_string),
+        refresh_rate,
+    }
+}
+
+fn forced(device_name: &str, refresh_rate: u32) -> ForcedRate {
+    ForcedRate {
+        device_name: device_name.to_string(),
+        refresh_rate,
+    }
+}
+
+#[test]
+fn apps_match_by_name_or_path_glob() {
+    assert!(rule("EldenRing.exe", None, 144).matches_app(GAME));
+    assert!(rule("elden*.exe", None, 144).matches_app(GAME));
+    assert!(rule("c:/games/**/*.exe", None, 144).matches_app(GAME));
+    assert!(rule(r"C:\Program Files\Adobe\*\*.exe", None, 60).matches_app(EDITOR));
+
+    // `*` stops at separators, and names only match the file name.
+    assert!(!rule(r"C:\Games\*.exe", None, 144).matches_app(GAME));
+    assert!(!rule("Game", None, 144).matches_app(GAME));
+    assert!(!rule("eldenring.ex?", None, 144).matches_app("eldenring.ex"));
+    // Many wildcards that almost match must not backtrack exponentially.
+    let path = format!(r"C:\{}\{}.exe", "a".repeat(200), "a".repeat(200));
+    assert!(!rule("*a*a*a*a*a*a*a*a*a*a*b", None, 60).matches_app(&path));
+    assert!(!rule(r"**a**a**a**a**a**a**a**a**\b*", None, 60).matches_app(&path));
+    assert!(rule(r"c:\**a*a\*a*a.exe", None, 60).matches_app(&path));
+}
+
+#[test]
+fn the_first_rule_per_monitor_wins() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let rules = [
+        rule("eldenring.exe", Some("GSM5B7F"), 144),
+        rule("*.exe", None, 60),
+        rule("eldenring.exe", Some("gsm5b7f"), 120),
+        rule("eldenring.exe", Some("SAM0F9E"), 60),
+    ];
+
+    assert_eq!(
+        forced_rates(&rules, GAME, &devices),
+        [forced(DISPLAY2, 144), forced(DISPLAY1, 60)]
+    );
+    assert_eq!(forced_rates(&rules, "notepad", &devices), []);
+}
+
+#[test]
+fn previous_rates_come_back_when_the_app_leaves_the_foreground() {
+    let backend = backend();
+    let mut overrides = RateOverrides::new();
+    let options = ChangeOptions::default();
+
+    let results = overrides.sync_with(&backend, &[forced(DISPLAY1, 144)], options);
+    assert_eq!(results[0].result, Ok(ChangeOutcome::Applied));
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+    assert_eq!(overrides.active()[0].previous, 120);
+
+    // Still in the foreground: nothing to do.
+    assert!(overrides
+        .sync_with(&backend, &[forced(DISPLAY1, 144)], options)
+        .is_empty());
+
+    // Another rule takes over; the original rate is kept for later.
+    overrides.sync_with(&backend, &[forced(DISPLAY1, 60)], options);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 60);
+    assert_eq!(overrides.active()[0].previous, 120);
+
+    let results = overrides.sync_with(&backend, &[], options);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].refresh_rate, 120);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 120);
+    assert!(overrides.active().is_empty());
+}
+
+#[test]
+fn failed_switches_are_not_restored() {
+    let backend = backend();
+    let mut overrides = RateOverrides::new();
+    let options = ChangeOptions::default();
+
+    let results = overrides.sync_with(
+        &backend,
+        &[forced(DISPLAY1, 240), forced(DISPLAY2, 144)],
+        options,
+    );
+    assert!(results[0].result.is_err());
+    assert_eq!(overrides.active().len(), 1);
+
+    overrides.restore_all_with(&backend, options);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 120);
+    assert_eq!(backend.current_mode(DISPLAY2).unwrap().frequency, 60);
+}
+
+#[test]
+fn rules_are_read_from_the_config_file() {
+    let text = "version = 1\n\n[[apps]]\napp = 'C:\\Games\\**\\*.exe'\nmonitor = \"GSM5B7F\"\nrefresh_rate = 144\n\n[[apps]]\napp = \"vlc.exe\"\nrefresh_rate = 60\n";
+    let config = Config::from_toml(text).unwrap();
+    assert_eq!(
+        config.apps,
+        [
+            rule(r"C:\Games\**\*.exe", Some("GSM5B7F"), 144),
+            rule("vlc.exe", None, 60)
+        ]
+    );
+    assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use refresh_rate_windows_rs::revert::prompt_text;
+use refresh_rate_windows_rs::{
+    AutoRevert, ChangeOptions, ChangeOutcome, DisplayError, FakeAdapter, FakeBackend, Persistence,
+    RefreshRate, RequestedChange, RevertTick,
+};
+
+use common::{modes, DISPLAY1};
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+fn backend() -> FakeBackend {
+    FakeBackend::new().with_adapter(
+        FakeAdapter::new(DISPLAY1)
+            .primary()
+            .monitor("Generic PnP Monitor")
+            .modes(&modes(2560, 1440, &[60, 59, 120, 144]))
+            .exact_rates(&[
+                RefreshRate::integer(60),
+                RefreshRate::ntsc(60),
+                RefreshRate::integer(120),
+                RefreshRate::integer(144),
+            ]),
+    )
+}
+
+fn current_hz(backend: &FakeBackend) -> u32 {
+    backend
+        .applied_rates()
+        .last()
+        .map_or(60, |c| c.rate.dm_frequency())
+}
+
+#[test]
+fn reverts_once_the_countdown_runs_out() {
+    let backend = backend();
+    let mut auto_revert = AutoRevert::new(TIMEOUT);
+    let start = Instant::now();
+
+    let options = ChangeOptions::with_persistence(Persistence::Persistent);
+    assert_eq!(
+        auto_revert.apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(144),
+            options,
+            start
+        ),
+        Ok(ChangeOutcome::Applied)
+    );
+    let change = auto_revert.pending().unwrap();
+    assert_eq!(change.previous_mode.frequency, 60);
+    assert_eq!(change.persistence, Persistence::Persistent);
+    assert_eq!(
+        prompt_text(change, TIMEOUT),
+        "Keep 144 Hz on \\\\.\\DISPLAY1?\nReverting in 15 seconds."
+    );
+    assert_eq!(current_hz(&backend), 144);
+
+    assert_eq!(
+        auto_revert.tick_with(&backend, start + Duration::from_millis(5500)),
+        RevertTick::Waiting {
+            remaining: Duration::from_millis(9500)
+        }
+    );
+    assert!(backend.applied().is_empty());
+
+    let tick = auto_revert.tick_with(&backend, start + TIMEOUT);
+    assert!(matches!(tick, RevertTick::Reverted { result: Ok(_), .. }));
+    let restored = backend.applied();
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].mode.frequency, 60);
+    assert_eq!(restored[0].flags, Persistence::Persistent.cds_flags());
+    assert_eq!(
+        auto_revert.tick_with(&backend, start + TIMEOUT),
+        RevertTick::Idle
+    );
+}
+
+#[test]
+fn confirming_keeps_the_change() {
+    let backend = backend();
+    let mut auto_revert = AutoRevert::new(TIMEOUT);
+    let start = Instant::now();
+
+    auto_revert
+        .apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(120),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    let kept = auto_revert.confirm().map(|c| c.requested);
+    assert_eq!(
+        kept,
+        Some(RequestedChange::RefreshRate(RefreshRate::integer(120)))
+    );
+    // What a rule forcing the monitor should put back afterwards.
+    assert_eq!(kept.and_then(|c| c.refresh_rate()), Some(120));
+    assert_eq!(
+        auto_revert.tick_with(&backend, start + TIMEOUT),
+        RevertTick::Idle
+    );
+    assert_eq!(auto_revert.revert_with(&backend), None);
+    assert!(backend.applied().is_empty());
+    assert_eq!(current_hz(&backend), 120);
+}
+
+#[test]
+fn restores_exact_fractional_rates() {
+    let backend = backend();
+    let mut auto_revert = AutoRevert::new(TIMEOUT);
+    let start = Instant::now();
+
+    auto_revert
+        .apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::ntsc(60),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    auto_revert.confirm();
+    auto_revert
+        .apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(144),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    assert_eq!(
+        auto_revert.pending().unwrap().previous_rate,
+        Some(RefreshRate::ntsc(60))
+    );
+
+    // Starting another change puts the unconfirmed one back first.
+    auto_revert
+        .apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(120),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    let rates: Vec<RefreshRate> = backend.applied_rates().iter().map(|c| c.rate).collect();
+    assert_eq!(
+        rates,
+        [
+            RefreshRate::ntsc(60),
+            RefreshRate::integer(144),
+            RefreshRate::ntsc(60),
+            RefreshRate::integer(120),
+        ]
+    );
+
+    assert!(matches!(auto_revert.revert_with(&backend), Some(Ok(_))));
+    assert_eq!(
+        backend.applied_rates().last().unwrap().rate,
+        RefreshRate::ntsc(60)
+    );
+}
+
+#[test]
+fn leaves_nothing_pending_without_a_change() {
+    let backend = backend();
+    let mut auto_revert = AutoRevert::new(TIMEOUT);
+    let start = Instant::now();
+
+    assert_eq!(
+        auto_revert.apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(144),
+            ChangeOptions::dry_run(),
+            start
+        ),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(
+        auto_revert.apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(60),
+            ChangeOptions::default(),
+            start
+        ),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert!(auto_revert.pending().is_none());
+
+    // Without a snapshot the change is not attempted.
+    backend.fail_current_mode(DISPLAY1, 1168);
+    assert_eq!(
+        auto_revert.apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(144),
+            ChangeOptions::default(),
+            start
+        ),
+        Err(DisplayError::CurrentModeUnavailable {
+            device_name: DISPLAY1.to_string(),
+            os_error: 1168,
+        })
+    );
+    assert!(backend.applied_rates().is_empty());
+    backend.clear_failures();
+
+    // A zero timeout keeps changes without asking.
+    auto_revert.set_timeout(Duration::ZERO);
+    auto_revert
+        .apply_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(144),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    assert!(auto_revert.pending().is_none());
+    assert_eq!(current_hz(&backend), 144);
+}
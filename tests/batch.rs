@@ -0,0 +1,190 @@
+mod common;
+
+use refresh_rate_windows_rs::backend::{
+    CDS_NORESET, CDS_UPDATEREGISTRY, DISP_CHANGE_BADMODE, DISP_CHANGE_FAILED,
+};
+use refresh_rate_windows_rs::{
+    ChangeOptions, ChangeOutcome, DisplayBackend, DisplayBatch, DisplayError, DisplayMode,
+    FakeAdapter, FakeBackend, Persistence,
+};
+
+use common::{desktop, modes, DISPLAY1, DISPLAY2, DISPLAY3};
+
+fn backend() -> FakeBackend {
+    FakeBackend::new()
+        .with_adapter(desktop().monitor("Generic PnP Monitor"))
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor("Generic PnP Monitor")
+                .modes(&modes(1920, 1080, &[60, 75])),
+        )
+        .with_adapter(
+            FakeAdapter::new(DISPLAY3)
+                .monitor("Generic PnP Monitor")
+                .modes(&modes(1920, 1080, &[60, 75])),
+        )
+}
+
+fn persistent() -> ChangeOptions {
+    ChangeOptions::with_persistence(Persistence::Persistent)
+}
+
+fn current_hz(backend: &FakeBackend, device_name: &str) -> u32 {
+    backend.current_mode(device_name).unwrap().frequency
+}
+
+#[test]
+fn stages_every_device_and_commits_once() {
+    let backend = backend();
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 144)
+        .refresh_rate(DISPLAY2, 75)
+        .refresh_rate(DISPLAY3, 60);
+
+    assert_eq!(
+        batch.apply_with(&backend, persistent()),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(backend.commits(), 1);
+    // DISPLAY3 is already at 60 Hz, so only two devices are staged.
+    let staged: Vec<(String, u32, u32)> = backend
+        .applied()
+        .into_iter()
+        .map(|c| (c.device_name, c.mode.frequency, c.flags))
+        .collect();
+    let flags = CDS_NORESET | CDS_UPDATEREGISTRY;
+    assert_eq!(
+        staged,
+        [
+            (DISPLAY1.to_string(), 144, flags),
+            (DISPLAY2.to_string(), 75, flags),
+        ]
+    );
+    assert_eq!(current_hz(&backend, DISPLAY1), 144);
+    assert_eq!(current_hz(&backend, DISPLAY2), 75);
+
+    assert_eq!(
+        batch.apply_with(&backend, persistent()),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert_eq!(backend.commits(), 1);
+}
+
+#[test]
+fn rolls_back_staged_devices_when_one_fails() {
+    let backend = backend();
+    backend.fail_apply(DISPLAY3, DISP_CHANGE_BADMODE);
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 144)
+        .refresh_rate(DISPLAY2, 75)
+        .mode(DISPLAY3, DisplayMode::new(1920, 1080, 32, 75));
+
+    assert_eq!(
+        batch.apply_with(&backend, persistent()),
+        Err(DisplayError::BadMode {
+            device_name: DISPLAY3.to_string()
+        })
+    );
+    assert_eq!(backend.commits(), 0);
+    let staged: Vec<(String, u32)> = backend
+        .applied()
+        .into_iter()
+        .map(|c| (c.device_name, c.mode.frequency))
+        .collect();
+    assert_eq!(
+        staged,
+        [
+            (DISPLAY1.to_string(), 144),
+            (DISPLAY2.to_string(), 75),
+            (DISPLAY1.to_string(), 60),
+            (DISPLAY2.to_string(), 60),
+        ]
+    );
+    assert_eq!(current_hz(&backend, DISPLAY1), 60);
+    assert_eq!(current_hz(&backend, DISPLAY2), 60);
+
+    // Unknown rates are caught before anything is staged.
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 144)
+        .refresh_rate(DISPLAY2, 144);
+    assert!(matches!(
+        batch.apply_with(&backend, persistent()),
+        Err(DisplayError::UnsupportedMode { .. })
+    ));
+    assert_eq!(backend.applied().len(), 4);
+}
+
+#[test]
+fn reports_a_failed_commit() {
+    let backend = backend();
+    backend.fail_commit(DISP_CHANGE_FAILED);
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 120)
+        .refresh_rate(DISPLAY2, 75);
+
+    let error = batch.apply_with(&backend, persistent()).unwrap_err();
+    assert_eq!(
+        error,
+        DisplayError::CommitFailed {
+            device_names: vec![DISPLAY1.to_string(), DISPLAY2.to_string()],
+            code: DISP_CHANGE_FAILED,
+        }
+    );
+    assert_eq!(error.change_code(), Some(DISP_CHANGE_FAILED));
+    assert_eq!(current_hz(&backend, DISPLAY1), 60);
+
+    // Dry runs only ask the driver.
+    backend.clear_failures();
+    assert_eq!(
+        batch.apply_with(&backend, ChangeOptions::dry_run()),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(backend.commits(), 0);
+    assert_eq!(current_hz(&backend, DISPLAY2), 60);
+}
+
+#[test]
+fn temporary_batches_commit_once_without_the_registry() {
+    let backend = backend();
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 144)
+        .refresh_rate(DISPLAY2, 75);
+
+    assert_eq!(
+        batch.apply_with(&backend, ChangeOptions::default()),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(backend.commits(), 1);
+    let applied = backend.applied();
+    assert_eq!(applied.len(), 2);
+    assert!(applied.iter().all(|c| c.flags == CDS_NORESET));
+    assert_eq!(current_hz(&backend, DISPLAY1), 144);
+    assert_eq!(current_hz(&backend, DISPLAY2), 75);
+
+    // A device refusing its mode mid-batch rolls the others back unapplied.
+    backend.fail_apply(DISPLAY2, DISP_CHANGE_BADMODE);
+    let batch = DisplayBatch::new()
+        .refresh_rate(DISPLAY1, 60)
+        .refresh_rate(DISPLAY2, 60)
+        .refresh_rate(DISPLAY3, 75);
+    assert_eq!(
+        batch.apply_with(&backend, ChangeOptions::default()),
+        Err(DisplayError::BadMode {
+            device_name: DISPLAY2.to_string()
+        })
+    );
+    assert_eq!(backend.commits(), 1);
+    let rolled_back: Vec<(String, u32, u32)> = backend.applied()[2..]
+        .iter()
+        .map(|c| (c.device_name.clone(), c.mode.frequency, c.flags))
+        .collect();
+    assert_eq!(
+        rolled_back,
+        [
+            (DISPLAY1.to_string(), 60, CDS_NORESET),
+            (DISPLAY1.to_string(), 144, CDS_NORESET),
+        ]
+    );
+    assert_eq!(current_hz(&backend, DISPLAY1), 144);
+    assert_eq!(current_hz(&backend, DISPLAY3), 60);
+}
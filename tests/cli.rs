@@ -0,0 +1,183 @@
+mod common;
+
+use refresh_rate_windows_rs::cli::{
+    exit_code, main_with, CliError, Command, Invocation, EXIT_CHANGE_FAILED,
+    EXIT_MONITOR_NOT_FOUND, EXIT_RESTART_REQUIRED, EXIT_SUCCESS, EXIT_UNSUPPORTED, EXIT_USAGE,
+};
+use refresh_rate_windows_rs::{
+    ChangeOptions, DisplayBackend, DisplayError, FakeBackend, Persistence, RefreshRate,
+};
+
+use common::{desktop, tv, DISPLAY1, DISPLAY2};
+
+const DISP_CHANGE_RESTART: i32 = 1;
+const DISP_CHANGE_FAILED: i32 = -1;
+const ERROR_INVALID_PARAMETER: u32 = 87;
+
+fn backend() -> FakeBackend {
+    FakeBackend::new()
+        .with_adapter(desktop().monitor_with_id("Dell U2720Q", "DEL4123"))
+        .with_adapter(tv(DISPLAY2).monitor_with_id("Living Room TV", "SAM0F9E"))
+}
+
+fn parse(args: &[&str]) -> Result<Command, CliError> {
+    Invocation::parse(args).map(|invocation| invocation.command)
+}
+
+/// Runs a command line, returning the exit code, stdout and stderr.
+fn run(backend: &FakeBackend, args: &str) -> (i32, String, String) {
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let code = main_with(backend, args.split_whitespace(), &mut out, &mut err);
+    (
+        code,
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(err).unwrap(),
+    )
+}
+
+#[test]
+fn commands_are_parsed() {
+    assert_eq!(
+        parse(&["set", "2", "59.94 Hz", "--persistence=global"]),
+        Ok(Command::Set {
+            monitor: "2".to_string(),
+            rate: RefreshRate::ntsc(60),
+            options: ChangeOptions::with_persistence(Persistence::Global),
+        })
+    );
+    assert_eq!(parse(&["get"]), Ok(Command::Get { monitor: None }));
+    assert_eq!(parse(&[]), Ok(Command::Help));
+    assert_eq!("144".parse(), Ok(RefreshRate::integer(144)));
+    assert_eq!("23.976".parse(), Ok(RefreshRate::ntsc(24)));
+    assert!("fast".parse::<RefreshRate>().is_err());
+
+    for args in [
+        &["modes"][..],
+        &["set", "1", "0"],
+        &["get", "1", "2"],
+        &["reboot"],
+    ] {
+        match parse(args) {
+            Err(error @ CliError::Usage(_)) => assert_eq!(error.exit_code(), EXIT_USAGE),
+            other => panic!("{:?} parsed as {:?}", args, other),
+        }
+    }
+}
+
+#[test]
+fn queries_report_monitors_and_rates() {
+    let backend = backend();
+
+    let (code, out, _) = run(&backend, "list");
+    assert_eq!(code, EXIT_SUCCESS);
+    assert_eq!(
+        out,
+        "1: \\\\.\\DISPLAY1 Dell U2720Q (DEL4123), 2560x1440, 60 Hz, primary\n\
+         2: \\\\.\\DISPLAY2 Living Room TV (SAM0F9E), 3840x2160, 60 Hz\n"
+    );
+    assert_eq!(run(&backend, "get DEL4123").1, "60 Hz\n");
+    assert_eq!(run(&backend, "primary").1, format!("{}\n", DISPLAY1));
+    assert_eq!(
+        run(&backend, "modes display2").1,
+        "59.94 Hz\n60 Hz (current)\n"
+    );
+
+    let (code, out, err) = run(&backend, "get GSM5B7F");
+    assert_eq!(code, EXIT_MONITOR_NOT_FOUND);
+    assert_eq!(out, "");
+    assert_eq!(err, "error: monitor GSM5B7F is not connected\n");
+}
+
+#[test]
+fn list_keeps_going_past_an_unreadable_monitor() {
+    let backend = backend();
+    backend.fail_current_mode(DISPLAY1, ERROR_INVALID_PARAMETER);
+    backend.fail_config(DISPLAY1, ERROR_INVALID_PARAMETER);
+
+    let (code, out, _) = run(&backend, "list");
+    assert_eq!(code, EXIT_SUCCESS);
+    assert_eq!(
+        out,
+        "1: \\\\.\\DISPLAY1 Dell U2720Q (DEL4123), unknown rate, primary\n\
+         2: \\\\.\\DISPLAY2 Living Room TV (SAM0F9E), 3840x2160, 60 Hz\n"
+    );
+
+    let (code, out, _) = run(&backend, "list --json");
+    assert_eq!(code, EXIT_SUCCESS);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(!lines[0].contains("\"rate\""));
+    assert!(lines[1].contains("\"rate\""));
+}
+
+#[test]
+fn get_reports_every_readable_monitor_before_failing() {
+    let backend = backend();
+    backend.fail_current_mode(DISPLAY1, ERROR_INVALID_PARAMETER);
+    backend.fail_config(DISPLAY1, ERROR_INVALID_PARAMETER);
+    let error = DisplayError::CurrentModeUnavailable {
+        device_name: DISPLAY1.to_string(),
+        os_error: ERROR_INVALID_PARAMETER,
+    };
+
+    let (code, out, err) = run(&backend, "get");
+    assert_eq!(code, exit_code(&error));
+    assert_eq!(
+        out,
+        format!("{}: unknown rate\n{}: 60 Hz\n", DISPLAY1, DISPLAY2)
+    );
+    assert_eq!(err, format!("error: {}\n", error));
+
+    let (code, out, err) = run(&backend, "get --json");
+    assert_eq!(code, exit_code(&error));
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("DISPLAY2"));
+    assert!(err.contains("\"type\":\"error\""));
+}
+
+#[test]
+fn set_switches_integer_and_exact_rates() {
+    let backend = backend();
+
+    let (code, out, _) = run(&backend, "set 1 144");
+    assert_eq!(code, EXIT_SUCCESS);
+    assert_eq!(out, format!("{}: switched to 144 Hz\n", DISPLAY1));
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+
+    assert_eq!(run(&backend, "set SAM0F9E 59.94").0, EXIT_SUCCESS);
+    assert_eq!(run(&backend, "get 2").1, "59.94 Hz\n");
+    // DEVMODE reports 59.94 Hz as 59, which is not the exact 59 Hz asked for.
+    assert_eq!(run(&backend, "set 2 59").0, EXIT_UNSUPPORTED);
+    let (code, out, _) = run(&backend, "set 2 60");
+    assert_eq!(code, EXIT_SUCCESS);
+    assert_eq!(out, format!("{}: switched to 60 Hz\n", DISPLAY2));
+    assert_eq!(run(&backend, "get 2").1, "60 Hz\n");
+
+    let (code, out, _) = run(&backend, "set 1 120 --dry-run");
+    assert_eq!(code, EXIT_SUCCESS);
+    assert_eq!(out, format!("{}: would switch to 120 Hz\n", DISPLAY1));
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+}
+
+#[test]
+fn failures_map_to_distinct_exit_codes() {
+    let backend = backend();
+
+    assert_eq!(run(&backend, "set 1 75").0, EXIT_UNSUPPORTED);
+    assert_eq!(run(&backend, "set 3 60").0, EXIT_MONITOR_NOT_FOUND);
+    assert_eq!(run(&backend, "set 1 144 --force").0, EXIT_USAGE);
+
+    backend.fail_apply(DISPLAY1, DISP_CHANGE_FAILED);
+    assert_eq!(run(&backend, "set 1 144").0, EXIT_CHANGE_FAILED);
+    backend.fail_apply(DISPLAY1, DISP_CHANGE_RESTART);
+    assert_eq!(run(&backend, "set 1 144").0, EXIT_RESTART_REQUIRED);
+
+    assert_eq!(
+        exit_code(&DisplayError::MonitorNotFound {
+            monitor: "1".to_string()
+        }),
+        EXIT_MONITOR_NOT_FOUND
+    );
+}
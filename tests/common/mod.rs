@@ -0,0 +1,43 @@
+//! Displays shared by the integration tests. Each test file starts from these
+//! and adds only what it is about.
+
+// Every test file is its own crate, and none of them uses all of this.
+#![allow(dead_code)]
+
+use refresh_rate_windows_rs::{DisplayMode, FakeAdapter, FakeBackend, RefreshRate};
+
+pub const DISPLAY1: &str = r"\\.\DISPLAY1";
+pub const DISPLAY2: &str = r"\\.\DISPLAY2";
+pub const DISPLAY3: &str = r"\\.\DISPLAY3";
+
+/// 32-bit `width`x`height` modes, one per rate in `rates`.
+pub fn modes(width: u32, height: u32, rates: &[u32]) -> Vec<DisplayMode> {
+    rates
+        .iter()
+        .map(|&hz| DisplayMode::new(width, height, 32, hz))
+        .collect()
+}
+
+/// The modes of `desktop`: 2560x1440 at 60, 120 and 144 Hz.
+pub fn desktop_modes() -> Vec<DisplayMode> {
+    modes(2560, 1440, &[60, 120, 144])
+}
+
+/// The primary `DISPLAY1` output at 2560x1440 and 60 Hz, with no monitor so
+/// tests can attach the one they need.
+pub fn desktop() -> FakeAdapter {
+    FakeAdapter::new(DISPLAY1).primary().modes(&desktop_modes())
+}
+
+/// A 4K output at 60 Hz that also runs 59.94 Hz, which `EnumDisplaySettingsW`
+/// lists as 59.
+pub fn tv(device_name: &str) -> FakeAdapter {
+    FakeAdapter::new(device_name)
+        .modes(&modes(3840, 2160, &[60, 59]))
+        .exact_rates(&[RefreshRate::integer(60), RefreshRate::ntsc(60)])
+}
+
+/// `desktop` with a generic monitor, on its own.
+pub fn backend() -> FakeBackend {
+    FakeBackend::new().with_adapter(desktop().monitor("Generic PnP Monitor"))
+}
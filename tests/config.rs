@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::LevelFilter;
+use refresh_rate_windows_rs::config::{migrate, CONFIG_VERSION};
+use refresh_rate_windows_rs::{Config, ConfigError, Persistence};
+
+/// A fresh directory for one test's config file.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("refresh-rate-windows-rs-tests")
+        .join(name);
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn defaults_are_written_on_first_run() {
+    let path = temp_dir("first-run").join("config.toml");
+
+    assert_eq!(Config::load_or_create(&path), Ok(Config::default()));
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("version = 1\n"), "{}", written);
+    assert_eq!(Config::from_toml(&written), Ok(Config::default()));
+}
+
+#[test]
+fn settings_round_trip() {
+    let text = r#"
+version = 1
+persistence = "persistent"
+revert_timeout = 0
+log_level = "debug"
+tooltip = "Displays"
+
+[[profiles]]
+name = "Gaming"
+
+[[profiles.monitors]]
+monitor = "DEL4123"
+refresh_rate = 144
+"#;
+    let config = Config::from_toml(text).unwrap();
+    assert_eq!(config.persistence, Persistence::Persistent);
+    assert_eq!(config.revert_timeout, Duration::ZERO);
+    assert_eq!(config.log_level, LevelFilter::Debug);
+    assert_eq!(config.tooltip, "Displays");
+    assert_eq!(config.profiles[0].monitors[0].refresh_rate, Some(144));
+    assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+
+    // Settings that are left out take their defaults.
+    assert_eq!(Config::from_toml("version = 1"), Ok(Config::default()));
+}
+
+#[test]
+fn older_files_are_migrated_and_written_back() {
+    let path = temp_dir("migrate").join("config.toml");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    // Before versioning, the file only held profiles.
+    let legacy = "[[profiles]]\nname = \"Office\"\n";
+    fs::write(&path, legacy).unwrap();
+
+    let config = Config::load_or_create(&path).unwrap();
+    assert_eq!(config.profiles[0].name, "Office");
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("version = 1\n"), "{}", written);
+
+    let mut value = toml::from_str(legacy).unwrap();
+    assert_eq!(migrate(&mut value), Ok(0));
+    assert_eq!(value["version"].as_integer(), Some(CONFIG_VERSION));
+    assert_eq!(
+        Config::from_toml("version = 2"),
+        Err(ConfigError::UnsupportedVersion(2))
+    );
+}
+
+#[test]
+fn saving_one_setting_keeps_the_rest_of_the_file() {
+    let path = temp_dir("save-setting").join("config.toml");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let text = "version = 1\ntooltip = \"Displays\"\nfuture_setting = true\n";
+    fs::write(&path, text).unwrap();
+
+    let mut config = Config::load_or_create(&path).unwrap();
+    config.persistence = Persistence::Persistent;
+    // Only `persistence` is written, not what else changed in memory.
+    config.tooltip = "Changed".to_string();
+    config.save_setting(&path, "persistence").unwrap();
+
+    let written = fs::read_to_string(&path).unwrap();
+    assert!(written.starts_with("version = 1\n"), "{}", written);
+    let value: toml::Value = toml::from_str(&written).unwrap();
+    assert_eq!(value["persistence"].as_str(), Some("persistent"));
+    assert_eq!(value["tooltip"].as_str(), Some("Displays"));
+    assert_eq!(value["future_setting"].as_bool(), Some(true));
+    assert_eq!(value.as_table().unwrap().len(), 4);
+
+    // Without a file the whole config is written.
+    fs::remove_file(&path).unwrap();
+    config.save_setting(&path, "persistence").unwrap();
+    assert_eq!(Config::load_or_create(&path), Ok(config));
+}
+
+#[test]
+fn errors_point_at_the_offending_line_and_column() {
+    assert_eq!(
+        Config::from_toml("version = 1\npersistence = \"forever\"\n"),
+        Err(ConfigError::Invalid {
+            path: "persistence".to_string(),
+            expected: "temporary, persistent or global",
+            position: Some((2, 15)),
+        })
+    );
+
+    let text = "version = 1\n\n[[profiles]]\nname = \"Gaming\"\n\n[[profiles.monitors]]\nmonitor = \"DEL4123\"\nrefresh_rate = -1\n";
+    let error = Config::from_toml(text).unwrap_err();
+    assert_eq!(
+        error,
+        ConfigError::Invalid {
+            path: "profiles[0].monitors[0].refresh_rate".to_string(),
+            expected: "a non-negative integer",
+            position: Some((8, 16)),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "line 8, column 16: `profiles[0].monitors[0].refresh_rate` must be a non-negative integer"
+    );
+
+    match Config::from_toml("version = 1\ntooltip = \"unterminated\n") {
+        Err(ConfigError::Syntax { line, column, .. }) => assert_eq!((line, column), (2, 24)),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    // A broken file is reported, not replaced with defaults.
+    let path = temp_dir("broken").join("config.toml");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, "revert_timeout = \"soon\"\n").unwrap();
+    assert!(matches!(
+        Config::load_or_create(&path),
+        Err(ConfigError::Invalid { .. })
+    ));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "revert_timeout = \"soon\"\n"
+    );
+}
@@ -0,0 +1,116 @@
+use refresh_rate_windows_rs::backend::{MonitorDevNode, RawDisplayDevice};
+use refresh_rate_windows_rs::devnode::{
+    instance_id_from_interface_path, match_devnode, split_monitor_device_id,
+};
+use refresh_rate_windows_rs::{
+    devnode_for, get_all_display_devices_with, FakeAdapter, FakeBackend,
+};
+
+fn named(monitor: &RawDisplayDevice, friendly_name: &str) -> MonitorDevNode {
+    MonitorDevNode {
+        friendly_name: Some(friendly_name.to_string()),
+        ..devnode_for(monitor)
+    }
+}
+
+fn display_names(backend: &FakeBackend) -> Vec<String> {
+    get_all_display_devices_with(backend)
+        .unwrap()
+        .into_iter()
+        .map(|device| device.display_name)
+        .collect()
+}
+
+#[test]
+fn parses_interface_paths_and_device_ids() {
+    assert_eq!(
+        instance_id_from_interface_path(
+            r"\\?\DISPLAY#DEL4123#5&1a2b3c&0&UID4353#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}"
+        )
+        .as_deref(),
+        Some(r"DISPLAY\DEL4123\5&1a2b3c&0&UID4353")
+    );
+    assert_eq!(instance_id_from_interface_path(""), None);
+    assert_eq!(
+        split_monitor_device_id(r"MONITOR\DEL4123\{4d36e96e-e325-11ce-bfc1-08002be10318}\0001"),
+        Some((
+            r"MONITOR\DEL4123",
+            r"{4d36e96e-e325-11ce-bfc1-08002be10318}\0001"
+        ))
+    );
+}
+
+#[test]
+fn identical_monitors_get_their_own_friendly_names() {
+    let left = FakeAdapter::new(r"\\.\DISPLAY1")
+        .primary()
+        .monitor_with_id("Generic PnP Monitor", "DEL4123");
+    let right = FakeAdapter::new(r"\\.\DISPLAY2").monitor_with_id("Generic PnP Monitor", "DEL4123");
+    // SetupAPI order does not follow adapter order.
+    let backend = FakeBackend::new()
+        .with_devnode(named(&right.monitors[0], "DELL U2720Q (right)"))
+        .with_devnode(named(&left.monitors[0], "DELL U2720Q (left)"))
+        .with_adapter(left)
+        .with_adapter(right);
+
+    assert_eq!(
+        display_names(&backend),
+        ["DELL U2720Q (left)", "DELL U2720Q (right)"]
+    );
+}
+
+#[test]
+fn falls_back_to_driver_key_then_unique_hardware_id() {
+    let mut adapter = FakeAdapter::new(r"\\.\DISPLAY1")
+        .monitor_with_id("Generic PnP Monitor", "DEL4123")
+        .monitor_with_id("Generic PnP Monitor", "SAM0F99");
+    let by_driver_key = named(&adapter.monitors[0], "DELL U2720Q");
+    let by_hardware_id = MonitorDevNode {
+        driver_key: None,
+        ..named(&adapter.monitors[1], "SAMSUNG Odyssey")
+    };
+    for monitor in &mut adapter.monitors {
+        monitor.interface_path.clear();
+    }
+
+    assert_eq!(
+        match_devnode(&adapter.monitors[0], std::slice::from_ref(&by_driver_key)),
+        Some(&by_driver_key)
+    );
+    assert_eq!(
+        match_devnode(&adapter.monitors[1], std::slice::from_ref(&by_hardware_id)),
+        Some(&by_hardware_id)
+    );
+
+    // Two devnodes with the same hardware ID and nothing else to go on: no guess.
+    let twin = MonitorDevNode {
+        friendly_name: Some("Other".to_string()),
+        ..by_hardware_id.clone()
+    };
+    assert_eq!(
+        match_devnode(&adapter.monitors[1], &[by_hardware_id, twin]),
+        None
+    );
+}
+
+#[test]
+fn unmatched_or_generic_devnodes_keep_the_device_string() {
+    let adapter = FakeAdapter::new(r"\\.\DISPLAY1")
+        .monitor("LG ULTRAGEAR")
+        .monitor("Generic PnP Monitor");
+    let generic = devnode_for(&adapter.monitors[1]);
+    let described = MonitorDevNode {
+        friendly_name: Some("Generic PnP Monitor".to_string()),
+        device_desc: Some("BenQ EX2780Q".to_string()),
+        ..devnode_for(&adapter.monitors[0])
+    };
+    let backend = FakeBackend::new()
+        .with_devnode(generic)
+        .with_devnode(described)
+        .with_adapter(adapter);
+
+    assert_eq!(
+        display_names(&backend),
+        ["BenQ EX2780Q", "Generic PnP Monitor"]
+    );
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use refresh_rate_windows_rs::{
+    set_display_refresh_rate_with, ChangeOptions, DisplayEvent, DisplayState, DisplayTracker,
+    FakeAdapter, FakeBackend,
+};
+
+use common::{modes, DISPLAY1, DISPLAY2};
+
+fn adapter(device_name: &str) -> FakeAdapter {
+    FakeAdapter::new(device_name)
+        .monitor("Generic PnP Monitor")
+        .modes(&modes(2560, 1440, &[60, 144]))
+}
+
+#[test]
+fn reports_nothing_without_changes() {
+    let backend = FakeBackend::new().with_adapter(adapter(DISPLAY1).primary());
+    let mut tracker = DisplayTracker::new_with(&backend).unwrap();
+    assert_eq!(tracker.state().monitors.len(), 1);
+    assert_eq!(tracker.refresh_with(&backend), Ok(vec![]));
+}
+
+#[test]
+fn reports_mode_changes() {
+    let backend = FakeBackend::new().with_adapter(adapter(DISPLAY1).primary());
+    let mut tracker = DisplayTracker::new_with(&backend).unwrap();
+
+    set_display_refresh_rate_with(&backend, DISPLAY1, 144, ChangeOptions::default()).unwrap();
+    let events = tracker.refresh_with(&backend).unwrap();
+    assert_eq!(events.len(), 1);
+    let DisplayEvent::ModeChanged { device, old, new } = &events[0] else {
+        panic!("expected a mode change, got {:?}", events[0]);
+    };
+    assert_eq!(device.device_name, DISPLAY1);
+    assert_eq!((old.frequency, new.frequency), (60, 144));
+
+    // Events are reported once.
+    assert_eq!(tracker.refresh_with(&backend), Ok(vec![]));
+}
+
+#[test]
+fn reports_monitors_coming_and_going() {
+    let backend = FakeBackend::new().with_adapter(adapter(DISPLAY1).primary());
+    let mut tracker = DisplayTracker::new_with(&backend).unwrap();
+
+    backend.add_adapter(adapter(DISPLAY2));
+    let events = tracker.refresh_with(&backend).unwrap();
+    assert!(matches!(
+        events.as_slice(),
+        [DisplayEvent::MonitorAdded(device)] if device.device_name == DISPLAY2
+    ));
+
+    // Unplugging the primary moves it to the remaining monitor.
+    backend.remove_adapter(DISPLAY1);
+    backend.set_primary(DISPLAY2);
+    let events = tracker.refresh_with(&backend).unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        &events[0],
+        DisplayEvent::MonitorRemoved(device) if device.device_name == DISPLAY1
+    ));
+    let DisplayEvent::PrimaryChanged { old, new } = &events[1] else {
+        panic!("expected a primary change, got {:?}", events[1]);
+    };
+    assert_eq!(old.as_ref().map(|d| d.device_name.as_str()), Some(DISPLAY1));
+    assert_eq!(new.as_ref().map(|d| d.device_name.as_str()), Some(DISPLAY2));
+}
+
+#[test]
+fn matches_monitors_by_interface_path() {
+    let backend = FakeBackend::new()
+        .with_adapter(adapter(DISPLAY1).primary())
+        .with_adapter(adapter(DISPLAY2));
+    let before = DisplayState::capture_with(&backend).unwrap();
+
+    // The same snapshot in another order is no change.
+    let mut reordered = before.clone();
+    reordered.monitors.reverse();
+    assert_eq!(before.diff(&reordered), vec![]);
+
+    // An unreadable mode is not reported as a change.
+    let mut unreadable = before.clone();
+    unreadable.monitors[1].mode = None;
+    assert_eq!(before.diff(&unreadable), vec![]);
+
+    // A different monitor on the same output is a replacement.
+    let mut replaced = before.clone();
+    replaced.monitors[1]
+        .device
+        .interface_path
+        .push_str("-other");
+    let events = before.diff(&replaced);
+    assert!(matches!(
+        events.as_slice(),
+        [
+            DisplayEvent::MonitorRemoved(_),
+            DisplayEvent::MonitorAdded(_)
+        ]
+    ));
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use refresh_rate_windows_rs::revert::prompt_text;
+use refresh_rate_windows_rs::tray::{
+    MenuCommand, TrayMenu, MENU_EXIT_ID, MENU_ITEMS_PER_MONITOR, MENU_MAX_MONITORS,
+};
+use refresh_rate_windows_rs::{
+    resolution_modes, set_display_mode_with, AutoRevert, ChangeOptions, ChangeOutcome,
+    DisplayBackend, DisplayError, DisplayMode, FakeAdapter, FakeBackend, RequestedChange,
+    RevertTick,
+};
+
+use common::{DISPLAY1, DISPLAY2};
+
+/// A 4K panel at 3840x2160@60, also offering 1440p up to 120 Hz and 1080p up
+/// to 144 Hz.
+fn modes() -> Vec<DisplayMode> {
+    vec![
+        DisplayMode::new(3840, 2160, 32, 60),
+        DisplayMode::new(1920, 1080, 32, 60),
+        DisplayMode::new(1920, 1080, 32, 144),
+        DisplayMode::new(2560, 1440, 32, 120),
+        DisplayMode::new(3840, 2160, 32, 30),
+        DisplayMode::new(1280, 720, 16, 60),
+    ]
+}
+
+fn backend() -> FakeBackend {
+    FakeBackend::new().with_adapter(
+        FakeAdapter::new(DISPLAY1)
+            .primary()
+            .monitor("Generic PnP Monitor")
+            .modes(&modes()),
+    )
+}
+
+#[test]
+fn sets_resolution_and_rate_in_one_change() {
+    let backend = backend();
+
+    assert_eq!(
+        set_display_mode_with(
+            &backend,
+            DISPLAY1,
+            2560,
+            1440,
+            120,
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Applied)
+    );
+    let applied = backend.applied();
+    assert_eq!(applied.len(), 1);
+    assert_eq!(applied[0].mode, DisplayMode::new(2560, 1440, 32, 120));
+
+    assert_eq!(
+        set_display_mode_with(
+            &backend,
+            DISPLAY1,
+            2560,
+            1440,
+            120,
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert_eq!(
+        set_display_mode_with(
+            &backend,
+            DISPLAY1,
+            2560,
+            1440,
+            144,
+            ChangeOptions::default()
+        ),
+        Err(DisplayError::UnsupportedMode {
+            device_name: DISPLAY1.to_string(),
+            mode: DisplayMode::new(2560, 1440, 32, 144),
+        })
+    );
+    // Other colour depths are not picked up.
+    assert!(matches!(
+        set_display_mode_with(&backend, DISPLAY1, 1280, 720, 60, ChangeOptions::default()),
+        Err(DisplayError::UnsupportedMode { .. })
+    ));
+    assert_eq!(
+        set_display_mode_with(
+            &backend,
+            DISPLAY1,
+            1920,
+            1080,
+            144,
+            ChangeOptions::dry_run()
+        ),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(backend.applied().len(), 1);
+}
+
+#[test]
+fn lists_one_mode_per_resolution() {
+    let current = DisplayMode::new(3840, 2160, 32, 60);
+    let resolutions: Vec<(u32, u32, u32)> = resolution_modes(&modes(), &current)
+        .iter()
+        .map(|mode| (mode.width, mode.height, mode.frequency))
+        .collect();
+    // The current rate where listed, the highest rate otherwise.
+    assert_eq!(
+        resolutions,
+        [(3840, 2160, 60), (2560, 1440, 120), (1920, 1080, 60)]
+    );
+}
+
+#[test]
+fn tray_offers_resolutions() {
+    let menu = TrayMenu::build(&backend()).unwrap();
+    let monitor = &menu.monitors[0];
+    let labels: Vec<String> = (0..monitor.resolutions.len())
+        .filter_map(|i| monitor.resolution_label(i))
+        .collect();
+    assert_eq!(labels, ["3840x2160", "2560x1440 @ 120 Hz", "1920x1080"]);
+    assert!(monitor.is_current_resolution(0));
+    assert!(!monitor.is_current_resolution(1));
+
+    assert_eq!(
+        menu.command(TrayMenu::resolution_command_id(0, 1)),
+        Some(MenuCommand::SetDisplayMode {
+            device_name: DISPLAY1.to_string(),
+            mode: DisplayMode::new(2560, 1440, 32, 120),
+        })
+    );
+    assert_eq!(menu.command(TrayMenu::resolution_command_id(0, 3)), None);
+    assert_eq!(menu.command(TrayMenu::resolution_command_id(1, 0)), None);
+}
+
+#[test]
+fn long_mode_lists_stay_within_their_monitor_command_ids() {
+    // Custom resolutions can give a panel well over 100 of them.
+    let many: Vec<DisplayMode> = (0..150)
+        .map(|i| DisplayMode::new(1280 + 16 * i, 720, 32, 60))
+        .chain((61..200).map(|hz| DisplayMode::new(1280, 720, 32, hz)))
+        .collect();
+    let backend = FakeBackend::new()
+        .with_adapter(
+            FakeAdapter::new(DISPLAY1)
+                .primary()
+                .monitor("Generic PnP Monitor")
+                .modes(&many),
+        )
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor("Generic PnP Monitor")
+                .modes(&modes()),
+        );
+    let menu = TrayMenu::build(&backend).unwrap();
+    let monitor = &menu.monitors[0];
+    assert_eq!(monitor.resolutions.len(), MENU_ITEMS_PER_MONITOR);
+    assert_eq!(monitor.rates.len(), MENU_ITEMS_PER_MONITOR);
+    // The largest resolutions are the ones kept.
+    assert_eq!(monitor.resolutions[0].width, 1280 + 16 * 149);
+
+    let device_name = |command: Option<MenuCommand>| match command {
+        Some(MenuCommand::SetDisplayMode { device_name, .. })
+        | Some(MenuCommand::SetRefreshRate { device_name, .. }) => device_name,
+        other => panic!("unexpected {:?}", other),
+    };
+    let last = MENU_ITEMS_PER_MONITOR - 1;
+    assert_eq!(
+        device_name(menu.command(TrayMenu::resolution_command_id(0, last))),
+        DISPLAY1
+    );
+    assert_eq!(
+        device_name(menu.command(TrayMenu::rate_command_id(0, last))),
+        DISPLAY1
+    );
+    assert_eq!(
+        device_name(menu.command(TrayMenu::resolution_command_id(1, 0))),
+        DISPLAY2
+    );
+    assert_eq!(
+        device_name(menu.command(TrayMenu::rate_command_id(1, 0))),
+        DISPLAY2
+    );
+}
+
+#[test]
+fn monitor_command_ids_never_reach_exit_or_the_next_kind() {
+    // 100 rates on each of more monitors than the menu has IDs for.
+    let rates: Vec<DisplayMode> = (1..=MENU_ITEMS_PER_MONITOR as u32)
+        .map(|hz| DisplayMode::new(1280, 720, 32, hz))
+        .collect();
+    let backend = (0..MENU_MAX_MONITORS + 2).fold(FakeBackend::new(), |backend, i| {
+        backend.with_adapter(
+            FakeAdapter::new(&format!(r"\\.\DISPLAY{}", i + 1))
+                .monitor("Generic PnP Monitor")
+                .modes(&rates),
+        )
+    });
+    let menu = TrayMenu::build(&backend).unwrap();
+    assert_eq!(menu.monitors.len(), MENU_MAX_MONITORS);
+
+    let last_monitor = MENU_MAX_MONITORS - 1;
+    let last_rate = TrayMenu::rate_command_id(last_monitor, MENU_ITEMS_PER_MONITOR - 1);
+    assert_ne!(last_rate, MENU_EXIT_ID);
+    assert!(last_rate < TrayMenu::resolution_command_id(0, 0));
+    assert_eq!(
+        menu.command(last_rate),
+        Some(MenuCommand::SetRefreshRate {
+            device_name: format!(r"\\.\DISPLAY{}", MENU_MAX_MONITORS),
+            rate: (MENU_ITEMS_PER_MONITOR as u32).into(),
+        })
+    );
+    assert_eq!(menu.command(MENU_EXIT_ID), Some(MenuCommand::Exit));
+}
+
+#[test]
+fn resolution_changes_revert_unless_confirmed() {
+    let backend = backend();
+    let mut auto_revert = AutoRevert::new(Duration::from_secs(10));
+    let start = Instant::now();
+
+    let mode = DisplayMode::new(1920, 1080, 32, 144);
+    auto_revert
+        .request_with(
+            &backend,
+            DISPLAY1,
+            RequestedChange::Mode(mode),
+            ChangeOptions::default(),
+            start,
+        )
+        .unwrap();
+    let change = auto_revert.pending().unwrap();
+    assert_eq!(
+        prompt_text(change, Duration::from_secs(10)),
+        "Keep 1920x1080 @ 144 Hz on \\\\.\\DISPLAY1?\nReverting in 10 seconds."
+    );
+
+    let tick = auto_revert.tick_with(&backend, start + Duration::from_secs(10));
+    assert!(matches!(tick, RevertTick::Reverted { result: Ok(_), .. }));
+    assert_eq!(
+        backend.current_mode(DISPLAY1),
+        Ok(DisplayMode::new(3840, 2160, 32, 60))
+    );
+}
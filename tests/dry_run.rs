@@ -0,0 +1,81 @@
+mod common;
+
+use refresh_rate_windows_rs::backend::{DISP_CHANGE_BADMODE, DISP_CHANGE_RESTART};
+use refresh_rate_windows_rs::tray::TrayMenu;
+use refresh_rate_windows_rs::{
+    set_display_refresh_rate_with, set_exact_refresh_rate_with, validate_mode_with, ChangeOptions,
+    ChangeOutcome, DisplayError, DisplayMode, RefreshRate,
+};
+
+use common::{backend, DISPLAY1};
+
+#[test]
+fn validate_mode_asks_the_driver_without_changing_anything() {
+    let backend = backend();
+
+    assert_eq!(
+        validate_mode_with(&backend, DISPLAY1, &DisplayMode::new(2560, 1440, 32, 144)),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(
+        validate_mode_with(&backend, DISPLAY1, &DisplayMode::new(2560, 1440, 32, 75)),
+        Err(DisplayError::BadMode {
+            device_name: DISPLAY1.to_string()
+        })
+    );
+
+    backend.fail_apply(DISPLAY1, DISP_CHANGE_RESTART);
+    assert_eq!(
+        validate_mode_with(&backend, DISPLAY1, &DisplayMode::new(2560, 1440, 32, 144)),
+        Ok(ChangeOutcome::RestartRequired)
+    );
+    assert!(backend.applied().is_empty());
+}
+
+#[test]
+fn dry_runs_leave_the_mode_alone() {
+    let backend = backend();
+
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, DISPLAY1, 144, ChangeOptions::dry_run()),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(120),
+            ChangeOptions::dry_run()
+        ),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert!(backend.applied().is_empty());
+    assert!(backend.applied_rates().is_empty());
+
+    // Dry runs still go through the usual checks.
+    assert!(matches!(
+        set_display_refresh_rate_with(&backend, DISPLAY1, 75, ChangeOptions::dry_run()),
+        Err(DisplayError::UnsupportedMode { .. })
+    ));
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, DISPLAY1, 60, ChangeOptions::dry_run()),
+        Ok(ChangeOutcome::Unchanged)
+    );
+}
+
+#[test]
+fn tray_greys_out_rates_the_driver_rejects() {
+    let backend = backend();
+    // Without the CCD path, rates are validated with CDS_TEST.
+    backend.fail_config(DISPLAY1, 1168);
+    backend.fail_apply(DISPLAY1, DISP_CHANGE_BADMODE);
+
+    let menu = TrayMenu::build(&backend).unwrap();
+    let accepted: Vec<(u32, bool)> = menu.monitors[0]
+        .rates
+        .iter()
+        .map(|support| (support.rate.rounded_hz(), support.accepted))
+        .collect();
+    // The current rate needs no change, so it stays selectable.
+    assert_eq!(accepted, [(60, true), (120, false), (144, false)]);
+}
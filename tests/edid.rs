@@ -0,0 +1,129 @@
+use refresh_rate_windows_rs::edid::{Edid, EdidError, ManufactureDate, RangeLimits};
+use refresh_rate_windows_rs::{
+    get_all_display_devices_with, get_monitor_edid_with, FakeAdapter, FakeBackend,
+};
+
+const DELL_U2720Q: &[u8] = include_bytes!("fixtures/edid/dell_u2720q.bin");
+const ASUS_VG27AQ: &[u8] = include_bytes!("fixtures/edid/asus_vg27aq.bin");
+
+fn with_checksum(mut block: Vec<u8>) -> Vec<u8> {
+    let sum = block[..127].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    block[127] = sum.wrapping_neg();
+    block
+}
+
+#[test]
+fn parses_identification() {
+    let edid = Edid::parse(DELL_U2720Q).unwrap();
+
+    assert_eq!(edid.manufacturer, "DEL");
+    assert_eq!(edid.product_code, 0xA0E6);
+    assert_eq!(edid.hardware_id(), "DELA0E6");
+    assert_eq!(edid.serial_number, 0x4C4B_3231);
+    assert_eq!(
+        edid.manufacture_date,
+        ManufactureDate {
+            week: Some(12),
+            year: 2021,
+            is_model_year: false
+        }
+    );
+    assert_eq!(edid.version, (1, 4));
+    assert_eq!(edid.physical_size_cm, Some((60, 34)));
+    assert_eq!(edid.name.as_deref(), Some("DELL U2720Q"));
+    assert_eq!(edid.serial.as_deref(), Some("7CPR123"));
+    assert!(edid.extensions.is_empty());
+}
+
+#[test]
+fn parses_preferred_timing_and_range_limits() {
+    let edid = Edid::parse(DELL_U2720Q).unwrap();
+    let timing = edid.preferred_timing.unwrap();
+    assert_eq!((timing.h_active, timing.v_active), (3840, 2160));
+    assert_eq!(timing.pixel_clock_khz, 533_250);
+    assert_eq!(timing.refresh_hz(), 60);
+    assert!(!timing.interlaced);
+    assert_eq!(
+        edid.range_limits,
+        Some(RangeLimits {
+            min_vertical_hz: 29,
+            max_vertical_hz: 76,
+            min_horizontal_khz: 30,
+            max_horizontal_khz: 140,
+            max_pixel_clock_mhz: Some(600),
+        })
+    );
+
+    let edid = Edid::parse(ASUS_VG27AQ).unwrap();
+    assert_eq!(edid.preferred_timing.unwrap().refresh_hz(), 144);
+    assert_eq!(edid.range_limits.unwrap().max_vertical_hz, 144);
+}
+
+#[test]
+fn parses_256_byte_blobs_and_model_years() {
+    let edid = Edid::parse(ASUS_VG27AQ).unwrap();
+    assert_eq!(edid.manufacturer, "AUS");
+    assert_eq!(edid.name.as_deref(), Some("VG27AQ"));
+    assert_eq!(edid.serial, None);
+    assert_eq!(
+        edid.manufacture_date,
+        ManufactureDate {
+            week: None,
+            year: 2019,
+            is_model_year: true
+        }
+    );
+    assert_eq!(edid.extensions.len(), 1);
+    assert_eq!(edid.extensions[0][0], 0x02);
+}
+
+#[test]
+fn applies_range_limit_offsets_above_255_hz() {
+    let mut block = DELL_U2720Q.to_vec();
+    // Range limits descriptor at 72: 48..=360 Hz needs the max-rate offset flag.
+    block[72 + 4] = 0x02;
+    block[72 + 5] = 48;
+    block[72 + 6] = (360 - 255) as u8;
+    let edid = Edid::parse(&with_checksum(block)).unwrap();
+
+    let limits = edid.range_limits.unwrap();
+    assert_eq!((limits.min_vertical_hz, limits.max_vertical_hz), (48, 360));
+}
+
+#[test]
+fn rejects_malformed_blobs() {
+    assert_eq!(
+        Edid::parse(&DELL_U2720Q[..100]),
+        Err(EdidError::BadLength(100))
+    );
+
+    let mut bad_header = DELL_U2720Q.to_vec();
+    bad_header[0] = 0x01;
+    assert_eq!(Edid::parse(&bad_header), Err(EdidError::BadHeader));
+
+    let mut bad_checksum = ASUS_VG27AQ.to_vec();
+    bad_checksum[200] ^= 0xFF;
+    assert_eq!(
+        Edid::parse(&bad_checksum),
+        Err(EdidError::BadChecksum { block: 1 })
+    );
+}
+
+#[test]
+fn fetches_edid_per_display_device() {
+    let adapter = FakeAdapter::new(r"\\.\DISPLAY1")
+        .monitor_with_id("Generic PnP Monitor", "DELA0E6")
+        .monitor_with_id("Generic PnP Monitor", "AUS27A1");
+    let dell_path = adapter.monitors[0].interface_path.clone();
+    let backend = FakeBackend::new()
+        .with_edid(&dell_path, DELL_U2720Q)
+        .with_adapter(adapter);
+
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let edid = get_monitor_edid_with(&backend, &devices[0]).unwrap();
+    assert_eq!(edid.name.as_deref(), Some("DELL U2720Q"));
+    assert_eq!(
+        get_monitor_edid_with(&backend, &devices[1]),
+        Err(EdidError::Unavailable)
+    );
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use refresh_rate_windows_rs::edid::displayid::DisplayIdTiming;
+use refresh_rate_windows_rs::edid::{Edid, RefreshRange};
+use refresh_rate_windows_rs::tray::TrayMenu;
+use refresh_rate_windows_rs::{
+    get_all_display_devices_with, get_refresh_rate_report_with, FakeAdapter, FakeBackend,
+    RefreshRate, RefreshRateSupport,
+};
+
+use common::{modes, DISPLAY1, DISPLAY2};
+
+const DELL_U2720Q: &[u8] = include_bytes!("fixtures/edid/dell_u2720q.bin");
+const ASUS_VG27AQ: &[u8] = include_bytes!("fixtures/edid/asus_vg27aq.bin");
+const LG_27GR95QE: &[u8] = include_bytes!("fixtures/edid/lg_27gr95qe.bin");
+/// `ASUS_VG27AQ` with the CTA block's offset byte cleared, leaving stale data
+/// blocks and a detailed timing behind it.
+const CTA_WITHOUT_DATA: &[u8] = include_bytes!("fixtures/edid/cta_without_data.bin");
+
+#[test]
+fn parses_cta_video_timings_and_hdmi_vrr() {
+    let edid = Edid::parse(ASUS_VG27AQ).unwrap();
+    assert_eq!(edid.cta.len(), 1);
+    let cta = &edid.cta[0];
+
+    assert_eq!(cta.revision, 3);
+    let vics: Vec<(u8, bool)> = cta
+        .video_timings
+        .iter()
+        .map(|v| (v.vic, v.native))
+        .collect();
+    assert_eq!(vics, [(16, true), (4, false), (63, false)]);
+    assert_eq!(
+        cta.hdmi_vrr,
+        Some(RefreshRange {
+            min_hz: 48,
+            max_hz: 144
+        })
+    );
+    assert_eq!(cta.freesync, None);
+
+    assert_eq!(cta.detailed_timings.len(), 1);
+    let timing = cta.detailed_timings[0];
+    assert_eq!((timing.h_active, timing.v_active), (2560, 1440));
+    assert_eq!(timing.refresh_hz(), 120);
+}
+
+#[test]
+fn cta_blocks_with_a_zero_offset_hold_no_timings() {
+    let edid = Edid::parse(CTA_WITHOUT_DATA).unwrap();
+    assert_eq!(edid.cta.len(), 1);
+    let cta = &edid.cta[0];
+    assert_eq!(cta.revision, 3);
+    assert!(cta.video_timings.is_empty());
+    assert!(cta.detailed_timings.is_empty());
+    assert_eq!(cta.hdmi_vrr, None);
+}
+
+#[test]
+fn parses_displayid_type_vii_and_adaptive_sync() {
+    let edid = Edid::parse(LG_27GR95QE).unwrap();
+    assert!(edid.cta.is_empty());
+    assert_eq!(edid.displayid.len(), 1);
+    let displayid = &edid.displayid[0];
+
+    assert_eq!(displayid.version, (2, 0));
+    assert_eq!(displayid.timings.len(), 1);
+    let timing = displayid.timings[0];
+    assert_eq!((timing.h_active, timing.v_active), (2560, 1440));
+    assert_eq!(timing.pixel_clock_khz, 995_520);
+    assert_eq!(timing.refresh_millihz(), 240_000);
+    assert!(timing.preferred);
+    assert_eq!(
+        displayid.adaptive_sync,
+        [RefreshRange {
+            min_hz: 48,
+            max_hz: 240
+        }]
+    );
+}
+
+#[test]
+fn displayid_timings_at_the_largest_sizes_do_not_overflow() {
+    // Every size field stored as 0x7FFF, i.e. 0x8000 after adding one.
+    let timing = DisplayIdTiming {
+        pixel_clock_khz: 0x0100_0000 * 10,
+        h_active: 0x8000,
+        h_blanking: 0x8000,
+        v_active: 0x8000,
+        v_blanking: 0x8000,
+        interlaced: false,
+        preferred: false,
+    };
+    // 167772160 kHz over a 65536 x 65536 total.
+    assert_eq!(timing.refresh_millihz(), 39_062);
+}
+
+#[test]
+fn merges_capabilities_across_blocks() {
+    let caps = Edid::parse(LG_27GR95QE).unwrap().refresh_capabilities();
+    let rates: Vec<u32> = caps.timings.iter().map(|t| t.refresh_hz()).collect();
+    // The base block timing stays first; the 240 Hz mode only exists in DisplayID.
+    assert_eq!(rates, [60, 240]);
+    assert_eq!(caps.refresh_range.map(|r| r.max_hz), Some(240));
+    assert_eq!(caps.vrr.map(|r| (r.min_hz, r.max_hz)), Some((48, 240)));
+    assert!(caps.advertises(2560, 1440, 240));
+    assert!(caps.advertises(2560, 1440, 165));
+    assert!(!caps.advertises(2560, 1440, 30));
+    assert!(!caps.advertises(1920, 1080, 120));
+
+    // Without VRR only explicit timings count.
+    let caps = Edid::parse(DELL_U2720Q).unwrap().refresh_capabilities();
+    assert_eq!(caps.vrr, None);
+    assert!(caps.advertises(3840, 2160, 60));
+    assert!(!caps.advertises(3840, 2160, 50));
+}
+
+#[test]
+fn flags_rates_the_panel_does_not_advertise() {
+    let modes = modes(2560, 1440, &[60, 120, 144, 165]);
+    let adapter = FakeAdapter::new(DISPLAY1)
+        .primary()
+        .monitor_with_id("Generic PnP Monitor", "AUS27A1")
+        .modes(&modes);
+    let backend = FakeBackend::new()
+        .with_edid(&adapter.monitors[0].interface_path, ASUS_VG27AQ)
+        .with_adapter(adapter)
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor("Generic PnP Monitor")
+                .modes(&modes),
+        );
+
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let report = get_refresh_rate_report_with(&backend, &devices[0]);
+    assert_eq!(
+        report.rates,
+        [
+            RefreshRateSupport {
+                rate: RefreshRate::integer(60),
+                advertised: Some(true),
+                accepted: true
+            },
+            RefreshRateSupport {
+                rate: RefreshRate::integer(120),
+                advertised: Some(true),
+                accepted: true
+            },
+            RefreshRateSupport {
+                rate: RefreshRate::integer(144),
+                advertised: Some(true),
+                accepted: true
+            },
+            RefreshRateSupport {
+                rate: RefreshRate::integer(165),
+                advertised: Some(false),
+                accepted: true
+            },
+        ]
+    );
+
+    // No EDID: nothing to compare against.
+    let report = get_refresh_rate_report_with(&backend, &devices[1]);
+    assert!(report.capabilities.is_none());
+    assert!(report
+        .rates
+        .iter()
+        .all(|support| support.advertised.is_none()));
+
+    let menu = TrayMenu::build(&backend).unwrap();
+    let monitor = &menu.monitors[0];
+    assert_eq!(
+        monitor.capabilities_label().as_deref(),
+        Some("Panel 48-144 Hz, VRR 48-144 Hz")
+    );
+    assert_eq!(monitor.rate_label(0).as_deref(), Some("60 Hz"));
+    assert_eq!(
+        monitor.rate_label(3).as_deref(),
+        Some("165 Hz (not advertised)")
+    );
+    assert_eq!(menu.monitors[1].capabilities_label(), None);
+}
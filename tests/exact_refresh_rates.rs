@@ -0,0 +1,165 @@
+mod common;
+
+use refresh_rate_windows_rs::backend::{SDC_APPLY, SDC_VALIDATE};
+use refresh_rate_windows_rs::{
+    get_exact_refresh_rates_with, set_exact_refresh_rate_with, ChangeOptions, ChangeOutcome,
+    DisplayError, FakeAdapter, FakeBackend, RefreshRate,
+};
+
+use common::{modes, DISPLAY1};
+
+/// A TV listing 23, 24, 59 and 60 Hz through `EnumDisplaySettingsW`, which are
+/// really 23.976, 24, 59.94 and 60 Hz.
+fn tv_backend() -> FakeBackend {
+    FakeBackend::new().with_adapter(
+        FakeAdapter::new(DISPLAY1)
+            .primary()
+            .monitor("Living Room TV")
+            .modes(&modes(3840, 2160, &[60, 59, 24, 23]))
+            .exact_rates(&[
+                RefreshRate::integer(60),
+                RefreshRate::ntsc(60),
+                RefreshRate::integer(24),
+                RefreshRate::ntsc(24),
+            ])
+            .current_rate(RefreshRate::integer(60)),
+    )
+}
+
+#[test]
+fn refresh_rate_arithmetic_and_display() {
+    let ntsc = RefreshRate::ntsc(60);
+    assert_eq!(ntsc.millihz(), 59_940);
+    assert_eq!(ntsc.dm_frequency(), 59);
+    assert_eq!(ntsc.rounded_hz(), 60);
+    assert!(!ntsc.is_integer());
+    assert_eq!(ntsc, RefreshRate::new(59_940, 1000));
+    assert_ne!(ntsc, RefreshRate::integer(60));
+    assert!(ntsc < RefreshRate::integer(60));
+
+    assert_eq!(ntsc.to_string(), "59.94 Hz");
+    assert_eq!(RefreshRate::ntsc(24).to_string(), "23.976 Hz");
+    assert_eq!(RefreshRate::integer(144).to_string(), "144 Hz");
+    assert_eq!(RefreshRate::new(0, 0).millihz(), 0);
+}
+
+#[test]
+fn lists_integer_and_fractional_variants() {
+    let backend = tv_backend();
+    assert_eq!(
+        get_exact_refresh_rates_with(&backend, DISPLAY1),
+        [
+            RefreshRate::ntsc(24),
+            RefreshRate::integer(24),
+            RefreshRate::ntsc(60),
+            RefreshRate::integer(60),
+        ]
+    );
+
+    // Without the CCD path only the integer rates are known.
+    backend.fail_config(DISPLAY1, 1168);
+    assert_eq!(
+        get_exact_refresh_rates_with(&backend, DISPLAY1),
+        [23, 24, 59, 60].map(RefreshRate::integer)
+    );
+}
+
+#[test]
+fn only_film_and_broadcast_rates_get_ntsc_variants() {
+    // A driver that would wrongly accept 60.94 and 144.86 Hz if asked.
+    let backend = FakeBackend::new().with_adapter(
+        FakeAdapter::new(DISPLAY1)
+            .primary()
+            .modes(&modes(2560, 1440, &[60, 119, 144]))
+            .exact_rates(&[
+                RefreshRate::integer(60),
+                RefreshRate::ntsc(61),
+                RefreshRate::ntsc(120),
+                RefreshRate::integer(144),
+                RefreshRate::ntsc(145),
+            ])
+            .current_rate(RefreshRate::integer(60)),
+    );
+    assert_eq!(
+        get_exact_refresh_rates_with(&backend, DISPLAY1),
+        [
+            RefreshRate::integer(60),
+            RefreshRate::ntsc(120),
+            RefreshRate::integer(144),
+        ]
+    );
+}
+
+#[test]
+fn sets_the_exact_rational_rate() {
+    let backend = tv_backend();
+
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::new(60_000, 1001),
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Applied)
+    );
+    let applied = backend.applied_rates();
+    assert_eq!(applied.len(), 1);
+    assert_eq!(applied[0].rate, RefreshRate::ntsc(60));
+    assert_ne!(applied[0].flags & SDC_APPLY, 0);
+    assert_eq!(applied[0].flags & SDC_VALIDATE, 0);
+
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::new(59_940, 1000),
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::ntsc(30),
+            ChangeOptions::default()
+        ),
+        Err(DisplayError::UnsupportedRefreshRate {
+            device_name: DISPLAY1.to_string(),
+            rate: RefreshRate::ntsc(30),
+        })
+    );
+    assert_eq!(backend.applied_rates().len(), 1);
+}
+
+#[test]
+fn falls_back_to_devmode_for_integer_rates() {
+    let backend = tv_backend();
+    backend.fail_config(DISPLAY1, 1168);
+
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::integer(24),
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(backend.applied()[0].mode.frequency, 24);
+    assert!(backend.applied_rates().is_empty());
+
+    assert_eq!(
+        set_exact_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            RefreshRate::ntsc(24),
+            ChangeOptions::default()
+        ),
+        Err(DisplayError::CurrentModeUnavailable {
+            device_name: DISPLAY1.to_string(),
+            os_error: 1168,
+        })
+    );
+}
@@ -0,0 +1,135 @@
+use refresh_rate_windows_rs::backend::{DISP_CHANGE_BADMODE, DISP_CHANGE_RESTART};
+use refresh_rate_windows_rs::tray::{MenuCommand, TrayMenu, MENU_EXIT_ID};
+use refresh_rate_windows_rs::{
+    get_all_display_devices_with, get_available_refresh_rates_with,
+    get_primary_display_device_name_with, set_display_refresh_rate_with, ChangeOptions,
+    ChangeOutcome, DisplayError, DisplayMode, FakeAdapter, FakeBackend, RefreshRate,
+};
+
+fn mode(width: u32, height: u32, frequency: u32) -> DisplayMode {
+    DisplayMode::new(width, height, 32, frequency)
+}
+
+fn two_monitor_backend() -> FakeBackend {
+    FakeBackend::new()
+        .with_adapter(
+            FakeAdapter::new(r"\\.\DISPLAY1")
+                .primary()
+                .monitor("Dell U2720Q")
+                .modes(&[
+                    mode(2560, 1440, 60),
+                    mode(2560, 1440, 144),
+                    mode(1920, 1080, 60),
+                ]),
+        )
+        .with_adapter(
+            FakeAdapter::new(r"\\.\DISPLAY2")
+                .monitor("Generic PnP Monitor")
+                .modes(&[mode(1920, 1080, 60), mode(1920, 1080, 75)]),
+        )
+        .with_adapter(
+            FakeAdapter::new(r"\\.\DISPLAY3")
+                .detached()
+                .monitor("Unplugged"),
+        )
+}
+
+#[test]
+fn enumerates_attached_monitors_and_their_rates() {
+    let backend = two_monitor_backend();
+
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let names: Vec<_> = devices.iter().map(|d| d.device_name.as_str()).collect();
+    assert_eq!(names, [r"\\.\DISPLAY1", r"\\.\DISPLAY2"]);
+    assert_eq!(devices[0].display_name, "Dell U2720Q");
+    assert_eq!(devices[0].monitor_device_name, r"\\.\DISPLAY1\Monitor0");
+    assert_eq!(devices[0].adapter_name, "Fake Display Adapter");
+    assert!(devices[0].is_primary && devices[0].is_attached);
+    assert!(!devices[1].is_primary);
+    assert_ne!(devices[0].interface_path, devices[1].interface_path);
+
+    assert_eq!(
+        get_available_refresh_rates_with(&backend, r"\\.\DISPLAY1"),
+        [60, 144]
+    );
+    assert_eq!(
+        get_primary_display_device_name_with(&backend).as_deref(),
+        Some(r"\\.\DISPLAY1")
+    );
+}
+
+#[test]
+fn set_refresh_rate_reports_typed_outcomes() {
+    let backend = two_monitor_backend();
+
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY1", 144, ChangeOptions::default()),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(
+        backend.applied().last().unwrap().mode,
+        mode(2560, 1440, 144)
+    );
+
+    // Not listed at the current resolution: refused before reaching the driver.
+    assert!(matches!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY1", 75, ChangeOptions::default()),
+        Err(DisplayError::UnsupportedMode { .. })
+    ));
+    assert_eq!(backend.applied().len(), 1);
+
+    // Already at the requested rate: nothing is applied.
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY1", 144, ChangeOptions::default()),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert_eq!(backend.applied().len(), 1);
+
+    backend.fail_apply(r"\\.\DISPLAY2", DISP_CHANGE_BADMODE);
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY2", 75, ChangeOptions::default()),
+        Err(DisplayError::BadMode {
+            device_name: r"\\.\DISPLAY2".to_string()
+        })
+    );
+
+    backend.fail_apply(r"\\.\DISPLAY2", DISP_CHANGE_RESTART);
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY2", 75, ChangeOptions::default()),
+        Ok(ChangeOutcome::RestartRequired)
+    );
+
+    backend.fail_current_mode(r"\\.\DISPLAY2", 5);
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, r"\\.\DISPLAY2", 75, ChangeOptions::default()),
+        Err(DisplayError::CurrentModeUnavailable {
+            device_name: r"\\.\DISPLAY2".to_string(),
+            os_error: 5
+        })
+    );
+}
+
+#[test]
+fn setupapi_failure_is_reported() {
+    let backend = two_monitor_backend();
+    backend.fail_devnodes(13);
+    assert_eq!(
+        get_all_display_devices_with(&backend).unwrap_err(),
+        DisplayError::SetupApi { os_error: 13 }
+    );
+}
+
+#[test]
+fn tray_menu_resolves_command_ids() {
+    let menu = TrayMenu::build(&two_monitor_backend()).unwrap();
+
+    assert_eq!(
+        menu.command(TrayMenu::rate_command_id(1, 1)),
+        Some(MenuCommand::SetRefreshRate {
+            device_name: r"\\.\DISPLAY2".to_string(),
+            rate: RefreshRate::integer(75)
+        })
+    );
+    assert_eq!(menu.command(MENU_EXIT_ID), Some(MenuCommand::Exit));
+    assert_eq!(menu.command(TrayMenu::rate_command_id(5, 0)), None);
+}
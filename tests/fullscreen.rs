@@ -0,0 +1,170 @@
+mod common;
+
+use refresh_rate_windows_rs::fullscreen::fullscreen_notification_state;
+use refresh_rate_windows_rs::{
+    fullscreen_rates_with, get_all_display_devices_with, ChangeOptions, Config, DisplayBackend,
+    FakeAdapter, FakeBackend, ForcedRate, ForegroundWindow, FullscreenRule, RateOverrides, Rect,
+};
+
+use common::{desktop, modes, DISPLAY1, DISPLAY2};
+
+const GAME: &str = r"C:\Games\Elden Ring\Game\eldenring.exe";
+const NOTEPAD: &str = r"C:\Windows\notepad.exe";
+const PLAYER: &str = r"C:\Program Files\VideoLAN\VLC\vlc.exe";
+
+/// A 2560x1440 monitor at the origin with a 1920x1080 one to its right.
+fn backend() -> FakeBackend {
+    FakeBackend::new()
+        .with_adapter(desktop().monitor_with_id("Dell U2720Q", "DEL4123"))
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor_with_id("LG Ultragear", "GSM5B7F")
+                .modes(&modes(1920, 1080, &[60, 165]))
+                .position(2560, 0),
+        )
+}
+
+fn window(path: &str, bounds: Rect) -> ForegroundWindow {
+    ForegroundWindow {
+        path: Some(path.to_string()),
+        bounds,
+        has_caption: false,
+        fullscreen_state: false,
+    }
+}
+
+fn rule(monitor: Option<&str>, refresh_rate: Option<u32>, exclude: &[&str]) -> FullscreenRule {
+    FullscreenRule {
+        monitor: monitor.map(str::to_string),
+        refresh_rate,
+        exclude: exclude.iter().map(|app| app.to_string()).collect(),
+    }
+}
+
+fn forced(device_name: &str, refresh_rate: u32) -> ForcedRate {
+    ForcedRate {
+        device_name: device_name.to_string(),
+        refresh_rate,
+    }
+}
+
+#[test]
+fn windows_covering_a_monitor_switch_it_to_its_highest_rate() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let rules = [rule(None, None, &[])];
+    let rates =
+        |window: &ForegroundWindow| fullscreen_rates_with(&backend, &rules, window, &devices);
+
+    let side = window(GAME, Rect::new(2560, 0, 4480, 1080));
+    assert_eq!(rates(&side), [forced(DISPLAY2, 165)]);
+    let spanning = window(GAME, Rect::new(0, 0, 4480, 1440));
+    assert_eq!(
+        rates(&spanning),
+        [forced(DISPLAY1, 144), forced(DISPLAY2, 165)]
+    );
+
+    // Maximized windows have a title bar, even where they cover the monitor.
+    let maximized = ForegroundWindow {
+        has_caption: true,
+        ..window(GAME, Rect::new(-8, -8, 2568, 1448))
+    };
+    assert_eq!(rates(&maximized), []);
+    assert_eq!(rates(&window(GAME, Rect::new(0, 0, 2560, 1400))), []);
+
+    // A reported full-screen application lets a covering window keep its
+    // title bar.
+    let exclusive = ForegroundWindow {
+        has_caption: true,
+        fullscreen_state: true,
+        ..window(GAME, Rect::new(2560, 0, 4480, 1080))
+    };
+    assert_eq!(rates(&exclusive), [forced(DISPLAY2, 165)]);
+    assert!(fullscreen_notification_state(3));
+    assert!(!fullscreen_notification_state(4));
+    assert!(!fullscreen_notification_state(5));
+}
+
+#[test]
+fn the_session_full_screen_state_alone_does_not_make_a_window_full_screen() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let rules = [rule(None, None, &[])];
+    let rates =
+        |window: &ForegroundWindow| fullscreen_rates_with(&backend, &rules, window, &devices);
+
+    // E.g. Notepad after alt-tabbing out of a game, while Windows still
+    // reports it as busy.
+    let notepad = ForegroundWindow {
+        has_caption: true,
+        fullscreen_state: true,
+        ..window(NOTEPAD, Rect::new(2600, 100, 3400, 700))
+    };
+    assert_eq!(rates(&notepad), []);
+    let borderless = ForegroundWindow {
+        fullscreen_state: true,
+        ..window(GAME, Rect::new(2600, 100, 3400, 700))
+    };
+    assert_eq!(rates(&borderless), []);
+}
+
+#[test]
+fn rules_are_scoped_per_monitor_and_skip_excluded_apps() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let rules = [
+        rule(Some("GSM5B7F"), Some(60), &["VLC.exe"]),
+        rule(Some("DEL4123"), Some(120), &[r"C:\Games\**"]),
+    ];
+    let everywhere = Rect::new(0, 0, 4480, 1440);
+
+    assert_eq!(
+        fullscreen_rates_with(&backend, &rules, &window(GAME, everywhere), &devices),
+        [forced(DISPLAY2, 60)]
+    );
+    assert_eq!(
+        fullscreen_rates_with(&backend, &rules, &window(PLAYER, everywhere), &devices),
+        [forced(DISPLAY1, 120)]
+    );
+    assert_eq!(
+        fullscreen_rates_with(
+            &backend,
+            &rules[..1],
+            &window(GAME, Rect::new(0, 0, 2560, 1440)),
+            &devices
+        ),
+        []
+    );
+}
+
+#[test]
+fn the_previous_rate_comes_back_when_full_screen_ends() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let rules = [rule(Some("DEL4123"), None, &[])];
+    let mut overrides = RateOverrides::new();
+    let options = ChangeOptions::default();
+
+    let game = window(GAME, Rect::new(0, 0, 2560, 1440));
+    let forced = fullscreen_rates_with(&backend, &rules, &game, &devices);
+    overrides.sync_with(&backend, &forced, options);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+
+    overrides.sync_with(&backend, &[], options);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 60);
+}
+
+#[test]
+fn rules_are_read_from_the_config_file() {
+    let text = "version = 1\n\n[[fullscreen]]\nmonitor = \"DEL4123\"\nexclude = [\"vlc.exe\", 'C:\\Tools\\*.exe']\n\n[[fullscreen]]\nrefresh_rate = 120\n";
+    let config = Config::from_toml(text).unwrap();
+    assert_eq!(
+        config.fullscreen,
+        [
+            rule(Some("DEL4123"), None, &["vlc.exe", r"C:\Tools\*.exe"]),
+            rule(None, Some(120), &[])
+        ]
+    );
+    assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+    assert!(Config::from_toml("version = 1\n[[fullscreen]]\nexclude = \"vlc.exe\"\n").is_err());
+}
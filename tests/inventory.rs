@@ -0,0 +1,53 @@
+mod common;
+
+use refresh_rate_windows_rs::{FakeAdapter, FakeBackend, InventoryCache};
+
+use common::{modes, DISPLAY1, DISPLAY2};
+
+fn adapter(device_name: &str) -> FakeAdapter {
+    FakeAdapter::new(device_name)
+        .monitor("Generic PnP Monitor")
+        .modes(&modes(2560, 1440, &[60, 144]))
+}
+
+#[test]
+fn serves_the_cached_inventory_until_invalidated() {
+    let backend = FakeBackend::new().with_adapter(adapter(DISPLAY1).primary());
+    let cache = InventoryCache::new();
+    assert!(cache.get().is_none());
+    assert!(cache.is_stale());
+
+    let menu = cache.get_or_refresh_with(&backend).unwrap();
+    assert_eq!(menu.monitors.len(), 1);
+    assert!(!cache.is_stale());
+
+    // Changes go unnoticed until a notification invalidates the cache.
+    backend.add_adapter(adapter(DISPLAY2));
+    assert_eq!(
+        cache.get_or_refresh_with(&backend).unwrap().monitors.len(),
+        1
+    );
+
+    cache.invalidate();
+    assert!(cache.is_stale());
+    // The stale copy stays readable while it is rebuilt.
+    assert_eq!(cache.get().unwrap().monitors.len(), 1);
+    assert_eq!(
+        cache.get_or_refresh_with(&backend).unwrap().monitors.len(),
+        2
+    );
+    assert!(!cache.is_stale());
+}
+
+#[test]
+fn keeps_the_previous_inventory_when_a_rebuild_fails() {
+    let backend = FakeBackend::new().with_adapter(adapter(DISPLAY1).primary());
+    let cache = InventoryCache::new();
+    cache.refresh_with(&backend).unwrap();
+
+    backend.fail_devnodes(5);
+    cache.invalidate();
+    assert!(cache.refresh_with(&backend).is_err());
+    assert!(cache.is_stale());
+    assert_eq!(cache.get().unwrap().monitors.len(), 1);
+}
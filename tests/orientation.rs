@@ -0,0 +1,140 @@
+mod common;
+
+use refresh_rate_windows_rs::backend::DISP_CHANGE_BADMODE;
+use refresh_rate_windows_rs::tray::{MenuCommand, TrayMenu};
+use refresh_rate_windows_rs::{
+    get_display_orientation_with, set_display_orientation_with, set_display_refresh_rate_with,
+    ChangeOptions, ChangeOutcome, DisplayBackend, DisplayError, DisplayMode, FakeAdapter,
+    FakeBackend, Orientation,
+};
+
+use common::{modes, DISPLAY1};
+
+fn backend() -> FakeBackend {
+    FakeBackend::new().with_adapter(
+        FakeAdapter::new(DISPLAY1)
+            .primary()
+            .monitor("Generic PnP Monitor")
+            .modes(&modes(2560, 1440, &[60, 144])),
+    )
+}
+
+#[test]
+fn orientation_degrees_and_rotation() {
+    let degrees: Vec<u32> = Orientation::ALL.iter().map(|o| o.degrees()).collect();
+    assert_eq!(degrees, [0, 90, 180, 270]);
+    assert_eq!(
+        Orientation::from_degrees(270),
+        Some(Orientation::PortraitFlipped)
+    );
+    assert_eq!(Orientation::from_degrees(450), Some(Orientation::Portrait));
+    assert_eq!(Orientation::from_degrees(45), None);
+
+    let landscape = DisplayMode::new(2560, 1440, 32, 60);
+    let portrait = landscape.rotated(Orientation::Portrait);
+    assert_eq!((portrait.width, portrait.height), (1440, 2560));
+    let flipped = portrait.rotated(Orientation::PortraitFlipped);
+    assert_eq!((flipped.width, flipped.height), (1440, 2560));
+    assert_eq!(flipped.rotated(Orientation::LandscapeFlipped).width, 2560);
+}
+
+#[test]
+fn rotates_and_swaps_width_and_height() {
+    let backend = backend();
+
+    assert_eq!(
+        set_display_orientation_with(
+            &backend,
+            DISPLAY1,
+            Orientation::Portrait,
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Applied)
+    );
+    let current = backend.current_mode(DISPLAY1).unwrap();
+    assert_eq!((current.width, current.height), (1440, 2560));
+    assert_eq!(
+        get_display_orientation_with(&backend, DISPLAY1),
+        Ok(Orientation::Portrait)
+    );
+
+    // Refresh rate changes keep working in portrait.
+    assert_eq!(
+        set_display_refresh_rate_with(&backend, DISPLAY1, 144, ChangeOptions::default()),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(
+        backend.current_mode(DISPLAY1).unwrap().orientation,
+        Orientation::Portrait
+    );
+
+    assert_eq!(
+        set_display_orientation_with(
+            &backend,
+            DISPLAY1,
+            Orientation::Portrait,
+            ChangeOptions::default()
+        ),
+        Ok(ChangeOutcome::Unchanged)
+    );
+    assert_eq!(
+        set_display_orientation_with(
+            &backend,
+            DISPLAY1,
+            Orientation::LandscapeFlipped,
+            ChangeOptions::dry_run()
+        ),
+        Ok(ChangeOutcome::Validated)
+    );
+    assert_eq!(backend.applied().len(), 2);
+}
+
+#[test]
+fn rejected_rotations_change_nothing() {
+    let backend = backend();
+    backend.fail_apply(DISPLAY1, DISP_CHANGE_BADMODE);
+
+    assert_eq!(
+        set_display_orientation_with(
+            &backend,
+            DISPLAY1,
+            Orientation::Portrait,
+            ChangeOptions::default()
+        ),
+        Err(DisplayError::BadMode {
+            device_name: DISPLAY1.to_string()
+        })
+    );
+    assert!(backend.applied().is_empty());
+
+    let menu = TrayMenu::build(&backend).unwrap();
+    let accepted: Vec<bool> = menu.monitors[0]
+        .orientations
+        .iter()
+        .map(|support| support.accepted)
+        .collect();
+    // The current orientation needs no change, so it stays selectable.
+    assert_eq!(accepted, [true, false, false, false]);
+}
+
+#[test]
+fn tray_offers_orientations() {
+    let menu = TrayMenu::build(&backend()).unwrap();
+    let monitor = &menu.monitors[0];
+    assert_eq!(
+        monitor.orientation_label(1).as_deref(),
+        Some("90° Portrait")
+    );
+    assert_eq!(
+        monitor.orientation_label(2).as_deref(),
+        Some("180° Landscape (flipped)")
+    );
+    assert_eq!(
+        menu.command(TrayMenu::orientation_command_id(0, 3)),
+        Some(MenuCommand::SetOrientation {
+            device_name: DISPLAY1.to_string(),
+            orientation: Orientation::PortraitFlipped,
+        })
+    );
+    assert_eq!(menu.command(TrayMenu::orientation_command_id(0, 4)), None);
+}
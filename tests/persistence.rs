@@ -0,0 +1,66 @@
+mod common;
+
+use refresh_rate_windows_rs::backend::{
+    CDS_GLOBAL, CDS_UPDATEREGISTRY, SDC_APPLY, SDC_SAVE_TO_DATABASE,
+};
+use refresh_rate_windows_rs::tray::{MenuCommand, TrayMenu};
+use refresh_rate_windows_rs::{
+    set_display_refresh_rate_with, set_exact_refresh_rate_with, ChangeOptions, Persistence,
+    RefreshRate,
+};
+
+use common::{backend, DISPLAY1};
+
+#[test]
+fn persistence_selects_change_flags() {
+    let backend = backend();
+
+    for (rate, persistence) in [120, 144, 60].into_iter().zip(Persistence::ALL) {
+        set_display_refresh_rate_with(
+            &backend,
+            DISPLAY1,
+            rate,
+            ChangeOptions::with_persistence(persistence),
+        )
+        .unwrap();
+    }
+    let flags: Vec<u32> = backend.applied().iter().map(|c| c.flags).collect();
+    assert_eq!(
+        flags,
+        [0, CDS_UPDATEREGISTRY, CDS_UPDATEREGISTRY | CDS_GLOBAL]
+    );
+
+    set_exact_refresh_rate_with(
+        &backend,
+        DISPLAY1,
+        RefreshRate::integer(144),
+        ChangeOptions::with_persistence(Persistence::Persistent),
+    )
+    .unwrap();
+    let applied = backend.applied_rates();
+    assert_eq!(applied.len(), 1);
+    assert_eq!(
+        applied[0].flags & (SDC_APPLY | SDC_SAVE_TO_DATABASE),
+        SDC_APPLY | SDC_SAVE_TO_DATABASE
+    );
+}
+
+#[test]
+fn persistence_round_trips_through_its_config_name() {
+    for persistence in Persistence::ALL {
+        assert_eq!(persistence.to_string().parse(), Ok(persistence));
+    }
+    assert_eq!("Persistent".parse(), Ok(Persistence::Persistent));
+    assert!("forever".parse::<Persistence>().is_err());
+    assert_eq!(Persistence::default(), Persistence::Temporary);
+}
+
+#[test]
+fn tray_resolves_persistence_commands() {
+    let menu = TrayMenu::build(&backend()).unwrap();
+    assert_eq!(
+        menu.command(TrayMenu::persistence_command_id(2)),
+        Some(MenuCommand::SetPersistence(Persistence::Global))
+    );
+    assert_eq!(menu.command(TrayMenu::persistence_command_id(3)), None);
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use refresh_rate_windows_rs::{
+    ChangeOptions, Config, DisplayBackend, FakeAdapter, FakeBackend, ForcedRate, PowerRule,
+    PowerRules, PowerSource, RateOverrides,
+};
+
+use common::{desktop, desktop_modes, DISPLAY1, DISPLAY2};
+
+fn backend() -> FakeBackend {
+    let modes = desktop_modes();
+    FakeBackend::new()
+        .with_adapter(
+            desktop()
+                .monitor_with_id("Laptop Panel", "BOE0A1C")
+                .current(modes[2]),
+        )
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor_with_id("Dell U2720Q", "DEL4123")
+                .modes(&modes),
+        )
+}
+
+fn rules() -> PowerRules {
+    PowerRules::new(vec![
+        PowerRule {
+            monitor: "BOE0A1C".to_string(),
+            ac: Some(144),
+            battery: Some(60),
+        },
+        PowerRule {
+            monitor: "DEL4123".to_string(),
+            ac: Some(120),
+            battery: None,
+        },
+    ])
+}
+
+#[test]
+fn ac_line_status_maps_to_a_source() {
+    assert_eq!(
+        PowerSource::from_ac_line_status(0),
+        Some(PowerSource::Battery)
+    );
+    assert_eq!(PowerSource::from_ac_line_status(1), Some(PowerSource::Ac));
+    assert_eq!(PowerSource::from_ac_line_status(255), None);
+}
+
+#[test]
+fn only_source_changes_switch_rates() {
+    let mut rules = rules();
+
+    // The first source seen applies its rates.
+    let ac = rules.update(PowerSource::Ac).unwrap();
+    assert_eq!(ac.monitors.len(), 2);
+    assert_eq!(rules.update(PowerSource::Ac), None);
+
+    // Monitors without a battery rate are left alone.
+    let battery = rules.update(PowerSource::Battery).unwrap();
+    assert_eq!(battery.monitors.len(), 1);
+    assert_eq!(battery.monitors[0].monitor, "BOE0A1C");
+    assert_eq!(battery.monitors[0].refresh_rate, Some(60));
+    assert_eq!(rules.source(), Some(PowerSource::Battery));
+
+    assert!(PowerRules::default().update(PowerSource::Ac).is_none());
+}
+
+#[test]
+fn unplugging_and_plugging_in_apply_the_configured_rates() {
+    let backend = backend();
+    let mut rules = rules();
+    let options = ChangeOptions::default();
+    let mut overrides = RateOverrides::new();
+
+    let report = rules
+        .apply_with(&backend, PowerSource::Battery, options, &mut overrides)
+        .unwrap();
+    assert!(report.is_success(), "{}", report);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 60);
+    assert_eq!(backend.current_mode(DISPLAY2).unwrap().frequency, 60);
+
+    assert!(rules
+        .apply_with(&backend, PowerSource::Battery, options, &mut overrides)
+        .is_none());
+    let commits = backend.commits();
+
+    rules
+        .apply_with(&backend, PowerSource::Ac, options, &mut overrides)
+        .unwrap();
+    assert_eq!(backend.commits(), commits + 1);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+    assert_eq!(backend.current_mode(DISPLAY2).unwrap().frequency, 120);
+}
+
+#[test]
+fn forced_monitors_get_the_power_rate_once_released() {
+    let backend = backend();
+    let mut rules = rules();
+    let options = ChangeOptions::default();
+    let mut overrides = RateOverrides::new();
+    rules.apply_with(&backend, PowerSource::Ac, options, &mut overrides);
+
+    let game = ForcedRate {
+        device_name: DISPLAY1.to_string(),
+        refresh_rate: 120,
+    };
+    overrides.sync_with(&backend, &[game], options);
+
+    // Unplugged mid-game: the game keeps its rate, battery comes after it.
+    rules
+        .apply_with(&backend, PowerSource::Battery, options, &mut overrides)
+        .unwrap();
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 120);
+    assert_eq!(overrides.active()[0].previous, 60);
+
+    overrides.sync_with(&backend, &[], options);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 60);
+}
+
+#[test]
+fn rules_are_read_from_the_config_file() {
+    let text = "version = 1\n\n[[power]]\nmonitor = \"BOE0A1C\"\nac = 144\nbattery = 60\n\n[[power]]\nmonitor = \"DEL4123\"\nac = 120\n";
+    let config = Config::from_toml(text).unwrap();
+    assert_eq!(config.power, rules().rules());
+    assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+    assert!(
+        Config::from_toml("version = 1\n\n[[power]]\nmonitor = \"DEL4123\"\nac = \"fast\"\n")
+            .is_err()
+    );
+}
@@ -0,0 +1,215 @@
+mod common;
+
+use refresh_rate_windows_rs::profile::profiles_from_toml;
+use refresh_rate_windows_rs::tray::{MenuCommand, TrayMenu};
+use refresh_rate_windows_rs::{
+    get_all_display_devices_with, ChangeOptions, ChangeOutcome, DisplayBackend, DisplayError,
+    DisplayMode, FakeAdapter, FakeBackend, FormatError, Profile, ProfileMonitor,
+};
+
+use common::{desktop, desktop_modes, modes, DISPLAY1, DISPLAY2};
+
+const CONFIG: &str = r#"
+[[profiles]]
+name = "Gaming"
+
+[[profiles.monitors]]
+monitor = "DEL4123"
+refresh_rate = 144
+
+[[profiles.monitors]]
+monitor = "GSM5B7F"
+resolution = "1280x720"
+
+[[profiles]]
+name = "Office"
+
+[[profiles.monitors]]
+monitor = "del4123"
+refresh_rate = 60
+"#;
+
+fn backend() -> FakeBackend {
+    let mut side_modes = modes(1920, 1080, &[60, 75]);
+    side_modes.extend(modes(1280, 720, &[60]));
+    FakeBackend::new()
+        .with_adapter(desktop().monitor_with_id("Dell U2720Q", "DEL4123"))
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor_with_id("LG Ultragear", "GSM5B7F")
+                .modes(&side_modes)
+                .current(side_modes[1]),
+        )
+}
+
+#[test]
+fn profiles_are_read_from_the_config_file() {
+    let profiles = profiles_from_toml(CONFIG).unwrap();
+
+    assert_eq!(profiles.len(), 2);
+    assert_eq!(profiles[0].name, "Gaming");
+    assert_eq!(
+        profiles[0].monitors[1],
+        ProfileMonitor {
+            monitor: "GSM5B7F".to_string(),
+            resolution: Some((1280, 720)),
+            refresh_rate: None,
+        }
+    );
+    assert_eq!(profiles_from_toml(""), Ok(Vec::new()));
+
+    let duplicate = CONFIG.replace("\"Office\"", "\"gaming\"");
+    assert_eq!(
+        profiles_from_toml(&duplicate),
+        Err(FormatError::InvalidField {
+            path: "profiles[1].name".to_string(),
+            expected: "a unique name",
+        })
+    );
+    let bad_resolution = CONFIG.replace("1280x720", "720p");
+    assert_eq!(
+        profiles_from_toml(&bad_resolution),
+        Err(FormatError::InvalidField {
+            path: "profiles[0].monitors[1].resolution".to_string(),
+            expected: "like \"1920x1080\"",
+        })
+    );
+}
+
+#[test]
+fn monitors_match_by_stable_identifiers() {
+    let backend = backend();
+    let devices = get_all_display_devices_with(&backend).unwrap();
+    let entry = |monitor: &str| ProfileMonitor {
+        monitor: monitor.to_string(),
+        resolution: None,
+        refresh_rate: None,
+    };
+
+    assert!(entry("DEL4123").matches(&devices[0]));
+    assert!(entry(&devices[0].interface_path.to_lowercase()).matches(&devices[0]));
+    assert!(entry(&devices[0].device_id).matches(&devices[0]));
+    assert!(!entry("DEL4123").matches(&devices[1]));
+    assert!(!entry(DISPLAY1).matches(&devices[0]));
+}
+
+#[test]
+fn profiles_apply_every_monitor_in_one_commit() {
+    let backend = backend();
+    let profiles = profiles_from_toml(CONFIG).unwrap();
+    let options = ChangeOptions::default();
+
+    let report = profiles[0].apply_with(&backend, options);
+    assert!(report.is_success(), "{}", report);
+    let devices: Vec<_> = report
+        .monitors
+        .iter()
+        .map(|m| m.device_name.as_deref())
+        .collect();
+    assert_eq!(devices, [Some(DISPLAY1), Some(DISPLAY2)]);
+    assert_eq!(backend.commits(), 1);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+    assert_eq!(
+        backend.current_mode(DISPLAY2).unwrap(),
+        DisplayMode::new(1280, 720, 32, 60)
+    );
+
+    // Applying it again changes nothing.
+    let report = profiles[0].apply_with(&backend, options);
+    assert!(report
+        .monitors
+        .iter()
+        .all(|m| m.result == Ok(ChangeOutcome::Unchanged)));
+    assert_eq!(backend.commits(), 1);
+}
+
+#[test]
+fn identical_monitors_are_matched_once_each() {
+    let backend = FakeBackend::new()
+        .with_adapter(desktop().monitor_with_id("Dell U2720Q", "DEL4123"))
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor_with_id("Dell U2720Q", "DEL4123")
+                .modes(&desktop_modes()),
+        );
+    let entry = |refresh_rate| ProfileMonitor {
+        monitor: "DEL4123".to_string(),
+        resolution: None,
+        refresh_rate: Some(refresh_rate),
+    };
+    let profile = Profile {
+        name: "Twins".to_string(),
+        monitors: vec![entry(144), entry(120), entry(60)],
+    };
+
+    let report = profile.apply_with(&backend, ChangeOptions::default());
+    let devices: Vec<_> = report
+        .monitors
+        .iter()
+        .map(|m| m.device_name.as_deref())
+        .collect();
+    assert_eq!(devices, [Some(DISPLAY1), Some(DISPLAY2), None]);
+    assert_eq!(
+        report.monitors[2].result,
+        Err(DisplayError::MonitorNotFound {
+            monitor: "DEL4123".to_string()
+        })
+    );
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 144);
+    assert_eq!(backend.current_mode(DISPLAY2).unwrap().frequency, 120);
+}
+
+#[test]
+fn failures_are_reported_per_monitor() {
+    let backend = backend();
+    let profile = Profile {
+        name: "Travel".to_string(),
+        monitors: vec![
+            ProfileMonitor {
+                monitor: "SAM0F9E".to_string(),
+                resolution: None,
+                refresh_rate: Some(60),
+            },
+            ProfileMonitor {
+                monitor: "GSM5B7F".to_string(),
+                resolution: None,
+                refresh_rate: Some(240),
+            },
+            ProfileMonitor {
+                monitor: "DEL4123".to_string(),
+                resolution: None,
+                refresh_rate: Some(120),
+            },
+        ],
+    };
+
+    let report = profile.apply_with(&backend, ChangeOptions::default());
+    assert_eq!(
+        report.monitors[0].result,
+        Err(DisplayError::MonitorNotFound {
+            monitor: "SAM0F9E".to_string()
+        })
+    );
+    assert!(matches!(
+        report.monitors[1].result,
+        Err(DisplayError::UnsupportedMode { .. })
+    ));
+    assert_eq!(report.monitors[2].result, Ok(ChangeOutcome::Applied));
+    assert_eq!(report.failures().count(), 2);
+    assert_eq!(backend.current_mode(DISPLAY1).unwrap().frequency, 120);
+}
+
+#[test]
+fn tray_lists_profiles() {
+    let profiles = profiles_from_toml(CONFIG).unwrap();
+    let menu = TrayMenu::build(&backend())
+        .unwrap()
+        .with_profiles(&profiles);
+
+    assert_eq!(menu.profiles, ["Gaming", "Office"]);
+    assert_eq!(
+        menu.command(TrayMenu::profile_command_id(1)),
+        Some(MenuCommand::ApplyProfile("Office".to_string()))
+    );
+    assert_eq!(menu.command(TrayMenu::profile_command_id(2)), None);
+}
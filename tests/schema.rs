@@ -0,0 +1,126 @@
+mod common;
+
+use refresh_rate_windows_rs::cli::{main_with, EXIT_MONITOR_NOT_FOUND, EXIT_SUCCESS};
+use refresh_rate_windows_rs::schema::{Record, SCHEMA_VERSION};
+use refresh_rate_windows_rs::{
+    get_all_display_devices_with, DisplayMode, FakeAdapter, FakeBackend, RefreshRate,
+};
+use toml::Value;
+
+use common::{tv, DISPLAY1, DISPLAY2};
+
+fn backend() -> FakeBackend {
+    FakeBackend::new()
+        .with_adapter(
+            FakeAdapter::new(DISPLAY1)
+                .primary()
+                .monitor_with_id("Dell U2720Q", "DEL4123")
+                .modes(&[DisplayMode::new(2560, 1440, 32, 144)]),
+        )
+        .with_adapter(
+            tv(DISPLAY2)
+                .monitor("Living Room TV")
+                .position(2560, 0)
+                .current(DisplayMode::new(3840, 2160, 32, 59))
+                .current_rate(RefreshRate::ntsc(60)),
+        )
+}
+
+/// Runs a command line and parses every line it wrote to stdout and stderr.
+fn run(backend: &FakeBackend, args: &str) -> (i32, Vec<Value>, Vec<Value>) {
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let code = main_with(backend, args.split_whitespace(), &mut out, &mut err);
+    let records = |bytes: Vec<u8>| {
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+    (code, records(out), records(err))
+}
+
+#[test]
+fn every_record_carries_the_schema_version_and_type() {
+    let record = Record::Primary {
+        device_name: DISPLAY1,
+    };
+    assert_eq!(
+        record.to_json(),
+        r#"{"schema":1,"type":"primary","device_name":"\\\\.\\DISPLAY1"}"#
+    );
+
+    // Optional fields are left out rather than `null`.
+    let devices = get_all_display_devices_with(&backend()).unwrap();
+    let record = Record::Monitor {
+        index: 1,
+        device: &devices[0],
+        mode: None,
+        rate: None,
+        position: None,
+    };
+    let json = record.to_json();
+    assert!(!json.contains("mode") && !json.contains("null"), "{}", json);
+
+    for command in ["list", "get", "modes 2", "primary", "set 1 144"] {
+        let (code, records, _) = run(&backend(), &format!("{} --json", command));
+        assert_eq!(code, EXIT_SUCCESS, "{}", command);
+        assert!(!records.is_empty(), "{}", command);
+        for record in records {
+            assert_eq!(record["schema"].as_integer(), Some(SCHEMA_VERSION));
+            assert!(record["type"].is_str());
+        }
+    }
+}
+
+#[test]
+fn listings_write_one_record_per_line() {
+    let (_, monitors, _) = run(&backend(), "list --json");
+    assert_eq!(monitors.len(), 2);
+    let dell = &monitors[0];
+    assert_eq!(dell["type"].as_str(), Some("monitor"));
+    assert_eq!(dell["index"].as_integer(), Some(1));
+    assert_eq!(dell["device"]["device_name"].as_str(), Some(DISPLAY1));
+    assert_eq!(dell["device"]["hardware_id"].as_str(), Some("DEL4123"));
+    assert_eq!(dell["device"]["primary"].as_bool(), Some(true));
+    assert_eq!(dell["mode"]["width"].as_integer(), Some(2560));
+    assert_eq!(dell["mode"]["orientation"].as_integer(), Some(0));
+    assert_eq!(dell["rate"]["hz"].as_float(), Some(144.0));
+    assert_eq!(dell["position"]["x"].as_integer(), Some(0));
+    assert_eq!(monitors[1]["position"]["x"].as_integer(), Some(2560));
+
+    let (_, rates, _) = run(&backend(), "modes 2 --json");
+    let listed: Vec<_> = rates
+        .iter()
+        .map(|r| (r["rate"]["hz"].as_float(), r["current"].as_bool()))
+        .collect();
+    assert_eq!(
+        listed,
+        [(Some(59.94), Some(true)), (Some(60.0), Some(false))]
+    );
+    assert_eq!(rates[0]["rate"]["denominator"].as_integer(), Some(1001));
+}
+
+#[test]
+fn changes_and_errors_are_records_too() {
+    let backend = backend();
+
+    let (_, change, _) = run(&backend, "set 2 60 --json");
+    assert_eq!(change[0]["type"].as_str(), Some("change"));
+    assert_eq!(change[0]["device_name"].as_str(), Some(DISPLAY2));
+    assert_eq!(change[0]["outcome"].as_str(), Some("applied"));
+
+    let (code, out, err) = run(&backend, "get DISPLAY7 --json");
+    assert_eq!(code, EXIT_MONITOR_NOT_FOUND);
+    assert!(out.is_empty());
+    assert_eq!(err[0]["type"].as_str(), Some("error"));
+    assert_eq!(
+        err[0]["exit_code"].as_integer(),
+        Some(EXIT_MONITOR_NOT_FOUND.into())
+    );
+    assert_eq!(
+        err[0]["message"].as_str(),
+        Some("monitor DISPLAY7 is not connected")
+    );
+}
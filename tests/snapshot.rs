@@ -0,0 +1,121 @@
+mod common;
+
+use refresh_rate_windows_rs::{
+    set_display_refresh_rate_with, ChangeOptions, ChangeOutcome, DisplayBatch, DisplayError,
+    DisplayMode, DisplaySnapshot, FakeAdapter, FakeBackend, FormatError, Orientation, Position,
+    RefreshRate,
+};
+
+use common::{tv, DISPLAY1, DISPLAY2};
+
+/// A TV at 59.94 Hz with a portrait monitor to its right.
+fn backend() -> FakeBackend {
+    let monitor_mode = DisplayMode::new(1920, 1080, 32, 60);
+    FakeBackend::new()
+        .with_adapter(
+            tv(DISPLAY1)
+                .primary()
+                .monitor("Living Room TV")
+                .current(DisplayMode::new(3840, 2160, 32, 59))
+                .current_rate(RefreshRate::ntsc(60)),
+        )
+        .with_adapter(
+            FakeAdapter::new(DISPLAY2)
+                .monitor("Generic PnP Monitor")
+                .modes(&[monitor_mode])
+                .current(monitor_mode.rotated(Orientation::Portrait))
+                .position(3840, 0),
+        )
+}
+
+#[test]
+fn capture_records_every_monitor() {
+    let snapshot = DisplaySnapshot::capture_with(&backend()).unwrap();
+
+    assert_eq!(snapshot.monitors.len(), 2);
+    let tv = &snapshot.monitors[0];
+    assert_eq!(tv.device_name, DISPLAY1);
+    assert_eq!(tv.display_name, "Living Room TV");
+    assert_eq!(tv.rate, Some(RefreshRate::ntsc(60)));
+    assert!(tv.primary);
+    let side = &snapshot.monitors[1];
+    assert_eq!(side.position, Position::new(3840, 0));
+    assert_eq!(side.mode.orientation, Orientation::Portrait);
+    assert_eq!((side.mode.width, side.mode.height), (1080, 1920));
+    assert!(!side.primary);
+}
+
+#[test]
+fn snapshots_round_trip_through_toml_and_json() {
+    let snapshot = DisplaySnapshot::capture_with(&backend()).unwrap();
+
+    let toml = snapshot.to_toml();
+    assert!(toml.starts_with("version = 1\n"), "{}", toml);
+    assert_eq!(DisplaySnapshot::from_toml(&toml), Ok(snapshot.clone()));
+    assert_eq!(
+        DisplaySnapshot::from_json(&snapshot.to_json()),
+        Ok(snapshot.clone())
+    );
+
+    assert_eq!(
+        DisplaySnapshot::from_toml("version = 2"),
+        Err(FormatError::UnsupportedVersion(2))
+    );
+    let broken = toml.replacen("orientation = 90", "orientation = 45", 1);
+    assert_eq!(
+        DisplaySnapshot::from_toml(&broken),
+        Err(FormatError::InvalidField {
+            path: "monitors[1].mode.orientation".to_string(),
+            expected: "0, 90, 180 or 270",
+        })
+    );
+    match DisplaySnapshot::from_json("{\"version\": 1,\n \"monitors\": null}") {
+        Err(FormatError::Json { line, column, .. }) => assert_eq!((line, column), (2, 17)),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn restore_puts_back_modes_positions_primary_and_exact_rates() {
+    let backend = backend();
+    let snapshot = DisplaySnapshot::capture_with(&backend).unwrap();
+
+    set_display_refresh_rate_with(&backend, DISPLAY1, 60, ChangeOptions::default()).unwrap();
+    let monitor_mode = DisplayMode::new(1920, 1080, 32, 60);
+    DisplayBatch::new()
+        .layout(DISPLAY2, monitor_mode, Position::new(0, 0), true)
+        .layout(
+            DISPLAY1,
+            DisplayMode::new(3840, 2160, 32, 60),
+            Position::new(-3840, 0),
+            false,
+        )
+        .apply_with(&backend, ChangeOptions::default())
+        .unwrap();
+    assert_ne!(DisplaySnapshot::capture_with(&backend).unwrap(), snapshot);
+
+    assert_eq!(
+        snapshot.restore_with(&backend, ChangeOptions::default()),
+        Ok(ChangeOutcome::Applied)
+    );
+    assert_eq!(DisplaySnapshot::capture_with(&backend).unwrap(), snapshot);
+    assert_eq!(
+        snapshot.restore_with(&backend, ChangeOptions::default()),
+        Ok(ChangeOutcome::Unchanged)
+    );
+}
+
+#[test]
+fn restore_fails_before_changing_anything_when_a_monitor_is_missing() {
+    let backend = backend();
+    let snapshot = DisplaySnapshot::capture_with(&backend).unwrap();
+    backend.remove_adapter(DISPLAY2);
+
+    assert_eq!(
+        snapshot.restore_with(&backend, ChangeOptions::default()),
+        Err(DisplayError::MonitorNotFound {
+            monitor: "Generic PnP Monitor".to_string()
+        })
+    );
+    assert!(backend.applied().is_empty());
+}